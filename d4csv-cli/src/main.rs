@@ -0,0 +1,296 @@
+//! The same pipeline as the web app, from the command line. Reads a CSV,
+//! writes the better CSV and the report, and complains on stderr.
+
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::process::ExitCode;
+use d4csv_core::context::{ContextInputData, SalesContext};
+use d4csv_core::error::D4Error;
+use d4csv_core::i18n::{Lang, tr, trf};
+use d4csv_core::loader::{Loader, LoadSource, LoadStep, QuoteChar};
+use d4csv_core::report::ReportTemplate;
+use d4csv_core::sale::columns::Column;
+use d4csv_core::sale::dedup::DedupKey;
+use d4csv_core::sale::kind::ForcedKind;
+use d4csv_core::sale::error::group_errors;
+use d4csv_core::sale::revenue::Revenue;
+
+/// Bad arguments.
+const EXIT_USAGE: u8 = 2;
+/// The context doesn't make sense.
+const EXIT_CONTEXT: u8 = 3;
+/// No sales at all, or more broken lines than allowed.
+const EXIT_PARSE: u8 = 4;
+/// Couldn't read or write some file.
+const EXIT_IO: u8 = 5;
+
+/// Everything that came in through the arguments.
+struct Args {
+  input: String,
+  data: ContextInputData,
+  quote: QuoteChar,
+  has_headers: bool,
+  max_errors: usize,
+  out: Option<String>,
+  sellers: Option<String>,
+  anon: Option<String>,
+  no_contact: Option<String>,
+  report: Option<String>,
+  excel: bool,
+  net: bool
+}
+
+/// Parses a comma-separated list of numbers.
+fn parse_list(flag: &str, s: &str) -> Result<Vec<f64>, String> {
+  return s.split(',')
+    .map(|p| p.trim().parse::<f64>())
+    .collect::<Result<Vec<f64>, _>>()
+    .map_err(|_| trf("cli_bad_value", &[&flag, &s]));
+}
+
+/// Parses a single number.
+fn parse_num(flag: &str, s: &str) -> Result<f64, String> {
+  return s.trim().parse::<f64>()
+    .map_err(|_| trf("cli_bad_value", &[&flag, &s]));
+}
+
+/// Makes sense of the arguments, starting from the same defaults as the form.
+fn parse_args<T>(mut it: T) -> Result<Args, String>
+where T: Iterator<Item = String> {
+  let mut args = Args {
+    input: String::new(),
+    data: ContextInputData::default(),
+    quote: QuoteChar::default(),
+    has_headers: true,
+    max_errors: 10,
+    out: None,
+    sellers: None,
+    anon: None,
+    no_contact: None,
+    report: None,
+    excel: false,
+    net: false
+  };
+  let mut prices: Option<Vec<f64>> = None;
+  while let Some(flag) = it.next() {
+    let mut value = || {
+      return it.next().ok_or(trf("cli_missing_value", &[&flag]));
+    };
+    match flag.as_str() {
+      "--input" => args.input = value()?,
+      "--prices" => prices = Some(parse_list(&flag, &value()?)?),
+      "--cents" => args.data.cents = true,
+      "--capacities" => {
+        args.data.capacities = parse_list(&flag, &value()?)?;
+      },
+      "--fee" => args.data.webfee = parse_num(&flag, &value()?)?,
+      "--offline-fee" => {
+        args.data.offline_fee = parse_num(&flag, &value()?)?;
+      },
+      "--no-promo" => args.data.promo_enabled = false,
+      "--promo-limit" => {
+        args.data.promos = parse_num(&flag, &value()?)?;
+        args.data.promo_unlimited = false;
+      },
+      "--promo-unlimited" => args.data.promo_unlimited = true,
+      "--no-combo" => args.data.promo_combo = false,
+      "--max-span" => args.data.max_span = parse_num(&flag, &value()?)?,
+      "--solver" => args.data.solver = value()?,
+      "--per-day" => args.data.online_per_day = true,
+      "--free-tickets" => args.data.free_tickets = true,
+      "--dedup" => {
+        let v = value()?;
+        args.data.dedup_key = DedupKey::try_from(v.as_str())
+          .map_err(|_| trf("cli_bad_value", &[&flag, &v]))?;
+      },
+      "--force-kind" => {
+        let v = value()?;
+        args.data.forced_kind = ForcedKind::try_from(v.as_str())
+          .map_err(|_| trf("cli_bad_value", &[&flag, &v]))?;
+      },
+      "--date-format" => args.data.date_format = value()?,
+      "--utc-offset" => {
+        args.data.utc_offset = parse_num(&flag, &value()?)?;
+      },
+      "--commission" => {
+        args.data.commission = parse_num(&flag, &value()?)?;
+        args.data.commission_percent = false;
+      },
+      "--column" => {
+        let v = value()?;
+        let (h, c) = v.split_once('=')
+          .and_then(|(h, c)| Some((h, Column::try_from(c).ok()?)))
+          .ok_or(trf("cli_bad_value", &[&flag, &v]))?;
+        args.data.columns.insert(h.to_owned(), c);
+      },
+      "--commission-pct" => {
+        args.data.commission = parse_num(&flag, &value()?)?;
+        args.data.commission_percent = true;
+      },
+      "--quote" => {
+        let v = value()?;
+        args.quote = v.as_str().try_into()
+          .map_err(|_| trf("cli_bad_value", &[&flag, &v]))?;
+      },
+      "--no-header" => args.has_headers = false,
+      "--max-errors" => {
+        let v = value()?;
+        args.max_errors = v.parse()
+          .map_err(|_| trf("cli_bad_value", &[&flag, &v]))?;
+      },
+      "--lang" => match value()?.as_str() {
+        "pt" => Lang::Pt.set_current(),
+        "en" => Lang::En.set_current(),
+        v => return Err(trf("cli_bad_value", &[&flag, &v])),
+      },
+      "--out" => args.out = Some(value()?),
+      "--sellers" => args.sellers = Some(value()?),
+      "--anon" => args.anon = Some(value()?),
+      "--no-contact" => args.no_contact = Some(value()?),
+      "--report" => args.report = Some(value()?),
+      "--excel" => args.excel = true,
+      "--net" => args.net = true,
+      _ => return Err(trf("cli_unknown_flag", &[&flag])),
+    }
+  }
+  if args.input.is_empty() {
+    return Err(trf("cli_missing_flag", &[&"--input"]));
+  }
+  if let Some(mut ps) = prices {
+    if args.data.promo_enabled && !ps.is_empty() {
+      args.data.promo_price = ps.remove(0);
+    }
+    args.data.rows = ps;
+  }
+  return Ok(args);
+}
+
+/// Writes the groups of broken lines to stderr.
+fn print_errors(ve: &[D4Error]) {
+  for g in group_errors(ve) {
+    eprintln!("{}x {}", g.count, g.kind);
+    if !g.example.is_empty() {
+      eprintln!("  {}", g.example);
+    }
+    if !g.lines.is_empty() {
+      let lines: Vec<String> = g.lines.iter().map(|l| l.to_string()).collect();
+      eprintln!("  {}", trf("error_lines", &[&lines.join(", ")]));
+    }
+  }
+}
+
+/// Writes some text to a file, if one was asked for.
+fn write_out(path: &Option<String>, txt: &str) -> Result<(), String> {
+  if let Some(p) = path {
+    fs::write(p, txt).map_err(|e| trf("cli_write", &[p, &e]))?;
+  }
+  return Ok(());
+}
+
+/// Picks the language before anything gets printed, so the help and the
+/// complaints about flags before --lang come out in it too. parse_args is
+/// the one that complains about a bad value.
+fn early_lang(argv: &[String]) {
+  let lang = argv.iter()
+    .position(|a| a == "--lang")
+    .and_then(|i| argv.get(i + 1));
+  match lang.map(|l| l.as_str()) {
+    Some("pt") => Lang::Pt.set_current(),
+    Some("en") => Lang::En.set_current(),
+    _ => {},
+  }
+}
+
+fn main() -> ExitCode {
+  let argv: Vec<String> = std::env::args().skip(1).collect();
+  early_lang(&argv);
+  if argv.iter().any(|a| a == "--help" || a == "-h") {
+    print!("{}", tr("cli_usage"));
+    return ExitCode::SUCCESS;
+  }
+  let args = match parse_args(argv.into_iter()) {
+    Ok(a) => a,
+    Err(e) => {
+      eprintln!("{}\n\n{}", e, tr("cli_usage"));
+      return ExitCode::from(EXIT_USAGE);
+    }
+  };
+  let ctx = match SalesContext::try_from(args.data.clone()) {
+    Ok(c) => c,
+    Err(e) => {
+      eprintln!("{}", trf("cli_bad_context", &[&e]));
+      return ExitCode::from(EXIT_CONTEXT);
+    }
+  };
+  for w in ctx.validate() {
+    eprintln!("{}: {}", tr("context_warning"), w);
+  }
+  let txt = match fs::read_to_string(&args.input) {
+    Ok(t) => t,
+    Err(e) => {
+      eprintln!("{}", trf("cli_read", &[&args.input, &e]));
+      return ExitCode::from(EXIT_IO);
+    }
+  };
+  let src = LoadSource::Csv(txt, args.quote, args.has_headers);
+  let mut ld = Loader::new(ctx, src, None);
+  let (mut sp, mut report) = loop {
+    match ld.step() {
+      LoadStep::Progress(_) => continue,
+      LoadStep::Done(sp, report, _) => break (*sp, *report),
+      LoadStep::Failed(ve) => {
+        print_errors(&ve);
+        return ExitCode::from(EXIT_PARSE);
+      },
+    }
+  };
+  let skipped = ld.skipped();
+  print_errors(skipped);
+  if skipped.len() > args.max_errors {
+    eprintln!("{}", trf("cli_too_many_errors", &[
+      &skipped.len(), &args.max_errors
+    ]));
+    return ExitCode::from(EXIT_PARSE);
+  }
+  if args.net {
+    // the loader always reports gross, and redoing the fields is cheap
+    sp.revenue = Revenue::Net;
+    report = ReportTemplate::default().compute(&sp);
+  }
+  let json = serde_json::to_string_pretty(&report).unwrap_or_default();
+  let written = report.better_csv_txt(args.excel)
+    .map_err(|e| e.to_string())
+    .and_then(|txt| write_out(&args.out, &txt))
+    .and_then(|_| {
+      report.seller_csv_txt(args.excel).map_err(|e| e.to_string())
+    })
+    .and_then(|txt| write_out(&args.sellers, &txt))
+    .and_then(|_| {
+      // a new salt every run, kept nowhere
+      let salt = RandomState::new().hash_one(0);
+      report.anon_csv_txt(args.excel, salt).map_err(|e| e.to_string())
+    })
+    .and_then(|txt| write_out(&args.anon, &txt))
+    .and_then(|_| {
+      report.no_contact_csv_txt(args.excel).map_err(|e| e.to_string())
+    })
+    .and_then(|txt| write_out(&args.no_contact, &txt))
+    .and_then(|_| write_out(&args.report, &json));
+  if let Err(e) = written {
+    eprintln!("{}", e);
+    return ExitCode::from(EXIT_IO);
+  }
+  for sf in &report.sfields {
+    println!("{}: {}", sf.0, sf.1);
+  }
+  if sp.looks_misconfigured() {
+    eprintln!("{}", trf("misconfigured", &[
+      &(100 * sp.oks().count() / sp.sales.len())
+    ]));
+  }
+  return ExitCode::SUCCESS;
+}
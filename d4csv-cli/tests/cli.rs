@@ -0,0 +1,123 @@
+//! Runs the built binary on a small export and checks what it prints and
+//! writes against saved copies, plus the exit codes when something's off.
+//! When a change to the output is on purpose, run with D4CSV_BLESS=1 to save
+//! the new copies, and check the diff before committing it.
+
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Set to anything to write the goldens instead of checking against them.
+static BLESS: &str = "D4CSV_BLESS";
+
+/// Where the fixtures are.
+fn fixture(name: &str) -> PathBuf {
+  return PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("tests")
+    .join("fixtures")
+    .join(name);
+}
+
+/// Somewhere to write outputs to, one per test.
+fn scratch(name: &str) -> PathBuf {
+  return PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+}
+
+/// Runs the CLI with some arguments.
+fn cli(args: &[&str]) -> Output {
+  return Command::new(env!("CARGO_BIN_EXE_d4csv-cli"))
+    .args(args)
+    .output()
+    .unwrap();
+}
+
+/// A CSV minus its footer, which stamps the time and build it came from.
+fn without_footer(csv: &str) -> &str {
+  return match csv.rfind("\n\"# ") {
+    Some(i) => &csv[..=i],
+    None => csv,
+  };
+}
+
+/// Compares some output with its golden, or saves it as the golden when
+/// blessing.
+fn check(name: &str, got: &str) {
+  let path = fixture(name);
+  if env::var_os(BLESS).is_some() {
+    fs::write(&path, got).unwrap();
+    return;
+  }
+  let want = fs::read_to_string(&path)
+    .unwrap_or_else(|e| panic!("{}: {} ({}=1 saves it)", name, e, BLESS));
+  assert!(
+    got == want,
+    "{} changed ({}=1 saves it if that's on purpose):\n{}",
+    name,
+    BLESS,
+    got
+  );
+}
+
+#[test]
+fn vendas() {
+  let input = fixture("vendas.csv");
+  let out = scratch("vendas.better.csv");
+  let sellers = scratch("vendas.sellers.csv");
+  let run = cli(&[
+    "--input", input.to_str().unwrap(),
+    "--out", out.to_str().unwrap(),
+    "--sellers", sellers.to_str().unwrap(),
+  ]);
+  assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+  check("vendas.stdout.txt", &String::from_utf8(run.stdout).unwrap());
+  let better = fs::read_to_string(out).unwrap();
+  check("vendas.better.csv", without_footer(&better));
+  let per_seller = fs::read_to_string(sellers).unwrap();
+  check("vendas.sellers.csv", without_footer(&per_seller));
+}
+
+#[test]
+fn help_follows_the_language() {
+  let help = |args: &[&str]| String::from_utf8(cli(args).stdout).unwrap();
+  assert!(help(&["--help"]).starts_with("uso:"));
+  assert!(help(&["--help", "--lang", "en"]).starts_with("usage:"));
+  let run = cli(&["--bogus", "--lang", "en"]);
+  assert_eq!(run.status.code(), Some(2));
+  let err = String::from_utf8(run.stderr).unwrap();
+  assert!(err.contains("unknown option") && err.contains("usage:"), "{}", err);
+}
+
+#[test]
+fn no_input_is_a_usage_error() {
+  assert_eq!(cli(&["--solver", "global"]).status.code(), Some(2));
+}
+
+#[test]
+fn bad_context_is_its_own_error() {
+  let input = fixture("vendas.csv");
+  let run = cli(&["--input", input.to_str().unwrap(), "--fee", "-0.1"]);
+  assert_eq!(run.status.code(), Some(3));
+  assert!(run.stdout.is_empty());
+}
+
+#[test]
+fn wrong_delimiter_is_a_parse_error() {
+  let semicolons = fs::read_to_string(fixture("vendas.csv"))
+    .unwrap()
+    .replace(',', ";");
+  let input = scratch("semicolons.csv");
+  fs::write(&input, semicolons).unwrap();
+  let run = cli(&["--input", input.to_str().unwrap()]);
+  assert_eq!(run.status.code(), Some(4));
+  assert!(!run.stderr.is_empty());
+}
+
+#[test]
+fn missing_file_is_an_io_error() {
+  let input = scratch("nowhere.csv");
+  assert_eq!(cli(&["--input", input.to_str().unwrap()]).status.code(), Some(5));
+}
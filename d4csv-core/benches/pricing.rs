@@ -0,0 +1,142 @@
+//! Numbers for the pricing and solving code, so changes to it can be compared
+//! against a baseline instead of guessed at. Save one before a change with
+//! `cargo bench -p d4csv-core -- --save-baseline before`, then compare after
+//! it with `cargo bench -p d4csv-core -- --baseline before`.
+
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::rc::Rc;
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use d4csv_core::{Sale, SalesContext, SalesPlus};
+use d4csv_core::sale::interner::Interner;
+use d4csv_core::sale::kind::{SaleKind, Seller};
+use d4csv_core::sale::price_deriving::{PricingCandidateCache, PricingMatch};
+use d4csv_core::ticket::batch::iter2bp;
+
+/// Seed for the generated sales, so every run sees the same ones.
+static SEED: u64 = 2022;
+/// How many sales the pipeline benchmark goes through.
+static PIPELINE_SALES: usize = 10_000;
+
+/// A tiny linear congruential generator. Good enough for made-up sales, and
+/// it keeps the benches free of another dependency.
+struct Lcg(u64);
+
+impl Lcg {
+  fn next(&mut self, n: u64) -> u64 {
+    self.0 = self.0
+      .wrapping_mul(6364136223846793005)
+      .wrapping_add(1442695040888963407);
+    return (self.0 >> 33) % n;
+  }
+}
+
+/// The 2022 context, but with six numbered batches.
+fn six_batches() -> SalesContext {
+  let prices = vec![5500, 6500, 7500, 8500, 9500, 10500, 11500];
+  return SalesContext {
+    batches: iter2bp(prices, Some(0)),
+    ..SalesContext::default()
+  };
+}
+
+/// Realistic prices: a few tickets from one or two batches, with and without
+/// the online fee undone, plus some that match nothing.
+fn price_grid() -> Vec<usize> {
+  return (1..=40).map(|k| k * 500 + 5000).collect();
+}
+
+/// Sales made from real matches, with the odd typo thrown in, spread over a
+/// couple of weeks and a handful of sellers, sharing names like parsed ones.
+fn gen_sales(ctx: &SalesContext, n: usize) -> Vec<Sale> {
+  let mut rng = Lcg(SEED);
+  let mut names = Interner::default();
+  let start = Utc.with_ymd_and_hms(2022, 10, 1, 12, 0, 0).unwrap();
+  let prices: Vec<usize> = ctx.batches.values().copied().collect();
+  return (0..n).map(|i| {
+    let online = rng.next(2) == 0;
+    let batch = prices[rng.next(prices.len() as u64) as usize];
+    let amount = 1 + rng.next(4) as usize;
+    let typo = if rng.next(50) == 0 { 137 } else { 0 };
+    let sale_kind = match online {
+      true => SaleKind::Online(ctx.online_fee),
+      false => SaleKind::Offline(ctx.offline_fee),
+    };
+    let seller_name = match online {
+      true => None,
+      false => Some(names.intern(&format!("vendedor {}", rng.next(8)))),
+    };
+    let seller_key = seller_name.as_deref()
+      .map(|s| names.intern(&Seller::normalize(s)));
+    Sale {
+      row: i,
+      when: start + Duration::minutes((i * 2) as i64),
+      buyer_email: None,
+      buyer_username: None,
+      value: sale_kind.apply_fee(batch * amount) + typo,
+      subtotal: None,
+      sale_kind,
+      seller_name,
+      seller_id: None,
+      seller_email: None,
+      seller_key,
+      token: format!("tok{}", i),
+      sale_id: format!("id{}", i),
+      card_name: None,
+      card_pfx: None,
+      card_sfx: None
+    }
+  }).collect();
+}
+
+fn all_priced(c: &mut Criterion) {
+  let grid = price_grid();
+  for (name, ctx) in [
+    ("all_priced/default", SalesContext::default()),
+    ("all_priced/six_batches", six_batches())
+  ] {
+    c.bench_function(name, |b| b.iter(|| {
+      for p in &grid {
+        black_box(PricingMatch::all_priced(*p, &ctx));
+      }
+    }));
+  }
+}
+
+fn cache(c: &mut Criterion) {
+  let ctx = Rc::new(SalesContext::default());
+  let grid = price_grid();
+  c.bench_function("cache/cold", |b| b.iter(|| {
+    let mut dude = PricingCandidateCache::from(Rc::clone(&ctx));
+    for p in &grid {
+      black_box(dude.from_price(*p));
+    }
+  }));
+  let mut warm = PricingCandidateCache::from(Rc::clone(&ctx));
+  for p in &grid {
+    warm.from_price(*p);
+  }
+  c.bench_function("cache/warm", |b| b.iter(|| {
+    for p in &grid {
+      black_box(warm.from_price(*p));
+    }
+  }));
+}
+
+fn pipeline(c: &mut Criterion) {
+  let ctx = Rc::new(SalesContext::default());
+  let sales = gen_sales(&ctx, PIPELINE_SALES);
+  let mut group = c.benchmark_group("pipeline");
+  group.sample_size(10);
+  group.bench_function("from_sales_and_solve", |b| b.iter(|| {
+    let iter = sales.clone().into_iter();
+    let mut sp = SalesPlus::from_sales(iter, Rc::clone(&ctx));
+    black_box(sp.solve_ambiguities());
+  }));
+  group.finish();
+}
+
+criterion_group!(benches, all_priced, cache, pipeline);
+criterion_main!(benches);
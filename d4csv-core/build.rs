@@ -0,0 +1,44 @@
+//! Bakes the git commit and the build time into the binaries, so reports and
+//! bug reports can say which build made them.
+
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What goes in when git can't tell us, like when building from a tarball.
+const UNKNOWN: &str = "desconhecido";
+
+/// The short hash of the current commit, if git is around and this is a repo.
+fn git_hash() -> Option<String> {
+  let out = Command::new("git")
+    .args(["rev-parse", "--short", "HEAD"])
+    .output()
+    .ok()?;
+  if !out.status.success() {
+    return None;
+  }
+  let hash = String::from_utf8(out.stdout).ok()?.trim().to_owned();
+  return match hash.is_empty() {
+    true => None,
+    false => Some(hash)
+  };
+}
+
+fn main() {
+  let hash = git_hash().unwrap_or(UNKNOWN.to_owned());
+  let built_at = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs().to_string())
+    .unwrap_or(UNKNOWN.to_owned());
+  println!("cargo:rustc-env=D4CSV_GIT_HASH={}", hash);
+  println!("cargo:rustc-env=D4CSV_BUILT_AT={}", built_at);
+  println!("cargo:rerun-if-changed=build.rs");
+  for f in [".git/HEAD", ".git/index"] {
+    if Path::new(f).exists() {
+      println!("cargo:rerun-if-changed={}", f);
+    }
+  }
+}
@@ -0,0 +1,39 @@
+//! A wall-clock budget for the heavy loops, so some adversarial CSV can't
+//! hang the page forever. This crate doesn't know what time it is wherever
+//! it runs, so the clock comes from outside.
+
+/// How many iterations the heavy loops go through between clock checks.
+/// Asking the browser for the time isn't free.
+pub static CHECK_EVERY: usize = 4096;
+
+/// A deadline, plus a way of telling when it's passed.
+#[derive(Copy, Clone, Debug)]
+pub struct Budget {
+  /// Current time in milliseconds, from any fixed point.
+  clock: fn() -> f64,
+  /// When time's up, by that clock.
+  deadline: f64
+}
+
+impl Budget {
+  /// A budget of some milliseconds, starting now.
+  pub fn new(clock: fn() -> f64, ms: f64) -> Self {
+    return Self { clock, deadline: clock() + ms };
+  }
+
+  /// A budget that never runs out.
+  pub fn unlimited() -> Self {
+    return Self { clock: || 0.0, deadline: f64::INFINITY };
+  }
+
+  /// Whether time's up.
+  pub fn exceeded(&self) -> bool {
+    return (self.clock)() > self.deadline;
+  }
+}
+
+impl Default for Budget {
+  fn default() -> Self {
+    return Self::unlimited();
+  }
+}
@@ -0,0 +1,26 @@
+//! Which build this is: crate version, git commit and build time, all baked
+//! in by the build script.
+
+use chrono::{TimeZone, Utc};
+
+/// The crate version.
+pub static VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The short git hash, or "desconhecido" if git wasn't around.
+pub static GIT_HASH: &str = env!("D4CSV_GIT_HASH");
+
+/// Build time in Unix seconds, or "desconhecido".
+pub static BUILT_AT: &str = env!("D4CSV_BUILT_AT");
+
+/// The build time as RFC 3339, or as it came if it's not a timestamp.
+pub fn built_at() -> String {
+  return BUILT_AT.parse::<i64>().ok()
+    .and_then(|s| Utc.timestamp_opt(s, 0).single())
+    .map(|t| t.to_rfc3339())
+    .unwrap_or(BUILT_AT.to_owned());
+}
+
+/// Everything in one line, like "v0.1.0 (abc1234, 2024-01-01T00:00:00+00:00)".
+pub fn describe() -> String {
+  return format!("v{} ({}, {})", VERSION, GIT_HASH, built_at());
+}
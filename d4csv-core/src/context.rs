@@ -0,0 +1,965 @@
+//! Sale context that comes from outside the CSV.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use chrono::FixedOffset;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::error::D4Error;
+use crate::format;
+use crate::i18n::{tr, trf};
+use crate::sale::ambiguity::AmbiguitySolver;
+use crate::sale::columns::{Column, normalize_header};
+use crate::sale::commission::Commission;
+use crate::sale::dedup::DedupKey;
+use crate::sale::kind::ForcedKind;
+use crate::sale::price_deriving::{
+  COLLISION_MAX_TICKETS, PricingMatch, collision_prices
+};
+use crate::ticket::batch::{BatchPrices, iter2bp, bp2iter, Batch};
+use crate::ticket::batchnum::BatchNum;
+
+static WEBFEE_PRECISION: usize = 1000;
+/// Prices from this many cents up are surely typos (R$ 10.000 a ticket).
+static ABSURD_PRICE: usize = 1_000_000;
+/// Promo limits above this are surely typos too.
+static ABSURD_PROMOS: usize = 20;
+/// How many inherently ambiguous prices a warning lists.
+static COLLISION_EXAMPLES: usize = 5;
+static PRICES_SEPARATOR: &str = ";";
+
+/// The newest version of the context file format this build understands.
+pub static CONTEXT_FILE_VERSION: u32 = 1;
+
+/// The context needed to derive ticket information from the CSV. Missing
+/// fields in older files fall back to the defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SalesContext {
+  /// Online fee.
+  pub online_fee: (usize, usize),
+  /// Offline fee, for points of sale that charge for handling cash. None
+  /// means offline sales pay face value.
+  pub offline_fee: Option<(usize, usize)>,
+  /// Batch prices.
+  pub batches: BatchPrices,
+  /// How many tickets each batch had, for the ones where that's known.
+  pub capacities: HashMap<BatchNum, usize>,
+  /// Whether there's a promo batch at all.
+  pub has_promo: bool,
+  /// Promo batch limit per person.
+  pub promo_limit: Option<usize>,
+  /// Whether promo tickets can be bought together with 1st batch ones. When
+  /// off, the promo batch only sells by itself.
+  pub enable_promo_combo: bool,
+  /// Most contiguous numbered batches a single purchase may span. 2 means
+  /// turn-of-batch at most; more allows multi-batch matches.
+  pub max_span: usize,
+  /// Ambiguity solver.
+  pub solver: AmbiguitySolver,
+  /// Whether online sales count as a different seller each day, so their
+  /// batch progression is tracked per day.
+  pub online_per_day: bool,
+  /// Whether sales are online or offline by their status, or all forced to
+  /// one of them.
+  pub forced_kind: ForcedKind,
+  /// What tells sales apart when dropping repeated ones, if anything.
+  pub dedup_key: DedupKey,
+  /// Whether zero-value sales (courtesies, tests) count as one ticket each,
+  /// rather than none.
+  pub free_tickets: bool,
+  /// strftime-style format for sale dates, tried before RFC 3339. Empty
+  /// means RFC 3339 only.
+  pub date_format: String,
+  /// The event's offset from UTC, in seconds east. Sales are kept in UTC;
+  /// this is for showing them, telling which day they were on, and reading
+  /// dates that come without an offset.
+  pub utc_offset: i32,
+  /// Manual rules for prices (without the fee) known to always mean a
+  /// specific match. These skip inference and the solvers altogether.
+  pub overrides: HashMap<usize, PricingMatch>,
+  /// What offline sellers get paid for their resolved tickets.
+  pub commission: Commission,
+  /// CSV headers to take as some sale field, for the ones the aliases don't
+  /// cover. Keyed by normalized header.
+  pub columns: HashMap<String, Column>
+}
+
+impl SalesContext {
+  /// The event's time zone.
+  pub fn tz(&self) -> FixedOffset {
+    return FixedOffset::east_opt(self.utc_offset)
+      .unwrap_or(FixedOffset::east_opt(0).unwrap());
+  }
+
+  /// Whether two contexts come up with the same pricing candidates. Solvers
+  /// and such don't matter here.
+  pub fn prices_like(&self, other: &Self) -> bool {
+    return self.online_fee == other.online_fee
+      && self.offline_fee == other.offline_fee
+      && self.forced_kind == other.forced_kind
+      && self.batches == other.batches
+      && self.has_promo == other.has_promo
+      && self.promo_limit == other.promo_limit
+      && self.enable_promo_combo == other.enable_promo_combo
+      && self.max_span == other.max_span
+      && self.overrides == other.overrides;
+  }
+}
+
+/// A context as kept in a file: the context itself, plus the format version.
+/// Only for writing: flattening loses the integer keys of the overrides on
+/// the way back, so from_json() takes the version off by hand instead.
+#[derive(Serialize)]
+struct ContextFile {
+  v: u32,
+  #[serde(flatten)]
+  context: SalesContext
+}
+
+impl SalesContext {
+  /// The context as a small, versioned JSON document for sharing.
+  pub fn to_json(&self) -> String {
+    let file = ContextFile {
+      v: CONTEXT_FILE_VERSION,
+      context: self.clone()
+    };
+    return serde_json::to_string_pretty(&file).unwrap_or_default();
+  }
+
+  /// Reads a context back from JSON, checking it makes sense the same way the
+  /// form does (and coming out the same as if it went through the form).
+  pub fn from_json(s: &str) -> Result<Self, String> {
+    let mut raw: serde_json::Value = serde_json::from_str(s)
+      .map_err(|e| trf("ctx_json_invalid", &[&e]))?;
+    let v = raw.as_object_mut()
+      .and_then(|o| o.remove("v"))
+      .and_then(|v| v.as_u64())
+      .ok_or(tr("ctx_json_no_version").to_owned())?;
+    if v > CONTEXT_FILE_VERSION as u64 {
+      return Err(trf("ctx_json_newer", &[&v, &CONTEXT_FILE_VERSION]));
+    }
+    let context: SalesContext = serde_json::from_value(raw)
+      .map_err(|e| trf("ctx_json_invalid", &[&e]))?;
+    if context.online_fee.1 == 0 {
+      return Err(trf("ctx_json_invalid", &[&"online_fee"]));
+    }
+    if FixedOffset::east_opt(context.utc_offset).is_none() {
+      return Err(trf("ctx_json_invalid", &[&"utc_offset"]));
+    }
+    if matches!(context.offline_fee, Some((_, 0))) {
+      return Err(trf("ctx_json_invalid", &[&"offline_fee"]));
+    }
+    let data = ContextInputData::from(&context);
+    return SalesContext::try_from(data).map_err(|e| e.to_string());
+  }
+}
+
+impl Default for SalesContext {
+  /// Data from the 2022 D4.
+  fn default() -> Self {
+    Self {
+      online_fee: (11, 10),
+      offline_fee: None,
+      batches: iter2bp(vec![5500, 6500, 7500, 8500], Some(0)),
+      capacities: HashMap::new(),
+      has_promo: true,
+      promo_limit: Some(1),
+      enable_promo_combo: true,
+      max_span: 2,
+      solver: AmbiguitySolver::SellerLookBehind,
+      online_per_day: false,
+      forced_kind: ForcedKind::Detect,
+      dedup_key: DedupKey::None,
+      free_tickets: false,
+      date_format: String::new(),
+      // Brasília time, no daylight saving since 2019
+      utc_offset: -3 * 3600,
+      overrides: HashMap::new(),
+      commission: Commission::Nothing,
+      columns: HashMap::new()
+    }
+  }
+}
+
+/// Context input as it comes from the document. Also what gets persisted in
+/// localStorage; missing fields in old stored data fall back to the defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextInputData {
+  pub webfee: f64,
+  /// Offline fee, as a fraction like the web fee. Zero means none.
+  pub offline_fee: f64,
+  /// Numbered batch prices, in reais (or centavos, see below), in order.
+  pub rows: Vec<f64>,
+  /// Whether the prices are typed in whole centavos instead of reais. Those
+  /// are taken as they are, so there's no float rounding to worry about.
+  pub cents: bool,
+  /// Numbered batch capacities, lined up with the rows. Zero or missing
+  /// means unknown.
+  pub capacities: Vec<f64>,
+  /// Whether there's a promo batch.
+  pub promo_enabled: bool,
+  /// Promo batch price, in the same unit as the rows.
+  pub promo_price: f64,
+  /// Promo batch capacity, zero meaning unknown.
+  pub promo_capacity: f64,
+  /// Promo tickets allowed per sale.
+  pub promos: f64,
+  /// Whether promo tickets are unlimited per sale (ignores the above).
+  pub promo_unlimited: bool,
+  /// Whether promo + 1st batch purchases are a thing.
+  pub promo_combo: bool,
+  /// Most contiguous batches a single purchase may span.
+  pub max_span: f64,
+  pub solver: String,
+  /// Whether online sales are split into one seller per day.
+  pub online_per_day: bool,
+  /// Whether sales are all forced online or offline.
+  pub forced_kind: ForcedKind,
+  /// What tells repeated sales apart.
+  pub dedup_key: DedupKey,
+  /// Whether zero-value sales count as a ticket each.
+  pub free_tickets: bool,
+  pub date_format: String,
+  /// The event's offset from UTC, in hours.
+  pub utc_offset: f64,
+  /// Manual price rules, by price in cents (without the fee).
+  pub overrides: HashMap<usize, PricingMatch>,
+  /// Offline seller commission: per ticket, in the same unit as the rows, or
+  /// a percentage. Zero means none.
+  pub commission: f64,
+  /// Whether the commission above is a percentage of the money taken.
+  pub commission_percent: bool,
+  /// Manual CSV header mappings, by header as typed.
+  pub columns: HashMap<String, Column>
+}
+
+/// Parses a price list like "55;65;77.5" into prices in reais.
+pub fn parse_prices(s: &str) -> Result<Vec<f64>, String> {
+  let mut v: Vec<f64> = Vec::new();
+  for p in s.split(PRICES_SEPARATOR) {
+    match str::parse::<f64>(p.trim()) {
+      Ok(f) if f > 0.0 => v.push(f),
+      _ => return Err(tr("ctx_bad_prices").to_owned())
+    }
+  }
+  return Ok(v);
+}
+
+/// Parses a quick-entry price list: the promo price, then at least one batch.
+pub fn parse_quick(s: &str) -> Result<Vec<f64>, String> {
+  let v = parse_prices(s)?;
+  if v.len() < 2 {
+    return Err(tr("ctx_quick_short").to_owned());
+  }
+  return Ok(v);
+}
+
+/// Parses a capacity list like "100;0;250". Zero means unknown.
+pub fn parse_capacities(s: &str) -> Result<Vec<f64>, String> {
+  let mut v: Vec<f64> = Vec::new();
+  for c in s.split(PRICES_SEPARATOR) {
+    match str::parse::<f64>(c.trim()) {
+      Ok(f) if f >= 0.0 && f.fract() == 0.0 => v.push(f),
+      _ => return Err(trf("ctx_bad_capacity", &[&c]))
+    }
+  }
+  return Ok(v);
+}
+
+/// Names an offset from UTC (in seconds east) like "UTC-3" or "UTC+5:30".
+pub fn offset_name(secs: i32) -> String {
+  let sign = if secs < 0 { "-" } else { "+" };
+  let (h, m) = (secs.abs() / 3600, secs.abs() % 3600 / 60);
+  return match (h, m) {
+    (0, 0) => "UTC".to_owned(),
+    (h, 0) => format!("UTC{}{}", sign, h),
+    (h, m) => format!("UTC{}{}:{:02}", sign, h, m),
+  };
+}
+
+/// Writes prices back as a price list.
+pub fn join_prices(prices: &[f64]) -> String {
+  return prices.iter().join(PRICES_SEPARATOR);
+}
+
+/// The fields in the context form, for pointing out errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContextField {
+  /// The web fee.
+  Webfee,
+  /// The offline fee.
+  OfflineFee,
+  /// The batch prices list.
+  Prices,
+  /// The promo batch price.
+  Promo,
+  /// The promo limit.
+  Promos,
+  /// The batch capacities.
+  Capacities,
+  /// The manual price rules.
+  Overrides,
+  /// The batch span limit.
+  MaxSpan,
+  /// The offline seller commission.
+  Commission,
+  /// The time zone.
+  Timezone
+}
+
+/// Something wrong with one of the context form's fields.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContextError {
+  /// Which field is wrong.
+  pub field: ContextField,
+  /// What's wrong with it.
+  pub msg: String
+}
+
+impl ContextError {
+  fn new(field: ContextField, msg: &str) -> Self {
+    return Self { field, msg: msg.to_owned() };
+  }
+}
+
+impl Display for ContextError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", self.msg);
+  }
+}
+
+/// Something odd about a context, found after it's put together. Hard ones
+/// keep it from being used; soft ones are only pointed out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ContextWarning {
+  /// A batch that ended up costing nothing (too many decimal places, say).
+  ZeroPrice(BatchNum),
+  /// A batch way too expensive to be real. Holds the price.
+  AbsurdPrice(BatchNum, usize),
+  /// Two numbered batches with the same price. Holds both and the price.
+  SamePrice(BatchNum, BatchNum, usize),
+  /// A numbered batch cheaper than the one before it.
+  Decreasing(BatchNum, BatchNum),
+  /// A promo batch that's no cheaper than the 1st batch.
+  PromoNotCheaper,
+  /// A fee over 100%, in the given field.
+  HugeFee(ContextField),
+  /// A promo limit of zero, or way too large. Holds it.
+  PromoLimit(usize),
+  /// Prices that different matches add up to, so no solver can be sure of
+  /// sales at them. Holds them, in cents and without the fee.
+  Collisions(Vec<usize>)
+}
+
+impl Display for ContextWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", match self {
+      ContextWarning::ZeroPrice(b) => trf("warn_zero_price", &[b]),
+      ContextWarning::AbsurdPrice(b, p) => {
+        trf("warn_absurd_price", &[b, &format::money(*p)])
+      },
+      ContextWarning::SamePrice(a, b, p) => {
+        trf("warn_same_price", &[a, b, &format::money(*p)])
+      },
+      ContextWarning::Decreasing(a, b) => trf("warn_decreasing", &[b, a]),
+      ContextWarning::PromoNotCheaper => tr("warn_promo_price").to_owned(),
+      ContextWarning::HugeFee(_) => tr("fee_warning").to_owned(),
+      ContextWarning::PromoLimit(n) => trf("warn_promo_limit", &[n]),
+      ContextWarning::Collisions(ps) => {
+        let mut shown = ps.iter()
+          .take(COLLISION_EXAMPLES)
+          .map(|p| format::money(*p))
+          .join(", ");
+        if ps.len() > COLLISION_EXAMPLES {
+          shown.push_str(", ...");
+        }
+        trf("warn_collisions", &[&ps.len(), &COLLISION_MAX_TICKETS, &shown])
+      },
+    });
+  }
+}
+
+impl ContextWarning {
+  /// Whether the context is unusable because of this.
+  pub fn is_hard(&self) -> bool {
+    return match self {
+      ContextWarning::ZeroPrice(_) => true,
+      ContextWarning::PromoLimit(n) => *n == 0,
+      _ => false,
+    };
+  }
+
+  /// The form field to point at.
+  pub fn field(&self) -> ContextField {
+    return match self {
+      ContextWarning::ZeroPrice(BatchNum::Promo) => ContextField::Promo,
+      ContextWarning::AbsurdPrice(BatchNum::Promo, _) => ContextField::Promo,
+      ContextWarning::PromoNotCheaper => ContextField::Promo,
+      ContextWarning::HugeFee(field) => *field,
+      ContextWarning::PromoLimit(_) => ContextField::Promos,
+      _ => ContextField::Prices,
+    };
+  }
+}
+
+impl SalesContext {
+  /// Looks for prices, fees and limits that make no sense, or that will make
+  /// a mess of the candidates. See ContextWarning::is_hard for which ones
+  /// keep the context from being used.
+  pub fn validate(&self) -> Vec<ContextWarning> {
+    let mut v: Vec<ContextWarning> = Vec::new();
+    for b in bp2iter(&self.batches) {
+      if b.price == 0 {
+        v.push(ContextWarning::ZeroPrice(b.num));
+      } else if b.price >= ABSURD_PRICE {
+        v.push(ContextWarning::AbsurdPrice(b.num, b.price));
+      }
+    }
+    let numbered: Vec<Batch> = bp2iter(&self.batches)
+      .filter(|b| b.num != BatchNum::Promo)
+      .collect();
+    for (a, b) in numbered.iter().tuple_windows() {
+      if a.price == b.price {
+        v.push(ContextWarning::SamePrice(a.num, b.num, b.price));
+      } else if a.price > b.price {
+        v.push(ContextWarning::Decreasing(a.num, b.num));
+      }
+    }
+    let promo = self.batches.get(&BatchNum::Promo);
+    let first = self.batches.get(&BatchNum::Numbered(1));
+    if let (Some(p), Some(f)) = (promo, first) {
+      if p >= f {
+        v.push(ContextWarning::PromoNotCheaper);
+      }
+    }
+    let huge = |(k, d): (usize, usize)| k > 2 * d;
+    if huge(self.online_fee) {
+      v.push(ContextWarning::HugeFee(ContextField::Webfee));
+    }
+    if self.offline_fee.map(huge).unwrap_or(false) {
+      v.push(ContextWarning::HugeFee(ContextField::OfflineFee));
+    }
+    if let (true, Some(n)) = (self.has_promo, self.promo_limit) {
+      if n == 0 || n > ABSURD_PROMOS {
+        v.push(ContextWarning::PromoLimit(n));
+      }
+    }
+    // only worth looking into when the prices make sense at all
+    if !v.iter().any(|w| w.is_hard()) {
+      let cs = collision_prices(self);
+      if !cs.is_empty() {
+        v.push(ContextWarning::Collisions(cs.keys().copied().collect()));
+      }
+    }
+    return v;
+  }
+}
+
+impl TryFrom<ContextInputData> for SalesContext {
+  type Error = D4Error;
+
+  fn try_from(data: ContextInputData) -> Result<Self, Self::Error> {
+    if data.webfee.is_nan() || data.webfee < 0.0 {
+      return Err(ContextError::new(
+        ContextField::Webfee,
+        tr("ctx_negative_fee")
+      ).into());
+    }
+    if data.offline_fee.is_nan() || data.offline_fee < 0.0 {
+      return Err(ContextError::new(
+        ContextField::OfflineFee,
+        tr("ctx_negative_fee")
+      ).into());
+    }
+    if data.rows.is_empty() {
+      return Err(ContextError::new(
+        ContextField::Prices,
+        tr("ctx_no_batches")
+      ).into());
+    }
+    let whole = |p: f64| !data.cents || p.fract() == 0.0;
+    let mut prices: Vec<f64> = Vec::new();
+    if data.promo_enabled {
+      if data.promo_price.is_nan() || data.promo_price <= 0.0 {
+        return Err(ContextError::new(
+          ContextField::Promo,
+          tr("ctx_promo_price")
+        ).into());
+      }
+      if !whole(data.promo_price) {
+        return Err(ContextError::new(
+          ContextField::Promo,
+          tr("ctx_promo_cents")
+        ).into());
+      }
+      if !data.promo_unlimited
+        && (data.promos.is_nan() || data.promos < 1.0
+          || data.promos.fract() != 0.0) {
+        return Err(ContextError::new(
+          ContextField::Promos,
+          tr("ctx_promo_limit")
+        ).into());
+      }
+      prices.push(data.promo_price);
+    }
+    for (i, price) in data.rows.iter().enumerate() {
+      if price.is_nan() || *price <= 0.0 {
+        return Err(ContextError {
+          field: ContextField::Prices,
+          msg: trf("ctx_price_zero", &[&(i + 1)])
+        }.into());
+      }
+      if !whole(*price) {
+        return Err(ContextError {
+          field: ContextField::Prices,
+          msg: trf("ctx_price_cents", &[&(i + 1)])
+        }.into());
+      }
+      prices.push(*price);
+    }
+    let cap_ok = |c: f64| c >= 0.0 && c.fract() == 0.0;
+    let mut capacities: HashMap<BatchNum, usize> = HashMap::new();
+    if data.promo_enabled && data.promo_capacity != 0.0 {
+      if !cap_ok(data.promo_capacity) {
+        return Err(ContextError::new(
+          ContextField::Capacities,
+          tr("ctx_promo_capacity")
+        ).into());
+      }
+      capacities.insert(BatchNum::Promo, data.promo_capacity as usize);
+    }
+    for (i, c) in data.capacities.iter().take(data.rows.len()).enumerate() {
+      if !cap_ok(*c) {
+        return Err(ContextError {
+          field: ContextField::Capacities,
+          msg: trf("ctx_capacity", &[&(i + 1)])
+        }.into());
+      }
+      if *c > 0.0 {
+        capacities.insert(BatchNum::Numbered(i + 1), *c as usize);
+      }
+    }
+    if data.max_span.is_nan() || data.max_span < 2.0
+      || data.max_span.fract() != 0.0 {
+      return Err(ContextError::new(
+        ContextField::MaxSpan,
+        tr("ctx_max_span")
+      ).into());
+    }
+    let quarters = data.utc_offset * 4.0;
+    if !(data.utc_offset >= -12.0 && data.utc_offset <= 14.0)
+      || quarters.fract() != 0.0 {
+      return Err(ContextError::new(
+        ContextField::Timezone,
+        tr("ctx_timezone")
+      ).into());
+    }
+    if data.commission.is_nan() || data.commission < 0.0
+      || (data.commission_percent && data.commission > 100.0) {
+      return Err(ContextError::new(
+        ContextField::Commission,
+        tr("ctx_commission")
+      ).into());
+    }
+    if !data.commission_percent && !whole(data.commission) {
+      return Err(ContextError::new(
+        ContextField::Commission,
+        tr("ctx_commission_cents")
+      ).into());
+    }
+    let c = data.commission;
+    let commission = match (c == 0.0, data.commission_percent) {
+      (true, _) => Commission::Nothing,
+      (false, true) => Commission::Percent(c),
+      (false, false) => Commission::PerTicket(data.to_cents(c))
+    };
+    let cents = prices.into_iter().map(|p| data.to_cents(p));
+    let batches = iter2bp(cents, data.promo_enabled.then_some(0));
+    // a rule has to add up to its price, with batches that still exist at
+    // the same prices, or it's stale
+    for (price, pm) in data.overrides.iter() {
+      let fits = pm.checked_price() == Some(*price)
+        && pm.amounts().iter()
+          .all(|ba| batches.get(&ba.0.num) == Some(&ba.0.price));
+      if !fits {
+        return Err(ContextError {
+          field: ContextField::Overrides,
+          msg: trf("ctx_stale_override", &[&format::money(*price), pm])
+        }.into());
+      }
+    }
+    let ctx = Self {
+      online_fee: (
+        ((data.webfee + 1.0) * (WEBFEE_PRECISION as f64)) as usize,
+        WEBFEE_PRECISION
+      ),
+      offline_fee: (data.offline_fee > 0.0).then_some((
+        ((data.offline_fee + 1.0) * (WEBFEE_PRECISION as f64)) as usize,
+        WEBFEE_PRECISION
+      )),
+      batches,
+      capacities,
+      has_promo: data.promo_enabled,
+      promo_limit: {
+        if data.promo_unlimited {
+          None
+        } else {
+          Some(data.promos as usize)
+        }
+      },
+      enable_promo_combo: data.promo_combo,
+      max_span: data.max_span as usize,
+      solver: data.solver.as_str().try_into().unwrap_or_default(),
+      online_per_day: data.online_per_day,
+      forced_kind: data.forced_kind,
+      dedup_key: data.dedup_key,
+      free_tickets: data.free_tickets,
+      date_format: data.date_format.trim().to_owned(),
+      utc_offset: (data.utc_offset * 3600.0).round() as i32,
+      overrides: data.overrides,
+      commission,
+      columns: data.columns.iter()
+        .map(|(h, c)| (normalize_header(h), *c))
+        .filter(|(h, _)| !h.is_empty())
+        .collect()
+    };
+    if let Some(w) = ctx.validate().into_iter().find(|w| w.is_hard()) {
+      return Err(ContextError {
+        field: w.field(),
+        msg: w.to_string()
+      }.into());
+    }
+    return Ok(ctx);
+  }
+}
+
+impl From<&SalesContext> for ContextInputData {
+  fn from(ctx: &SalesContext) -> Self {
+    let bps: Vec<Batch> = bp2iter(&ctx.batches).collect();
+    return Self {
+      webfee: (ctx.online_fee.0 as f64) / (ctx.online_fee.1 as f64) - 1.0,
+      offline_fee: match ctx.offline_fee {
+        Some((k, d)) => (k as f64) / (d as f64) - 1.0,
+        None => 0.0
+      },
+      rows: bps.iter()
+        .filter(|b| b.num != BatchNum::Promo)
+        .map(|b| (b.price as f64)/100.0)
+        .collect(),
+      // a context only knows cents, so reais it is
+      cents: false,
+      capacities: bps.iter()
+        .filter(|b| b.num != BatchNum::Promo)
+        .map(|b| ctx.capacities.get(&b.num).copied().unwrap_or(0) as f64)
+        .collect(),
+      promo_capacity: ctx.capacities.get(&BatchNum::Promo)
+        .copied()
+        .unwrap_or(0) as f64,
+      promo_enabled: ctx.has_promo,
+      promo_price: bps.iter()
+        .find(|b| b.num == BatchNum::Promo)
+        .map(|b| (b.price as f64)/100.0)
+        .unwrap_or(0.0),
+      promos: ctx.promo_limit.unwrap_or(1) as f64,
+      promo_unlimited: ctx.promo_limit.is_none(),
+      promo_combo: ctx.enable_promo_combo,
+      max_span: ctx.max_span as f64,
+      solver: ctx.solver.name().to_owned(),
+      online_per_day: ctx.online_per_day,
+      forced_kind: ctx.forced_kind,
+      dedup_key: ctx.dedup_key,
+      free_tickets: ctx.free_tickets,
+      date_format: ctx.date_format.clone(),
+      utc_offset: (ctx.utc_offset as f64) / 3600.0,
+      overrides: ctx.overrides.clone(),
+      commission: match ctx.commission {
+        Commission::Nothing => 0.0,
+        Commission::PerTicket(c) => (c as f64)/100.0,
+        Commission::Percent(p) => p
+      },
+      commission_percent: matches!(ctx.commission, Commission::Percent(_)),
+      columns: ctx.columns.clone()
+    }
+  }
+}
+
+impl ContextInputData {
+  /// Human-readable (name, value) pairs, in the same terms the form uses.
+  pub fn describe(&self) -> Vec<(&'static str, String)> {
+    return vec![
+      (tr("d_webfee"), self.webfee.to_string()),
+      (tr("d_offline_fee"), match self.offline_fee == 0.0 {
+        true => tr("none_f").to_owned(),
+        false => self.offline_fee.to_string()
+      }),
+      (tr("d_prices"), join_prices(&self.rows)),
+      (tr("d_unit"), match self.cents {
+        true => tr("unit_cents").into(),
+        false => tr("unit_reais").into()
+      }),
+      (tr("d_capacities"), {
+        let mut caps: Vec<String> = Vec::new();
+        if self.promo_enabled && self.promo_capacity > 0.0 {
+          caps.push(format!("promo: {}", self.promo_capacity));
+        }
+        for (i, c) in self.capacities.iter().enumerate() {
+          if *c > 0.0 && i < self.rows.len() {
+            caps.push(format!("{}º: {}", i + 1, c));
+          }
+        }
+        match caps.is_empty() {
+          true => tr("none_f").to_owned(),
+          false => caps.join("; ")
+        }
+      }),
+      (tr("d_promo"), match self.promo_enabled {
+        true => self.promo_price.to_string(),
+        false => tr("no").into()
+      }),
+      (tr("d_promos"), match self.promo_unlimited {
+        true => tr("d_unlimited").into(),
+        false => self.promos.to_string()
+      }),
+      (tr("d_combo"), match self.promo_combo {
+        true => tr("yes").into(),
+        false => tr("no").into()
+      }),
+      (tr("d_max_span"), self.max_span.to_string()),
+      (tr("d_solver"), self.solver.clone()),
+      (tr("d_per_day"), match self.online_per_day {
+        true => tr("yes").into(),
+        false => tr("no").into()
+      }),
+      (tr("d_forced_kind"), self.forced_kind.to_string()),
+      (tr("d_dedup"), self.dedup_key.to_string()),
+      (tr("d_free_tickets"), match self.free_tickets {
+        true => tr("yes").into(),
+        false => tr("no").into()
+      }),
+      (tr("d_date_format"), match self.date_format.is_empty() {
+        true => "RFC 3339".to_owned(),
+        false => self.date_format.clone()
+      }),
+      (tr("d_timezone"), offset_name((self.utc_offset * 3600.0) as i32)),
+      (tr("d_overrides"), match self.overrides.is_empty() {
+        true => tr("none_f").to_owned(),
+        false => self.overrides.iter()
+          .sorted_by_key(|(price, _)| **price)
+          .map(|(price, pm)| format!("{} = {}", format::money(*price), pm))
+          .join("; ")
+      }),
+      (tr("d_commission"), {
+        let c = self.commission;
+        match (c == 0.0, self.commission_percent) {
+          (true, _) => tr("none_f").to_owned(),
+          (false, true) => format!("{}%", c),
+          (false, false) => trf("commission_per_ticket", &[&c])
+        }
+      }),
+      (tr("d_columns"), match self.columns.is_empty() {
+        true => tr("none_f").to_owned(),
+        false => self.columns.iter()
+          .sorted_by_key(|(h, _)| h.as_str())
+          .map(|(h, c)| format!("{} = {}", h, c))
+          .join("; ")
+      })
+    ];
+  }
+}
+
+impl Default for ContextInputData {
+  fn default() -> Self {
+    return (&SalesContext::default()).into();
+  }
+}
+
+impl ContextInputData {
+  /// Turns a price as typed into cents: reais get multiplied (and rounded),
+  /// centavos are taken as they are.
+  pub fn to_cents(&self, price: f64) -> usize {
+    return match self.cents {
+      true => price as usize,
+      false => (price * 100.0).round() as usize
+    };
+  }
+
+  /// The quick-entry version of the prices: promo first, if there's one.
+  pub fn quick_prices(&self) -> String {
+    let mut v: Vec<f64> = Vec::new();
+    if self.promo_enabled {
+      v.push(self.promo_price);
+    }
+    v.extend(self.rows.iter());
+    return join_prices(&v);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::sale::price_deriving::PricingCandidate;
+
+  #[test]
+  fn promo_not_cheaper_is_soft() {
+    let data = ContextInputData {
+      promo_price: 60.0,
+      rows: vec![55.0, 65.0],
+      ..Default::default()
+    };
+    let ctx = SalesContext::try_from(data).unwrap();
+    assert!(ctx.validate().contains(&ContextWarning::PromoNotCheaper));
+    assert!(!ContextWarning::PromoNotCheaper.is_hard());
+    assert_eq!(PricingCandidate::from_price(6500, &ctx).count(), 1);
+    assert!(matches!(
+      PricingCandidate::from_price(11500, &ctx),
+      PricingCandidate::Precise(PricingMatch::PromoCombo(_, _))
+    ));
+  }
+
+  #[test]
+  fn first_price_is_promo_only_with_a_promo() {
+    let data = ContextInputData {
+      promo_enabled: false,
+      rows: vec![55.0, 65.0, 75.0],
+      ..Default::default()
+    };
+    let ctx = SalesContext::try_from(data).unwrap();
+    assert!(!ctx.has_promo);
+    assert_eq!(ctx.batches.get(&BatchNum::Numbered(1)), Some(&5500));
+    assert_eq!(ctx.batches.get(&BatchNum::Promo), None);
+    // 55 + 65 is a turn of batch now, not a promo combo
+    assert!(matches!(
+      PricingCandidate::from_price(12000, &ctx),
+      PricingCandidate::Precise(PricingMatch::TurnOfBatch(_, _))
+    ));
+    let ctx = SalesContext::try_from(ContextInputData {
+      promo_price: 55.0,
+      rows: vec![65.0, 75.0],
+      ..Default::default()
+    }).unwrap();
+    assert!(ctx.has_promo);
+    assert_eq!(ctx.batches.get(&BatchNum::Promo), Some(&5500));
+    assert!(matches!(
+      PricingCandidate::from_price(12000, &ctx),
+      PricingCandidate::Precise(PricingMatch::PromoCombo(_, _))
+    ));
+  }
+
+  #[test]
+  fn promo_enabled_and_unlimited() {
+    let combos = [(true, false), (true, true), (false, false), (false, true)];
+    for (enabled, unlimited) in combos {
+      let data = ContextInputData {
+        promo_enabled: enabled,
+        promo_unlimited: unlimited,
+        promos: 2.0,
+        ..Default::default()
+      };
+      let ctx = SalesContext::try_from(data.clone()).unwrap();
+      assert_eq!(ctx.has_promo, enabled);
+      assert_eq!(ctx.batches.contains_key(&BatchNum::Promo), enabled);
+      assert_eq!(ctx.promo_limit, (!unlimited).then_some(2));
+      let back = ContextInputData::from(&ctx);
+      assert_eq!(
+        (back.promo_enabled, back.promo_unlimited),
+        (enabled, unlimited)
+      );
+      // the limit is only checked when there's one to speak of
+      let zero = ContextInputData { promos: 0.0, ..data };
+      assert_eq!(SalesContext::try_from(zero).is_ok(), !enabled || unlimited);
+    }
+  }
+
+  #[test]
+  fn quick_entry_needs_a_promo_and_a_batch() {
+    assert_eq!(parse_quick("55; 65;77.5"), Ok(vec![55.0, 65.0, 77.5]));
+    assert_eq!(parse_quick("55"), Err(tr("ctx_quick_short").to_owned()));
+    assert_eq!(parse_quick("55;0"), Err(tr("ctx_bad_prices").to_owned()));
+  }
+
+  #[test]
+  fn prices_in_reais_or_cents() {
+    let reais = ContextInputData {
+      promo_price: 55.1,
+      rows: vec![65.35, 75.0],
+      commission: 2.5,
+      ..Default::default()
+    };
+    let cents = ContextInputData {
+      cents: true,
+      promo_price: 5510.0,
+      rows: vec![6535.0, 7500.0],
+      commission: 250.0,
+      ..Default::default()
+    };
+    let from_reais = SalesContext::try_from(reais).unwrap();
+    let from_cents = SalesContext::try_from(cents.clone()).unwrap();
+    assert_eq!(from_reais, from_cents);
+    assert_eq!(from_cents.batches.get(&BatchNum::Numbered(1)), Some(&6535));
+    assert_eq!(from_cents.commission, Commission::PerTicket(250));
+    let half_cent = ContextInputData { rows: vec![6535.5], ..cents };
+    assert!(SalesContext::try_from(half_cent).is_err());
+  }
+
+  #[test]
+  fn json_round_trip() {
+    let mut data = ContextInputData {
+      offline_fee: 0.05,
+      capacities: vec![0.0, 300.0],
+      promo_unlimited: true,
+      max_span: 3.0,
+      solver: "global".to_owned(),
+      dedup_key: DedupKey::Token,
+      utc_offset: 5.5,
+      commission: 7.5,
+      commission_percent: true,
+      ..Default::default()
+    };
+    data.columns.insert(normalize_header("Pago"), Column::Value);
+    let pm = PricingMatch::all_priced(13000, &SalesContext::default())
+      .remove(0);
+    data.overrides.insert(13000, pm);
+    let ctx = SalesContext::try_from(data).unwrap();
+    assert_eq!(ctx.overrides.len(), 1);
+    let json = ctx.to_json();
+    let raw: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(raw["v"].as_u64(), Some(CONTEXT_FILE_VERSION as u64));
+    assert_eq!(SalesContext::from_json(&json), Ok(ctx));
+  }
+
+  #[test]
+  fn json_is_friendly() {
+    let raw: serde_json::Value =
+      serde_json::from_str(&SalesContext::default().to_json()).unwrap();
+    assert_eq!(raw["batches"]["promo"], 5500);
+    assert_eq!(raw["batches"]["3"], 8500);
+    // fields missing from older files keep their defaults
+    let old = r#"{"v": 1, "batches": {"promo": 5000, "1": 6000}}"#;
+    let ctx = SalesContext::from_json(old).unwrap();
+    assert_eq!(ctx.batches.get(&BatchNum::Numbered(1)), Some(&6000));
+    assert_eq!(ctx.max_span, SalesContext::default().max_span);
+    assert_eq!(ctx.solver, AmbiguitySolver::default());
+    let garbage = SalesContext::from_json("{").unwrap_err();
+    assert!(garbage.starts_with(&trf("ctx_json_invalid", &[&""])));
+  }
+
+  #[test]
+  fn json_needs_a_known_version() {
+    let ctx = SalesContext::default();
+    let mut raw: serde_json::Value =
+      serde_json::from_str(&ctx.to_json()).unwrap();
+    let newer = CONTEXT_FILE_VERSION + 1;
+    raw["v"] = newer.into();
+    assert_eq!(
+      SalesContext::from_json(&raw.to_string()),
+      Err(trf("ctx_json_newer", &[&newer, &CONTEXT_FILE_VERSION]))
+    );
+    raw.as_object_mut().unwrap().remove("v");
+    assert_eq!(
+      SalesContext::from_json(&raw.to_string()),
+      Err(tr("ctx_json_no_version").to_owned())
+    );
+  }
+}
@@ -0,0 +1,61 @@
+//! The one error type everything in here comes back with, so callers can tell
+//! what went wrong by looking at the variant instead of reading the message.
+
+use std::error::Error;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::context::ContextError;
+use crate::i18n::{tr, trf};
+use crate::sale::error::ParseError;
+
+/// Something that went wrong anywhere between the form and the report. The
+/// messages are only put together (and translated) when displayed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum D4Error {
+  /// A CSV line that didn't make a sale: unreadable, wrong column count, bad
+  /// value, bad date... the kind says which.
+  Line(ParseError),
+  /// A context field that doesn't make sense.
+  Context(ContextError),
+  /// The CSV had no sales in it at all.
+  NoSales,
+  /// The loader was asked for more after it was done.
+  NothingToDo,
+  /// One of the output CSVs couldn't be written. Holds why.
+  Export(String),
+  /// Anything else, as a message.
+  Other(String)
+}
+
+impl Display for D4Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      D4Error::Line(pe) => pe.fmt(f),
+      D4Error::Context(ce) => ce.fmt(f),
+      D4Error::NoSales => write!(f, "{}", tr("no_sales")),
+      D4Error::NothingToDo => write!(f, "{}", tr("nothing_to_do")),
+      D4Error::Export(s) => write!(f, "{}", trf("csv_failed", &[s])),
+      D4Error::Other(s) => write!(f, "{}", s),
+    };
+  }
+}
+
+impl Error for D4Error {}
+
+impl From<ParseError> for D4Error {
+  fn from(pe: ParseError) -> Self {
+    return Self::Line(pe);
+  }
+}
+
+impl From<csv::Error> for D4Error {
+  fn from(e: csv::Error) -> Self {
+    return Self::Line(e.into());
+  }
+}
+
+impl From<ContextError> for D4Error {
+  fn from(ce: ContextError) -> Self {
+    return Self::Context(ce);
+  }
+}
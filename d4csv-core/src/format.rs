@@ -0,0 +1,102 @@
+//! Formatting numbers and money for people to read. Exports (CSVs, URLs)
+//! keep using plain numbers, so spreadsheets don't choke.
+
+/// How numbers are written in some locale.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NumberLocale {
+  /// Goes between groups of three digits.
+  pub thousands: char,
+  /// Goes between the integer part and the cents.
+  pub decimal: char,
+  /// Goes before money amounts.
+  pub currency: &'static str
+}
+
+/// The way it's done around here: "R$ 1.234.567,89".
+pub static PT_BR: NumberLocale = NumberLocale {
+  thousands: '.',
+  decimal: ',',
+  currency: "R$"
+};
+
+impl Default for NumberLocale {
+  fn default() -> Self {
+    return PT_BR;
+  }
+}
+
+impl NumberLocale {
+  /// Writes an integer with its thousands grouped, like "31.024".
+  pub fn int(&self, n: usize) -> String {
+    let digits = n.to_string();
+    let mut res = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+      if i > 0 && (digits.len() - i).is_multiple_of(3) {
+        res.push(self.thousands);
+      }
+      res.push(c);
+    }
+    return res;
+  }
+
+  /// Writes a count that may be fractional, to one decimal place, like
+  /// "1.234,5". Whole counts come out as integers.
+  pub fn tenths(&self, x: f64) -> String {
+    let t = (x.max(0.0) * 10.0).round() as usize;
+    return match t % 10 {
+      0 => self.int(t / 10),
+      d => format!("{}{}{}", self.int(t / 10), self.decimal, d)
+    };
+  }
+
+  /// Writes an amount in cents as money, like "R$ 1.234.567,89".
+  pub fn money(&self, cents: usize) -> String {
+    return format!(
+      "{} {}{}{:02}",
+      self.currency,
+      self.int(cents / 100),
+      self.decimal,
+      cents % 100
+    );
+  }
+}
+
+/// Writes an integer the default way.
+pub fn int(n: usize) -> String {
+  return NumberLocale::default().int(n);
+}
+
+/// Writes an amount in cents as money, the default way.
+pub fn money(cents: usize) -> String {
+  return NumberLocale::default().money(cents);
+}
+
+/// Writes a maybe fractional count, the default way.
+pub fn tenths(x: f64) -> String {
+  return NumberLocale::default().tenths(x);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn groups_thousands() {
+    assert_eq!(int(0), "0");
+    assert_eq!(int(999), "999");
+    assert_eq!(int(1000), "1.000");
+    assert_eq!(int(31024), "31.024");
+    assert_eq!(int(1234567), "1.234.567");
+  }
+
+  #[test]
+  fn money_and_tenths() {
+    assert_eq!(money(5), "R$ 0,05");
+    assert_eq!(money(123456789), "R$ 1.234.567,89");
+    assert_eq!(tenths(1234.5), "1.234,5");
+    assert_eq!(tenths(1234.0), "1.234");
+    assert_eq!(tenths(-3.0), "0");
+    let en = NumberLocale { thousands: ',', decimal: '.', currency: "$" };
+    assert_eq!(en.money(123456789), "$ 1,234,567.89");
+  }
+}
@@ -0,0 +1,868 @@
+//! A tiny translation layer. Portuguese is the default, English is there for
+//! whoever can't read it.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+use serde::{Deserialize, Serialize};
+
+/// The language in use. A global, so the report fields and the worker can
+/// get at it without a yew context.
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+/// The languages the app speaks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lang {
+  /// Portuguese (Brazil).
+  Pt,
+  /// English.
+  En
+}
+
+impl Default for Lang {
+  fn default() -> Self {
+    return Self::Pt;
+  }
+}
+
+impl Display for Lang {
+  /// Each language in itself.
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", match self {
+      Lang::Pt => "português",
+      Lang::En => "English",
+    });
+  }
+}
+
+impl Lang {
+  /// The language in use right now.
+  pub fn current() -> Self {
+    return match CURRENT.load(Ordering::Relaxed) {
+      1 => Lang::En,
+      _ => Lang::Pt,
+    };
+  }
+
+  /// Makes this the language in use.
+  pub fn set_current(&self) {
+    CURRENT.store(match self {
+      Lang::Pt => 0,
+      Lang::En => 1,
+    }, Ordering::Relaxed);
+  }
+
+  /// The one that isn't this one.
+  pub fn other(&self) -> Self {
+    return match self {
+      Lang::Pt => Lang::En,
+      Lang::En => Lang::Pt,
+    };
+  }
+}
+
+/// Translates a key into the language in use. Unknown keys come back as they
+/// are, so they stand out.
+pub fn tr(key: &'static str) -> &'static str {
+  let (pt, en) = match key {
+    // wrapper
+    "title" => ("parser do csv da D4", "D4 CSV parser"),
+    "tagline" => ("eu preciso dormir, cara. ", "I need some sleep, man. "),
+    "open_source" => (
+      "ah, e o código é aberto.",
+      "oh, and it's open source."
+    ),
+    // input screen
+    "quote_double" => ("aspas duplas", "double quotes"),
+    "quote_single" => ("aspas simples", "single quotes"),
+    "quote_none" => ("sem aspas", "no quotes"),
+    "fix_context" => (
+      "corrija os campos do contexto acima",
+      "fix the context fields above"
+    ),
+    "paste_csv" => ("cole o CSV", "paste the CSV"),
+    "check_price" => ("testar um preço", "check a price"),
+    "csv_quotes" => ("aspas no CSV: ", "CSV quoting: "),
+    "csv_has_header" => (
+      " a primeira linha do CSV é o cabeçalho",
+      " the first CSV line is the header"
+    ),
+    "go" => ("bora", "go"),
+    "clear" => ("limpar", "clear"),
+    // errors and progress
+    "went_wrong" => ("deu ruim", "something went wrong"),
+    "back_and_fix" => ("voltar e corrigir", "go back and fix it"),
+    "cancel" => ("cancelar", "cancel"),
+    "back" => ("voltar", "back"),
+    "misconfigured" => (
+      "Só {}% das vendas foram decifradas. O contexto deve estar errado!",
+      "Only {}% of the sales were decoded. The context must be wrong!"
+    ),
+    "culprit_fee" => (
+      "A taxa online bate com a da fatura?",
+      "Does the online fee match the invoice?"
+    ),
+    "culprit_prices" => (
+      "Os preços dos lotes estão em reais (não em centavos)?",
+      "Are the batch prices in reais (not in cents)?"
+    ),
+    "culprit_delimiter" => (
+      "O CSV foi colado inteiro, com as aspas certas?",
+      "Was the whole CSV pasted, with the right quotes?"
+    ),
+    "fix_context_now" => ("corrigir o contexto", "fix the context"),
+    "error_lines" => ("linhas: {}", "lines: {}"),
+    "more_lines" => ("mais {} linhas", "{} more lines"),
+    "line_n" => ("linha {}: ", "line {}: "),
+    "err_columns" => (
+      "{} colunas em vez de {} (o delimitador está certo?)",
+      "{} columns instead of {} (is the delimiter right?)"
+    ),
+    "err_value" => ("valor da compra inválido", "invalid sale value"),
+    "err_date" => (
+      "data inválida (o formato de data está certo?)",
+      "invalid date (is the date format right?)"
+    ),
+    "err_csv" => (
+      "CSV mal formado (as aspas estão certas?)",
+      "malformed CSV (is the quoting right?)"
+    ),
+    "no_sales" => (
+      "nenhuma linha do CSV virou uma venda!",
+      "not a single CSV line became a sale!"
+    ),
+    "nothing_to_do" => ("nada a fazer!", "nothing to do!"),
+    "csv_failed" => (
+      "falha ao gerar CSV: {}",
+      "couldn't generate the CSV: {}"
+    ),
+    "progress_parsing" => (
+      "lendo o CSV: {} linhas",
+      "reading the CSV: {} lines"
+    ),
+    "progress_pricing" => (
+      "calculando preços: {}/{} vendas, {} preços distintos",
+      "pricing: {}/{} sales, {} distinct prices"
+    ),
+    "progress_solving" => (
+      "resolvendo ambiguidades: passe {}, {} resolvidas, {} reduzidas",
+      "solving ambiguities: pass {}, {} resolved, {} narrowed"
+    ),
+    "progress_reporting" => ("gerando o relatório", "building the report"),
+    // report screen
+    "ran_with" => ("executado com: {}", "ran with: {}"),
+    "needs_attention" => (
+      "{} vendas precisam de atenção ({} revisadas)",
+      "{} sales need attention ({} reviewed)"
+    ),
+    "reviewed" => ("revisada", "reviewed"),
+    "run_again" => ("rodar de novo", "run again"),
+    "filter" => ("filtrar: ", "filter: "),
+    "from" => ("de ", "from "),
+    "until" => (" até ", " until "),
+    "clear_filter" => ("limpar filtro", "clear filter"),
+    "filtered_report" => (
+      "relatório filtrado, não são os totais! ",
+      "filtered report, these aren't the totals! "
+    ),
+    "better_csv" => ("CSV melhorado:", "Better CSV:"),
+    "search_placeholder" => (
+      "token, ID, e-mail ou nome do comprador",
+      "token, ID, buyer e-mail or name"
+    ),
+    "found" => (" {} encontradas", " {} found"),
+    "no_sales_found" => ("nenhuma venda encontrada", "no sales found"),
+    "download_better_csv" => ("baixar CSV melhorado", "download better CSV"),
+    "download_anon_csv" => ("exportar anonimizado", "export anonymized"),
+    "download_seller_csv" => (
+      "baixar resumo por vendedor (CSV)",
+      "download per-seller summary (CSV)"
+    ),
+    "tab_summary" => ("Resumo", "Summary"),
+    "tab_tables" => ("Tabelas", "Tables"),
+    "tab_csv" => ("CSV", "CSV"),
+    "tab_problems" => ("Problemas", "Problems"),
+    "no_problems" => ("nenhum problema!", "no problems!"),
+    "tab_no_contact" => ("Sem contato", "No contact"),
+    "no_no_contact" => (
+      "todas as vendas têm e-mail ou usuário!",
+      "every sale has an e-mail or username!"
+    ),
+    "tab_narrowed" => ("Reduzidas", "Narrowed"),
+    "tab_free" => ("Valor zero", "Zero value"),
+    "no_free" => (
+      "nenhuma venda de valor zero!",
+      "no zero-value sales!"
+    ),
+    "no_narrowed" => (
+      "nenhuma venda ficou só parcialmente resolvida!",
+      "no sale was left only partly resolved!"
+    ),
+    "download_no_contact_csv" => (
+      "baixar vendas sem contato (CSV)",
+      "download sales with no contact (CSV)"
+    ),
+    "previous" => ("‹ anterior", "‹ previous"),
+    "next" => ("próxima ›", "next ›"),
+    "page_of" => (" página {} de {} ", " page {} of {} "),
+    "no_rows" => ("nenhuma linha", "no rows"),
+    "rows_range" => ("linhas {} a {} de {}", "rows {} to {} of {}"),
+    // filter
+    "filter_sellers" => ("vendedores: {}", "sellers: {}"),
+    "filter_from" => ("de {}", "from {}"),
+    "filter_until" => ("até {}", "until {}"),
+    "none_m" => ("nenhum", "none"),
+    "none_f" => ("nenhuma", "none"),
+    // stamp
+    "filter_active" => ("FILTRO ATIVO", "FILTER ACTIVE"),
+    "generated_at" => ("Gerado em", "Generated at"),
+    "rows_read" => ("Linhas lidas do CSV", "CSV lines read"),
+    "build" => ("Versão", "Build"),
+    "cli_missing_flag" => ("faltou {}", "{} is missing"),
+    "cli_missing_value" => ("faltou o valor de {}", "{} needs a value"),
+    "cli_bad_value" => ("valor ruim pra {}: {}", "bad value for {}: {}"),
+    "cli_unknown_flag" => ("opção desconhecida: {}", "unknown option: {}"),
+    "cli_bad_context" => ("contexto inválido: {}", "invalid context: {}"),
+    "cli_read" => ("não deu pra ler {}: {}", "couldn't read {}: {}"),
+    "cli_write" => ("não deu pra escrever {}: {}", "couldn't write {}: {}"),
+    "cli_too_many_errors" => (
+      "{} linhas com erro, mais que as {} toleradas",
+      "{} broken lines, more than the {} allowed"
+    ),
+    "cli_usage" => (
+      "\
+uso: d4csv-cli --input vendas.csv [opções]
+
+  --input ARQ          CSV de vendas da D4 (obrigatório)
+  --prices P1,P2,...   preços dos lotes em reais; com promo, o 1º é o promo
+  --cents              preços em centavos em vez de reais
+  --capacities C1,...  capacidades dos lotes numerados (0 = desconhecida)
+  --fee F              taxa online, como fração (0.1 = 10%)
+  --offline-fee F      taxa das vendas físicas, como fração (padrão: 0)
+  --no-promo           não tem lote promo
+  --promo-limit N      promos permitidos por venda
+  --promo-unlimited    promos sem limite por venda
+  --no-combo           sem compras de promo + 1º lote
+  --max-span N         máximo de lotes seguidos numa compra (padrão: 2)
+  --solver NOME        nothing, temporal, seller, anchor, popularity ou
+                       global
+  --per-day            um vendedor online por dia
+  --force-kind K       detect, online ou offline: força o tipo das vendas
+  --dedup K            none, id ou token: descarta vendas repetidas
+  --free-tickets       vendas de valor zero contam como um ingresso
+  --date-format FMT    formato das datas no CSV (strftime)
+  --utc-offset H       fuso do evento, em horas (padrão: -3)
+  --commission C       comissão dos pontos de venda, por ingresso
+  --commission-pct P   comissão dos pontos de venda, em % da receita
+  --column CAB=CAMPO   lê a coluna CAB como CAMPO (date, value, token...)
+  --quote Q            aspas do CSV: double, single ou none
+  --no-header          o CSV não tem cabeçalho
+  --max-errors N       linhas com erro toleradas (padrão: 10)
+  --lang L             pt ou en
+  --out ARQ            onde escrever o CSV melhorado
+  --sellers ARQ        onde escrever o CSV por vendedor
+  --anon ARQ           onde escrever o CSV melhorado sem dados pessoais
+  --no-contact ARQ     onde escrever as vendas sem e-mail nem usuário
+  --report ARQ         onde escrever o relatório em JSON
+  --excel              CSVs com CRLF e BOM, pro Excel
+  --net                valores sem as taxas (padrão: com)
+",
+      "\
+usage: d4csv-cli --input sales.csv [options]
+
+  --input FILE         D4 sales CSV (required)
+  --prices P1,P2,...   batch prices in reais; with a promo, the 1st is it
+  --cents              prices in cents instead of reais
+  --capacities C1,...  numbered batch capacities (0 = unknown)
+  --fee F              online fee, as a fraction (0.1 = 10%)
+  --offline-fee F      fee on face-to-face sales, as a fraction (default: 0)
+  --no-promo           there's no promo batch
+  --promo-limit N      promos allowed per sale
+  --promo-unlimited    no limit on promos per sale
+  --no-combo           no promo + 1st batch purchases
+  --max-span N         most batches in a row in one purchase (default: 2)
+  --solver NAME        nothing, temporal, seller, anchor, popularity or
+                       global
+  --per-day            one online seller per day
+  --force-kind K       detect, online or offline: forces the sales' kind
+  --dedup K            none, id or token: drops repeated sales
+  --free-tickets       zero-value sales count as one ticket
+  --date-format FMT    format of the CSV's dates (strftime)
+  --utc-offset H       the event's time zone, in hours (default: -3)
+  --commission C       points of sale's commission, per ticket
+  --commission-pct P   points of sale's commission, in % of revenue
+  --column HDR=FIELD   reads column HDR as FIELD (date, value, token...)
+  --quote Q            CSV quotes: double, single or none
+  --no-header          the CSV has no header
+  --max-errors N       broken lines allowed (default: 10)
+  --lang L             pt or en
+  --out FILE           where to write the better CSV
+  --sellers FILE       where to write the per-seller CSV
+  --anon FILE          where to write the better CSV without personal data
+  --no-contact FILE    where to write the sales with no e-mail or username
+  --report FILE        where to write the report as JSON
+  --excel              CSVs with CRLF and a BOM, for Excel
+  --net                values without the fees (default: with)
+"
+    ),
+    "excel_compatible" => ("Excel-compatível", "Excel-compatible"),
+    "headers_lang" => ("Idioma dos cabeçalhos", "Header language"),
+    "net_values" => (
+      "valores líquidos (sem as taxas online)",
+      "net values (without the online fees)"
+    ),
+    "revenue_gross" => ("bruto, com taxas", "gross, fees included"),
+    "revenue_net" => ("líquido, sem taxas", "net, without fees"),
+    "revenue_mode" => ("Valores", "Money values"),
+    // string fields
+    "total_sales" => ("Total de vendas", "Total sales"),
+    "decoded_sales" => ("Vendas decodificadas", "Decoded sales"),
+    "duplicates" => ("Vendas repetidas descartadas", "Repeated sales dropped"),
+    "no_contact" => (
+      "Vendas sem contato do comprador",
+      "Sales with no buyer contact"
+    ),
+    "narrowed" => (
+      "Vendas com alternativas reduzidas, mas não resolvidas",
+      "Sales narrowed down, but not resolved"
+    ),
+    "narrowed_detail" => (
+      "{}, de {} para {} alternativas no total",
+      "{}, from {} down to {} alternatives in total"
+    ),
+    "total_tickets" => ("Total de ingressos", "Total tickets"),
+    "online_tickets" => ("Ingressos online", "Online tickets"),
+    "offline_tickets" => ("Ingressos físicos", "Offline tickets"),
+    "promo_tickets" => ("Ingressos promocionais", "Promo tickets"),
+    "total_revenue" => ("Receita total", "Total revenue"),
+    "average_ticket_value" => (
+      "Valor médio por ingresso",
+      "Average ticket value"
+    ),
+    "ambiguous_sales" => ("Vendas ambíguas", "Ambiguous sales"),
+    "unsolvable_sales" => ("Vendas sem solução", "Unsolvable sales"),
+    "free_sales" => ("Vendas de valor zero", "Zero-value sales"),
+    "timestamp_quality" => ("Qualidade dos horários", "Timestamp quality"),
+    "timestamp_detail" => (
+      "{} empates ({}%), {} fora de ordem no arquivo ({}%)",
+      "{} ties ({}%), {} out of order in the file ({}%)"
+    ),
+    "timestamp_warn" => (
+      " -- CUIDADO! os horários podem estar errados",
+      " -- CAREFUL! the timestamps may be wrong"
+    ),
+    "clock_skew" => (
+      "Desvio de relógio online/offline",
+      "Online/offline clock skew"
+    ),
+    "clock_skew_few" => (
+      "poucas vendas de um dos lados pra estimar",
+      "too few sales on one side to tell"
+    ),
+    "clock_skew_none" => ("nenhum desvio aparente", "no apparent skew"),
+    "token_collisions" => ("Tokens repetidos", "Repeated tokens"),
+    "fee_rounding" => ("Diferença de arredondamento", "Rounding discrepancy"),
+    "fee_rounding_detail" => (
+      "{} em {} de {} vendas online resolvidas",
+      "{} over {} of {} resolved online sales"
+    ),
+    "fees_paid" => ("Taxas pagas", "Fees paid"),
+    "fees_paid_detail" => (
+      "{} em {} vendas resolvidas com taxa",
+      "{} over {} resolved sales with a fee"
+    ),
+    "token_collisions_none" => ("nenhum", "none"),
+    "token_collisions_warn" => (
+      concat!(
+        "{} tokens em vendas com IDs diferentes ({}) ",
+        "-- CUIDADO! venda duplicada ou exportação com problema?"
+      ),
+      concat!(
+        "{} tokens on sales with different IDs ({}) ",
+        "-- CAREFUL! double sales, or a broken export?"
+      )
+    ),
+    "clock_skew_warn" => (
+      concat!(
+        "os horários offline parecem deslocados {}h em relação aos online ",
+        "-- CUIDADO! os métodos temporais podem errar"
+      ),
+      concat!(
+        "offline times look shifted by {}h from online ones ",
+        "-- CAREFUL! the temporal solvers may go wrong"
+      )
+    ),
+    "run_status" => ("Execução", "Run"),
+    "interrupted_by_time" => (
+      "interrompido por tempo, resultados parciais",
+      "interrupted for time, partial results"
+    ),
+    "interrupted_warn" => (
+      concat!(
+        "CUIDADO! interrompido por tempo -- isso demorou demais, então o ",
+        "relatório só tem o que deu pra calcular até ali"
+      ),
+      concat!(
+        "CAREFUL! interrupted for time -- this took too long, so the report ",
+        "only has what could be worked out until then"
+      )
+    ),
+    "csv_header" => ("Cabeçalho do CSV", "CSV header"),
+    "csv_header_warn" => (
+      concat!(
+        "CUIDADO! o cabeçalho parece uma venda -- se o CSV não tem ",
+        "cabeçalho, desmarque a opção para não perder a primeira linha"
+      ),
+      concat!(
+        "CAREFUL! the header looks like a sale -- if the CSV has no ",
+        "header, uncheck the option so the first line isn't lost"
+      )
+    ),
+    "csv_header_unmapped" => (
+      concat!(
+        "colunas lidas pela posição (não reconhecidas: {}) -- se algo ",
+        "saiu errado, associe-as no contexto"
+      ),
+      concat!(
+        "columns read by position (unrecognized: {}) -- if something ",
+        "went wrong, map them in the context"
+      )
+    ),
+    "csv_header_ignored" => (
+      "ok, colunas ignoradas: {}",
+      "ok, ignored columns: {}"
+    ),
+    // table fields
+    "tickets_per_seller" => (
+      "Ingressos físicos por ponto de venda",
+      "Offline tickets per point of sale"
+    ),
+    "tickets_per_hour" => ("Ingressos por hora do dia", "Tickets per hour"),
+    "commission_per_seller" => (
+      "comissão por ponto de venda ({})",
+      "commission per point of sale ({})"
+    ),
+    "resolution_per_batch" => (
+      "Vendas por lote e como foram resolvidas",
+      "Sales per batch, by how they were resolved"
+    ),
+    "res_precise" => ("exatas", "precise"),
+    "res_solved" => ("pelo método", "by the solver"),
+    "res_ambiguous" => ("ambíguas", "ambiguous"),
+    "batch_median_times" => (
+      "Horário mediano das vendas de cada lote",
+      "Median sale time for each batch"
+    ),
+    "fee_residuals" => (
+      "Vendas online por resíduo (centavos além do preço com taxa)",
+      "Online sales by residual (cents over the price with the fee)"
+    ),
+    "effective_fee" => (
+      "Taxa online efetiva (vendas)",
+      "Effective online fee (sales)"
+    ),
+    // matrix fields
+    "tickets_per_batch_kind" => (
+      "Ingressos por lote e tipo de venda",
+      "Tickets per batch and kind of sale"
+    ),
+    "online" => ("Online", "Online"),
+    "offline" => ("Físico", "Offline"),
+    "total" => ("Total", "Total"),
+    "collision_prices" => (
+      "Preços intrinsecamente ambíguos",
+      "Inherently ambiguous prices"
+    ),
+    "compositions" => ("Composições", "Compositions"),
+    "batch_capacities" => (
+      "Ingressos vendidos e capacidade por lote",
+      "Tickets sold and capacity per batch"
+    ),
+    "sold" => ("Vendidos", "Sold"),
+    "capacity" => ("Capacidade", "Capacity"),
+    "situation" => ("Situação", "Status"),
+    "overflowed" => ("ESTOUROU em {}!", "OVER by {}!"),
+    "left_over" => ("ok, sobraram {}", "ok, {} left"),
+    "batch_promo" => ("lote promocional", "promo batch"),
+    "batch_n" => ("{}º lote", "batch #{}"),
+    // better and seller CSVs
+    "yes" => ("sim", "yes"),
+    "no" => ("não", "no"),
+    "or" => ("  ou  ", "  or  "),
+    "partial" => ("parcial", "partial"),
+    "tragedy" => ("TRAGÉDIA", "TRAGEDY"),
+    "free_sale" => ("cortesia/teste", "courtesy/test"),
+    "h_date" => ("DataCompra", "PurchaseDate"),
+    "h_buyer_email" => ("EmailUsuarioAssociado", "BuyerEmail"),
+    "h_buyer_name" => ("NomeUsuarioAssociado", "BuyerName"),
+    "h_value" => ("ValorDaCompra", "PurchaseValue"),
+    "h_status" => ("Status", "Status"),
+    "h_change" => ("Mudança", "Change"),
+    "h_before" => ("Antes", "Before"),
+    "h_after" => ("Depois", "After"),
+    "h_seller_name" => ("NomeVendedor", "SellerName"),
+    "h_seller_id" => ("IDVendedor", "SellerID"),
+    "h_seller_email" => ("EmailVendedor", "SellerEmail"),
+    "h_token" => ("Token", "Token"),
+    "h_id" => ("ID", "ID"),
+    "h_card_name" => ("NomeCartao", "CardName"),
+    "h_card_pfx" => ("PrimDigitosCartao", "CardFirstDigits"),
+    "h_card_sfx" => ("UltDigitosCartao", "CardLastDigits"),
+    "h_subtotal" => ("ValorSemTaxa", "Subtotal"),
+    "h_resolved" => ("Resolvido?", "Resolved?"),
+    "h_decoding" => ("Decodificação de preço", "Price decoding"),
+    "h_alternatives" => ("Alternativas", "Alternatives"),
+    "h_ticket_price" => ("PrecoPorIngresso", "PricePerTicket"),
+    "h_fee_amount" => ("Taxa", "Fee"),
+    "h_residual" => ("ResíduoCentavos", "ResidualCents"),
+    "h_seller" => ("Vendedor", "Seller"),
+    "h_sales" => ("Vendas", "Sales"),
+    "h_tickets" => ("Ingressos", "Tickets"),
+    "h_revenue" => ("Receita", "Revenue"),
+    "h_batches" => ("Lotes", "Batches"),
+    "h_commission" => ("Comissao", "Commission"),
+    // solvers
+    "solver_nothing" => ("nenhum", "none"),
+    "solver_temporal" => ("olhar anteriores", "look behind"),
+    "solver_seller" => (
+      "olhar anteriores do mesmo ponto",
+      "look behind, per point of sale"
+    ),
+    "solver_anchor" => (
+      "ancorar as primeiras vendas e olhar anteriores do mesmo ponto",
+      "anchor the leading sales and look behind, per point of sale"
+    ),
+    "solver_popularity" => (
+      "olhar anteriores do mesmo ponto e desempatar pelo lote da moda",
+      "look behind, per point of sale, and break ties by the trending batch"
+    ),
+    "solver_global" => (
+      "lotes só avançam, em todos os pontos juntos (eventos só online)",
+      "batches only go forward, across all points of sale (online-only events)"
+    ),
+    // price checker
+    "kind_single" => ("lote único", "single batch"),
+    "kind_promo_combo" => ("promo + lote", "promo + batch"),
+    "kind_turn" => ("virada de lote", "batch turnover"),
+    "kind_multi" => ("vários lotes", "several batches"),
+    "trace_anchored" => (
+      "{}, pela venda {}; descartou: {}",
+      "{}, going by sale {}; dropped: {}"
+    ),
+    "trace_free" => ("{}; descartou: {}", "{}; dropped: {}"),
+    "kind_promo_turn" => (
+      "promo + virada de lote",
+      "promo + batch turnover"
+    ),
+    "fix_context_first" => (
+      "corrija o contexto primeiro",
+      "fix the context first"
+    ),
+    "compare_previous" => (
+      "comparar com a run anterior",
+      "compare with the previous run"
+    ),
+    "diff_none" => ("nenhuma venda mudou", "no sale changed"),
+    "diff_unmatched" => (
+      "{} vendas só na run anterior, {} só nesta",
+      "{} sales only in the previous run, {} only in this one"
+    ),
+    "change_resolved" => ("passou a resolvida", "got resolved"),
+    "change_unresolved" => ("deixou de ser resolvida", "got unresolved"),
+    "change_different" => ("resolvida de outro jeito", "resolved differently"),
+    "change_candidates" => ("candidatos mudaram", "candidates changed"),
+    "verdict_precise" => ("preciso", "precise"),
+    "verdict_ambiguous" => ("ambíguo", "ambiguous"),
+    "verdict_none" => ("nenhuma combinação", "no combination"),
+    "without_fee" => ("{} sem taxa: {}", "{} without the fee: {}"),
+    "decoding" => ("decodificação", "decoding"),
+    "tickets" => ("ingressos", "tickets"),
+    "kind" => ("tipo", "kind"),
+    "price_reais" => ("preço (R$): ", "price (R$): "),
+    "online_with_fee" => (" online (com taxa)", " online (fee included)"),
+    // context form
+    "ctx_bad_prices" => (
+      "preços inválidos! faça tipo: 55;65;77.5;100.0;101",
+      "invalid prices! do it like: 55;65;77.5;100.0;101"
+    ),
+    "ctx_quick_short" => (
+      "coloque o preço promocional e pelo menos um lote!",
+      "put in the promo price and at least one batch!"
+    ),
+    "ctx_bad_capacity" => ("capacidade inválida: {}", "invalid capacity: {}"),
+    "ctx_json_invalid" => (
+      "esse JSON de contexto não faz sentido: {}",
+      "that context JSON doesn't make sense: {}"
+    ),
+    "ctx_json_no_version" => (
+      "esse JSON não parece um contexto (falta o \"v\")",
+      "that JSON doesn't look like a context (no \"v\")"
+    ),
+    "ctx_json_newer" => (
+      "esse contexto é da versão {}, mas esta página só entende até a {}",
+      "that context is version {}, but this page only knows up to {}"
+    ),
+    "bad_batch_key" => ("lote desconhecido: {}", "unknown batch: {}"),
+    "bad_solver" => ("método desconhecido: {}", "unknown solver: {}"),
+    "export_context" => ("exportar contexto", "export context"),
+    "import_context" => ("importar contexto", "import context"),
+    "import_placeholder" => (
+      "cole aqui um contexto exportado",
+      "paste an exported context here"
+    ),
+    "ctx_negative_fee" => (
+      "a taxa web não pode ser negativa!",
+      "the web fee can't be negative!"
+    ),
+    "ctx_no_batches" => (
+      "adicione pelo menos um lote!",
+      "add at least one batch!"
+    ),
+    "ctx_promo_price" => (
+      "o preço promocional deve ser maior que zero!",
+      "the promo price must be greater than zero!"
+    ),
+    "ctx_promo_limit" => (
+      "o limite de promo deve ser um inteiro, 1 ou mais!",
+      "the promo limit must be an integer, 1 or more!"
+    ),
+    "ctx_price_zero" => (
+      "o {}º preço deve ser maior que zero!",
+      "price #{} must be greater than zero!"
+    ),
+    "ctx_promo_capacity" => (
+      "a capacidade do lote promocional deve ser um inteiro, 0 ou mais!",
+      "the promo batch capacity must be an integer, 0 or more!"
+    ),
+    "ctx_capacity" => (
+      "a capacidade do {}º lote deve ser um inteiro, 0 ou mais!",
+      "batch #{} capacity must be an integer, 0 or more!"
+    ),
+    "ctx_price_cents" => (
+      "o preço do {}º lote deve ser em centavos inteiros!",
+      "batch #{} price must be in whole cents!"
+    ),
+    "ctx_promo_cents" => (
+      "o preço promocional deve ser em centavos inteiros!",
+      "the promo price must be in whole cents!"
+    ),
+    "ctx_max_span" => (
+      "o máximo de lotes por compra deve ser um inteiro, 2 ou mais!",
+      "the most batches per purchase must be an integer, 2 or more!"
+    ),
+    "ctx_timezone" => (
+      "o fuso deve ficar entre UTC-12 e UTC+14!",
+      "the time zone must be between UTC-12 and UTC+14!"
+    ),
+    "ctx_commission" => (
+      "a comissão deve ser 0 ou mais (e no máximo 100%)!",
+      "the commission must be 0 or more (and at most 100%)!"
+    ),
+    "ctx_commission_cents" => (
+      "a comissão deve ser em centavos inteiros!",
+      "the commission must be in whole cents!"
+    ),
+    "ctx_stale_override" => (
+      "a regra manual {} = {} não bate mais com os lotes!",
+      "the manual rule {} = {} doesn't fit the batches anymore!"
+    ),
+    "ctx_name_preset" => (
+      "dê um nome pro preset antes de salvar!",
+      "name the preset before saving it!"
+    ),
+    "ctx_preset_builtin" => (
+      "\"{}\" é um preset embutido. escolha outro nome!",
+      "\"{}\" is a built-in preset. pick another name!"
+    ),
+    "ctx_preset_exists" => (
+      "já existe um preset \"{}\". sobrescrever?",
+      "there's already a preset \"{}\". overwrite it?"
+    ),
+    "d_webfee" => ("taxa web", "web fee"),
+    "d_offline_fee" => ("taxa física", "offline fee"),
+    "d_prices" => ("preços dos lotes", "batch prices"),
+    "d_unit" => ("unidade dos preços", "price unit"),
+    "unit_reais" => ("reais", "reais"),
+    "unit_cents" => ("centavos", "cents"),
+    "d_capacities" => ("capacidades", "capacities"),
+    "d_promo" => ("lote promocional", "promo batch"),
+    "d_promos" => ("promo/pessoa", "promo/person"),
+    "d_unlimited" => ("ilimitado", "unlimited"),
+    "d_combo" => ("promo + 1º lote", "promo + 1st batch"),
+    "d_solver" => (
+      "método para resolver ambiguidades",
+      "ambiguity solving method"
+    ),
+    "d_per_day" => ("online por dia", "online per day"),
+    "d_forced_kind" => ("tipo das vendas", "sale kinds"),
+    "d_dedup" => ("vendas repetidas", "repeated sales"),
+    "d_free_tickets" => (
+      "vendas de valor zero contam ingresso",
+      "zero-value sales count as a ticket"
+    ),
+    "d_date_format" => ("formato de data", "date format"),
+    "d_timezone" => ("fuso horário", "time zone"),
+    "d_overrides" => ("regras manuais", "manual rules"),
+    "d_max_span" => ("lotes por compra", "batches per purchase"),
+    "d_columns" => ("colunas associadas", "mapped columns"),
+    "d_commission" => ("comissão dos pontos", "point of sale commission"),
+    "commission_per_ticket" => ("{} por ingresso", "{} per ticket"),
+    "max_span_label" => (
+      "máximo de lotes seguidos numa compra: ",
+      "most batches in a row in one purchase: "
+    ),
+    "preset" => ("preset: ", "preset: "),
+    "preset_none" => ("(nenhum)", "(none)"),
+    "presets_builtin" => ("conhecidos", "known events"),
+    "presets_saved" => ("salvos", "saved"),
+    "delete" => ("excluir", "delete"),
+    "preset_name" => ("nome do preset", "preset name"),
+    "save_as" => ("salvar como", "save as"),
+    "webfee_label" => ("taxa web:", "web fee:"),
+    "offline_fee_label" => ("taxa física:", "offline fee:"),
+    "prices_label" => ("preços dos lotes:", "batch prices:"),
+    "batch" => ("lote", "batch"),
+    "price" => ("preço", "price"),
+    "price_col" => ("preço (R$)", "price (R$)"),
+    "price_col_cents" => ("preço (centavos)", "price (cents)"),
+    "cents_cb" => (" preços em centavos", " prices in cents"),
+    "capacity_col" => ("capacidade", "capacity"),
+    "add_batch" => ("adicionar lote", "add batch"),
+    "quick_entry" => ("entrada rápida: ", "quick entry: "),
+    "promo_batch_cb" => (" lote promocional", " promo batch"),
+    "promo_price_label" => ("preço promocional (R$): ", "promo price (R$): "),
+    "promo_price_cents" => (
+      "preço promocional (centavos): ",
+      "promo price (cents): "
+    ),
+    "promo_capacity_label" => (
+      "capacidade (0 = não sei): ",
+      "capacity (0 = don't know): "
+    ),
+    "dont_know" => ("0 = não sei", "0 = don't know"),
+    "promos_label" => ("promo/pessoa:", "promo/person:"),
+    "unlimited_cb" => (" ilimitado", " unlimited"),
+    "combo_cb" => (
+      " vende junto com o 1º lote",
+      " sold together with the 1st batch"
+    ),
+    "solver_label" => (
+      "método para resolver ambiguidades: ",
+      "ambiguity solving method: "
+    ),
+    "per_day_cb" => (
+      " separar as vendas online por dia",
+      " split online sales by day"
+    ),
+    "forced_kind_label" => ("tipo das vendas: ", "sale kinds: "),
+    "dedup_label" => ("vendas repetidas: ", "repeated sales: "),
+    "free_tickets_cb" => (
+      " contar vendas de valor zero (cortesias, testes) como um ingresso",
+      " count zero-value sales (courtesies, tests) as one ticket"
+    ),
+    "dedup_none" => ("manter todas", "keep them all"),
+    "dedup_id" => ("descartar por ID", "drop by ID"),
+    "dedup_token" => ("descartar por token", "drop by token"),
+    "kind_detect" => ("pela coluna de status", "by the status column"),
+    "kind_online" => ("todas online", "all online"),
+    "kind_offline" => ("todas físicas", "all offline"),
+    "commission_label" => (
+      "comissão dos pontos de venda por ingresso (0 = nenhuma): ",
+      "point of sale commission per ticket (0 = none): "
+    ),
+    "commission_pct_cb" => (
+      " em % da receita, não por ingresso",
+      " as % of revenue, not per ticket"
+    ),
+    "timezone_label" => ("fuso horário do evento: ", "event time zone: "),
+    "date_format_label" => (
+      "formato de data (vazio = RFC 3339): ",
+      "date format (empty = RFC 3339): "
+    ),
+    "reset_defaults" => ("restaurar padrão 2022", "restore the 2022 defaults"),
+    "fee_warning" => (
+      "taxa acima de 100%! não seria 0.1 em vez de 1.1?",
+      "fee over 100%! shouldn't it be 0.1 instead of 1.1?"
+    ),
+    "warn_zero_price" => (
+      "{} saiu de graça (casas decimais demais?)",
+      "{} came out free (too many decimal places?)"
+    ),
+    "warn_absurd_price" => (
+      "{} custa {}? parece erro de digitação",
+      "{} costs {}? looks like a typo"
+    ),
+    "warn_same_price" => (
+      concat!(
+        "{} e {} custam {}: vendas nesse preço não têm como ser ",
+        "distinguidas, então vão ficar ambíguas com qualquer método"
+      ),
+      concat!(
+        "{} and {} both cost {}: sales at that price can't be told apart, ",
+        "so they'll be ambiguous no matter the solver"
+      )
+    ),
+    "warn_decreasing" => (
+      "{} é mais barato que o {}; os lotes estão na ordem certa?",
+      "{} is cheaper than {}; are the batches in the right order?"
+    ),
+    "warn_promo_price" => (
+      "o lote promocional tem que ser mais barato que o 1º lote",
+      "the promo batch has to be cheaper than batch #1"
+    ),
+    "warn_collisions" => (
+      concat!(
+        "{} preços podem ser mais de uma compra de até {} ingressos, só ",
+        "pelo valor: {}"
+      ),
+      concat!(
+        "{} prices could be more than one purchase of up to {} tickets, ",
+        "going by the value alone: {}"
+      )
+    ),
+    "warn_promo_limit" => (
+      "limite de {} promos por venda? parece errado",
+      "a limit of {} promos per sale? looks wrong"
+    ),
+    "context_warning" => ("aviso do contexto", "context warning"),
+    "online_col" => ("online", "online"),
+    "offline_col" => ("físico", "offline"),
+    "remove" => ("remover", "remove"),
+    "overrides" => ("regras manuais de preço", "manual price rules"),
+    "override_price" => (
+      "preço sem taxa (R$): ",
+      "price without the fee (R$): "
+    ),
+    "override_price_cents" => (
+      "preço sem taxa (centavos): ",
+      "price without the fee (cents): "
+    ),
+    "always_this" => ("sempre essa", "always this one"),
+    "columns" => ("colunas do CSV", "CSV columns"),
+    "columns_missing" => (
+      "não achei no cabeçalho: {} -- diga que coluna é cada um",
+      "not found in the header: {} -- say which column each one is"
+    ),
+    _ => {
+      log::warn!("no translation for \"{}\"", key);
+      (key, key)
+    },
+  };
+  return match Lang::current() {
+    Lang::Pt => pt,
+    Lang::En => en,
+  };
+}
+
+/// Translates a key and fills its "{}" holes with the arguments, in order.
+pub fn trf(key: &'static str, args: &[&dyn Display]) -> String {
+  let mut parts = tr(key).split("{}");
+  let mut res = parts.next().unwrap_or_default().to_owned();
+  let mut args = args.iter();
+  for part in parts {
+    if let Some(a) = args.next() {
+      res.push_str(&a.to_string());
+    }
+    res.push_str(part);
+  }
+  return res;
+}
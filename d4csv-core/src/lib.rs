@@ -0,0 +1,27 @@
+//! Everything behind the types: parsing the CSV, inferring prices, solving
+//! ambiguities and computing reports. No browser stuff in here, so it builds
+//! and tests anywhere.
+
+#![allow(dead_code)]
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+pub mod ticket;
+pub mod sale;
+pub mod context;
+pub mod report;
+pub mod format;
+pub mod i18n;
+pub mod build_info;
+pub mod loader;
+pub mod error;
+pub mod budget;
+#[cfg(test)]
+mod testing;
+
+pub use context::SalesContext;
+pub use error::D4Error;
+pub use report::{Report, ReportTemplate};
+pub use sale::Sale;
+pub use sale::ambiguity::AmbiguitySolver;
+pub use sale::plus::SalesPlus;
@@ -0,0 +1,530 @@
+//! Turns the context and the CSV text into a report, a little bit at a time,
+//! so the browser gets to breathe in between.
+
+use std::fmt::Display;
+use std::io::Cursor;
+use std::rc::Rc;
+use csv::{Reader, ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+use crate::budget::Budget;
+use crate::context::SalesContext;
+use crate::error::D4Error;
+use crate::i18n::{tr, trf};
+use crate::report::{Report, ReportTemplate};
+use crate::sale::Sale;
+use crate::sale::columns::{ColumnMap, HeaderInfo};
+use crate::sale::dedup::dedup;
+use crate::sale::interner::Interner;
+use crate::sale::plus::SalesPlus;
+use crate::sale::price_deriving::PricingCandidateCache;
+
+/// How many CSV rows are parsed or priced before yielding.
+pub static CHUNK_ROWS: usize = 500;
+
+/// How fields are quoted in the input CSV.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteChar {
+  /// "Like this", the usual.
+  Double,
+  /// 'Like this'.
+  Single,
+  /// No quoting at all.
+  Nothing
+}
+
+impl Default for QuoteChar {
+  fn default() -> Self {
+    return Self::Double;
+  }
+}
+
+impl Display for QuoteChar {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      QuoteChar::Double => "quote_double",
+      QuoteChar::Single => "quote_single",
+      QuoteChar::Nothing => "quote_none",
+    }));
+  }
+}
+
+impl TryFrom<&str> for QuoteChar {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    return match s {
+      "double" => Ok(QuoteChar::Double),
+      "single" => Ok(QuoteChar::Single),
+      "none" => Ok(QuoteChar::Nothing),
+      _ => Err(())
+    };
+  }
+}
+
+impl QuoteChar {
+  pub fn name(&self) -> &'static str {
+    return match self {
+      QuoteChar::Double => "double",
+      QuoteChar::Single => "single",
+      QuoteChar::Nothing => "none",
+    };
+  }
+
+  pub fn available() -> impl Iterator<Item = Self> {
+    return [Self::Double, Self::Single, Self::Nothing].into_iter();
+  }
+
+  /// Sets up a CSV reader to use this quoting.
+  pub fn apply(&self, rb: &mut ReaderBuilder) {
+    match self {
+      QuoteChar::Double => rb.quoting(true).quote(b'\"'),
+      QuoteChar::Single => rb.quoting(true).quote(b'\''),
+      QuoteChar::Nothing => rb.quoting(false),
+    };
+  }
+}
+
+/// What the loader is doing right now, for showing the user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoadProgress {
+  /// Reading the CSV. Holds how many rows were read.
+  Parsing(usize),
+  /// Inferring prices. Holds (sales done, total sales, distinct prices).
+  Pricing(usize, usize, usize),
+  /// Running the solver. Holds (the pass about to run, sales resolved so
+  /// far, sales narrowed down but not resolved).
+  Solving(usize, usize, usize),
+  /// Computing the report fields.
+  Reporting
+}
+
+impl Display for LoadProgress {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", match self {
+      LoadProgress::Parsing(n) => trf("progress_parsing", &[n]),
+      LoadProgress::Pricing(n, t, p) => trf("progress_pricing", &[n, t, p]),
+      LoadProgress::Solving(p, r, n) => trf("progress_solving", &[p, r, n]),
+      LoadProgress::Reporting => tr("progress_reporting").to_owned(),
+    });
+  }
+}
+
+/// Where the loader starts from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LoadSource {
+  /// CSV text to parse: the text, how it's quoted, and whether it has a
+  /// header row.
+  Csv(String, QuoteChar, bool),
+  /// Sales parsed before, already sorted: the sales, how many CSV rows there
+  /// were, whether the header looked like a sale, which headers didn't
+  /// match, and how many repeated sales were dropped.
+  Parsed(Vec<Sale>, usize, bool, HeaderInfo, usize)
+}
+
+/// How the CSVs are read: commas, flexible line lengths, and the given
+/// quoting.
+fn builder(quote: QuoteChar, headers: bool) -> ReaderBuilder {
+  let mut rb = ReaderBuilder::new();
+  rb.delimiter(b',')
+    .flexible(true)
+    .has_headers(headers);
+  quote.apply(&mut rb);
+  return rb;
+}
+
+/// Checks out a CSV's header without reading the rest, to see which headers
+/// don't mean a sale field (yet). A header that parses as a sale is taken
+/// as no header at all.
+pub fn sniff_headers(
+  txt: &str,
+  quote: QuoteChar,
+  ctx: &SalesContext
+) -> HeaderInfo {
+  let mut rdr = builder(quote, true).from_reader(txt.as_bytes());
+  return match rdr.headers() {
+    Ok(h) if Sale::try_from((h.clone(), ctx)).is_err() => {
+      ColumnMap::from_headers(h, &ctx.columns).1
+    },
+    _ => HeaderInfo::default(),
+  };
+}
+
+/// What came out of a step.
+#[derive(Debug)]
+pub enum LoadStep {
+  /// Not done yet, call step again (after yielding).
+  Progress(LoadProgress),
+  /// All done. The pricing cache comes back for the next run. The sales and
+  /// the report are boxed, they dwarf the other variants.
+  Done(Box<SalesPlus>, Box<Report>, PricingCandidateCache),
+  /// Nothing to report on.
+  Failed(Vec<D4Error>)
+}
+
+/// Inner state of the loader.
+#[derive(Debug)]
+enum Stage {
+  Parsing {
+    rdr: Reader<Cursor<Vec<u8>>>,
+    row: usize,
+    sales: Vec<Sale>,
+    errors: Vec<D4Error>,
+    /// Seller names seen so far, shared between their sales.
+    names: Interner
+  },
+  Pricing {
+    pending: std::vec::IntoIter<Sale>,
+    total: usize,
+    sp: SalesPlus,
+    dude: PricingCandidateCache
+  },
+  Solving {
+    sp: SalesPlus,
+    passes: usize,
+    solves: usize,
+    dude: PricingCandidateCache
+  },
+  Reporting {
+    sp: SalesPlus,
+    dude: PricingCandidateCache
+  },
+  Finished
+}
+
+/// A resumable CSV-to-report pipeline. Each call to step does a bounded
+/// amount of work.
+#[derive(Debug)]
+pub struct Loader {
+  /// The context, handed on to the sales and the cache without copying.
+  ctx: Rc<SalesContext>,
+  /// Whether the header row would have made a fine sale, i.e. the CSV may
+  /// have no header at all and we just ate a sale.
+  header_is_sale: bool,
+  /// Where the sale fields are in each record.
+  cols: ColumnMap,
+  /// Which headers didn't match a field.
+  headers: HeaderInfo,
+  /// Lines that didn't make a sale, when enough others did.
+  skipped: Vec<D4Error>,
+  /// Sales dropped for repeating an earlier one's key.
+  duplicates: usize,
+  /// A pricing cache from an earlier run, if there's one.
+  cache: Option<PricingCandidateCache>,
+  /// How long pricing and solving may take, in all.
+  budget: Budget,
+  stage: Stage
+}
+
+impl Loader {
+  /// Sets things up. Nothing is parsed yet, except for the header. The cache
+  /// is only used if it prices things the same as the context.
+  pub fn new(
+    ctx: SalesContext,
+    src: LoadSource,
+    cache: Option<PricingCandidateCache>
+  ) -> Self {
+    let mut ld = Self {
+      ctx: Rc::new(ctx),
+      header_is_sale: false,
+      cols: ColumnMap::default(),
+      headers: HeaderInfo::default(),
+      skipped: Vec::new(),
+      duplicates: 0,
+      cache,
+      budget: Budget::unlimited(),
+      stage: Stage::Finished
+    };
+    ld.stage = match src {
+      LoadSource::Csv(txt, quote, headers) => ld.reader(txt, quote, headers),
+      LoadSource::Parsed(sales, rows, header_is_sale, headers, dups) => {
+        ld.header_is_sale = header_is_sale;
+        ld.headers = headers;
+        ld.duplicates = dups;
+        ld.pricing(sales, rows)
+      },
+    };
+    return ld;
+  }
+
+  /// Gives up on pricing and solving once the budget runs out, reporting on
+  /// whatever was done by then. There's no limit by default.
+  pub fn with_budget(mut self, budget: Budget) -> Self {
+    self.budget = budget;
+    return self;
+  }
+
+  /// Sets up the CSV reader, and checks out the header.
+  fn reader(&mut self, txt: String, quote: QuoteChar, headers: bool) -> Stage {
+    let mut rdr = builder(quote, headers)
+      .from_reader(Cursor::new(txt.into_bytes()));
+    self.header_is_sale = headers && match rdr.headers() {
+      Ok(h) => Sale::try_from((h.clone(), self.ctx.as_ref())).is_ok(),
+      Err(_) => false,
+    };
+    if self.header_is_sale {
+      log::warn!("CSV header parses as a sale, is it missing?");
+    } else if let (true, Ok(h)) = (headers, rdr.headers()) {
+      (self.cols, self.headers) = ColumnMap::from_headers(h, &self.ctx.columns);
+      if !self.headers.missing.is_empty() {
+        log::warn!(
+          "unrecognized CSV headers {:?}, taking columns by position",
+          self.headers.unknown
+        );
+      }
+    }
+    return Stage::Parsing {
+      rdr,
+      row: 0,
+      sales: Vec::new(),
+      errors: Vec::new(),
+      names: Interner::default()
+    };
+  }
+
+  /// Gets ready to price sorted sales, reusing the cache if it fits.
+  fn pricing(&mut self, sales: Vec<Sale>, rows: usize) -> Stage {
+    let total = sales.len();
+    let mut sp = SalesPlus::new(Rc::clone(&self.ctx));
+    sp.rows = rows;
+    sp.header_is_sale = self.header_is_sale;
+    sp.headers = self.headers.clone();
+    sp.duplicates = self.duplicates;
+    let dude = match self.cache.take() {
+      Some(c) if c.fits(&self.ctx) => c,
+      _ => PricingCandidateCache::from(Rc::clone(&self.ctx)),
+    };
+    return Stage::Pricing { pending: sales.into_iter(), total, sp, dude };
+  }
+
+  /// The lines that were skipped for not making sales. Only final once the
+  /// parsing is done.
+  pub fn skipped(&self) -> &[D4Error] {
+    return &self.skipped;
+  }
+
+  /// Does one chunk of work.
+  pub fn step(&mut self) -> LoadStep {
+    let stage = std::mem::replace(&mut self.stage, Stage::Finished);
+    let (next, res) = match stage {
+      Stage::Parsing {
+        mut rdr, mut row, mut sales, mut errors, mut names
+      } => {
+        let mut rec = StringRecord::new();
+        let mut eof = false;
+        for _ in 0..CHUNK_ROWS {
+          match rdr.read_record(&mut rec) {
+            Ok(true) => {
+              let cols = &self.cols;
+              match Sale::from_record(&rec, &self.ctx, cols, &mut names) {
+                Ok(s) => sales.push(Sale { row, ..s }),
+                Err(e) => errors.push(e),
+              }
+            },
+            Ok(false) => {
+              eof = true;
+              break;
+            },
+            Err(e) => errors.push(e.into()),
+          }
+          row += 1;
+        }
+        if !eof {
+          let p = LoadProgress::Parsing(row);
+          let next = Stage::Parsing { rdr, row, sales, errors, names };
+          (next, LoadStep::Progress(p))
+        } else if sales.is_empty() {
+          errors.insert(0, D4Error::NoSales);
+          (Stage::Finished, LoadStep::Failed(errors))
+        } else {
+          // first ones in the file stay, so before sorting
+          let (mut sales, dups) = dedup(sales, self.ctx.dedup_key);
+          if dups > 0 {
+            log::info!("dropped {} repeated sales", dups);
+          }
+          self.duplicates = dups;
+          sales.sort_by(Sale::cmp_dates);
+          self.skipped = errors;
+          let p = LoadProgress::Pricing(0, sales.len(), 0);
+          (self.pricing(sales, row), LoadStep::Progress(p))
+        }
+      },
+      Stage::Pricing { total: 0, .. } => {
+        // only when handed already parsed sales, the CSV path checks earlier
+        (Stage::Finished, LoadStep::Failed(vec![D4Error::NoSales]))
+      },
+      Stage::Pricing { mut pending, total, mut sp, mut dude } => {
+        for sale in pending.by_ref().take(CHUNK_ROWS) {
+          if !sp.push_sale_within(sale, &mut dude, &self.budget) {
+            log::warn!(
+              "out of time after pricing {} of {} sales",
+              sp.sales.len(),
+              total
+            );
+            sp.interrupted = true;
+            break;
+          }
+        }
+        if sp.sales.len() < total && !sp.interrupted {
+          let p = LoadProgress::Pricing(sp.sales.len(), total, dude.len());
+          (Stage::Pricing { pending, total, sp, dude }, LoadStep::Progress(p))
+        } else {
+          log::info!("pricing cache: {}", dude.stats());
+          let p = LoadProgress::Solving(1, 0, 0);
+          let next = Stage::Solving { sp, passes: 0, solves: 0, dude };
+          (next, LoadStep::Progress(p))
+        }
+      },
+      Stage::Solving { mut sp, passes, dude, .. } if self.budget.exceeded() => {
+        log::warn!("out of time after {} solver passes", passes);
+        sp.interrupted = true;
+        let p = LoadProgress::Reporting;
+        (Stage::Reporting { sp, dude }, LoadStep::Progress(p))
+      },
+      Stage::Solving { mut sp, passes, solves, dude } => {
+        let thispass = sp.run_solver();
+        let (passes, solves) = (passes + 1, solves + thispass);
+        let narrowed = sp.partially_resolved().count();
+        if thispass > 0 {
+          let p = LoadProgress::Solving(passes + 1, solves, narrowed);
+          let next = Stage::Solving { sp, passes, solves, dude };
+          (next, LoadStep::Progress(p))
+        } else {
+          log::info!(
+            "solver \"{}\" removed {} ambiguities in {} passes, narrowed {}",
+            sp.context.solver.name(),
+            solves,
+            passes,
+            narrowed
+          );
+          let p = LoadProgress::Reporting;
+          (Stage::Reporting { sp, dude }, LoadStep::Progress(p))
+        }
+      },
+      Stage::Reporting { sp, dude } => {
+        let report = ReportTemplate::default().compute(&sp);
+        (Stage::Finished, LoadStep::Done(
+          Box::new(sp), Box::new(report), dude
+        ))
+      },
+      Stage::Finished => {
+        (Stage::Finished, LoadStep::Failed(vec![D4Error::NothingToDo]))
+      },
+    };
+    self.stage = next;
+    return res;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::report::StringField;
+  use crate::sale::error::{ParseErrorKind, group_errors};
+
+  /// Five sales: a promo and a 1st batch ticket online, a promo + 1st batch
+  /// combo online, and two 2nd batch tickets from a point of sale.
+  static CSV: &str = concat!(
+    "Data Compra,Email Usuario,Nome Usuario,Valor,Status,Vendedor,",
+    "ID Vendedor,Email Vendedor,Token,ID,Nome Cartao,Prim Digitos Cartao,",
+    "Ult Digitos Cartao\n",
+    "2022-10-01T12:00:00Z,a@d4.com,a,60.5,Paga Online,N/A,N/A,N/A,",
+    "tk1,id1,N/A,N/A,N/A\n",
+    "2022-10-01T12:05:00Z,b@d4.com,b,71.5,Paga Online,N/A,N/A,N/A,",
+    "tk2,id2,N/A,N/A,N/A\n",
+    "2022-10-01T12:10:00Z,c@d4.com,c,132,Paga Online,N/A,N/A,N/A,",
+    "tk3,id3,N/A,N/A,N/A\n",
+    "2022-10-01T12:15:00Z,d@d4.com,d,75,Paga Offline,Fulano,v1,f@d4.com,",
+    "tk4,id4,N/A,N/A,N/A\n",
+    "2022-10-01T12:20:00Z,e@d4.com,e,75,Paga Offline,Fulano,v1,f@d4.com,",
+    "tk5,id5,N/A,N/A,N/A\n"
+  );
+
+  /// Steps a loader over some CSV, with the default context, until it's
+  /// done one way or the other.
+  fn load(csv: &str) -> LoadStep {
+    return load_quoted(csv, QuoteChar::default());
+  }
+
+  /// Same as load, with some other quote character.
+  fn load_quoted(csv: &str, quote: QuoteChar) -> LoadStep {
+    let src = LoadSource::Csv(csv.to_owned(), quote, true);
+    let mut ld = Loader::new(SalesContext::default(), src, None);
+    loop {
+      match ld.step() {
+        LoadStep::Progress(_) => continue,
+        done => return done,
+      }
+    }
+  }
+
+  #[test]
+  fn loads_a_csv_into_a_report() {
+    let (sp, report) = match load(CSV) {
+      LoadStep::Done(sp, report, _) => (sp, report),
+      other => panic!("expected a report, got {:?}", other),
+    };
+    assert_eq!(sp.sales.len(), 5);
+    assert_eq!(sp.oks().count(), 5);
+    assert!(sp.headers.missing.is_empty());
+    let total = StringField(tr("total_sales").to_owned(), "5".to_owned());
+    assert!(report.sfields.contains(&total));
+    let tokens: Vec<&str> = report.better_csv.iter()
+      .map(|r| r[8].as_str())
+      .collect();
+    assert_eq!(tokens, vec!["tk1", "tk2", "tk3", "tk4", "tk5"]);
+  }
+
+  #[test]
+  fn a_wrong_delimiter_fails_with_grouped_errors() {
+    let ve = match load(&CSV.replace(',', ";")) {
+      LoadStep::Failed(ve) => ve,
+      other => panic!("expected errors, got {:?}", other),
+    };
+    assert_eq!(ve[0], D4Error::NoSales);
+    let groups = group_errors(&ve);
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[1].kind, ParseErrorKind::Columns(1, 13));
+    assert_eq!(groups[1].count, 5);
+    assert_eq!(groups[1].lines, vec![2, 3, 4, 5, 6]);
+  }
+
+  #[test]
+  fn reads_single_quotes() {
+    let csv = CSV.replace("Fulano", "'Silva, Fulano'");
+    let sp = match load_quoted(&csv, QuoteChar::Single) {
+      LoadStep::Done(sp, _, _) => sp,
+      other => panic!("expected a report, got {:?}", other),
+    };
+    assert_eq!(sp.oks().count(), 5);
+    assert_eq!(
+      sp.sales[4].sale.seller_name.as_deref(),
+      Some("Silva, Fulano")
+    );
+    // with the usual quotes, the comma splits the seller's lines
+    assert!(matches!(
+      load(&csv),
+      LoadStep::Done(sp, _, _) if sp.sales.len() == 3
+    ));
+  }
+
+  #[test]
+  fn empty_input_has_no_sales() {
+    let header = CSV.lines().next().unwrap();
+    for csv in ["", "\n", header] {
+      assert!(matches!(
+        load(csv),
+        LoadStep::Failed(ve) if ve == vec![D4Error::NoSales]
+      ), "{:?}", csv);
+    }
+  }
+
+  #[test]
+  fn nothing_after_done() {
+    let src = LoadSource::Csv(CSV.to_owned(), QuoteChar::default(), true);
+    let mut ld = Loader::new(SalesContext::default(), src, None);
+    while let LoadStep::Progress(_) = ld.step() {}
+    assert!(matches!(
+      ld.step(),
+      LoadStep::Failed(ve) if ve == vec![D4Error::NothingToDo]
+    ));
+  }
+}
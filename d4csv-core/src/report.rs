@@ -0,0 +1,268 @@
+//! Useful information to report after looking at the sales list.
+
+pub mod anon;
+pub mod sfields;
+pub mod tfields;
+pub mod mfields;
+pub mod rfields;
+pub mod stamp;
+pub mod table;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::error::D4Error;
+use crate::report::sfields::SFIELDS;
+use crate::report::tfields::TFIELDS;
+use crate::report::mfields::MFIELDS;
+use crate::report::rfields::RFIELDS;
+use crate::report::table::RowStatus;
+use crate::sale::plus::{SalePlus, SalesPlus};
+
+/// A report field made out to be a single string.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StringField(pub String, pub String);
+
+/// A function that computes a string field from sales data.
+pub type FieldFn = fn(&SalesPlus) -> StringField;
+
+/// A report field that's a string-string table, rows in display order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableField(pub String, pub Vec<(String, String)>);
+
+/// A function that computes a table field from sales data.
+pub type TableFn = fn(&SalesPlus) -> TableField;
+
+/// A report field that's a proper table: name, column headers, and rows made
+/// of a row header plus one cell per column.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatrixField(
+  pub String,
+  pub Vec<String>,
+  pub Vec<(String, Vec<String>)>
+);
+
+/// A function that computes a matrix field from sales data.
+pub type MatrixFn = fn(&SalesPlus) -> MatrixField;
+
+/// A report field that breaks sales down by how they got resolved: name, and
+/// rows made of a row header plus a count per Resolution, in its order.
+/// Counts may be fractional, since unresolved sales can be spread out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResolutionField(pub String, pub Vec<(String, [f64; 3])>);
+
+/// A function that computes a resolution field from sales data.
+pub type ResolutionFn = fn(&SalesPlus) -> ResolutionField;
+
+/// How many reports were computed so far. Only for logging.
+static REPORTS_COMPUTED: AtomicUsize = AtomicUsize::new(0);
+
+/// A report skeleton, made out of field functions.
+pub struct ReportTemplate {
+  /// All string field functions.
+  sfields: Vec<FieldFn>,
+  /// All table field functions.
+  tfields: Vec<TableFn>,
+  /// All matrix field functions.
+  mfields: Vec<MatrixFn>,
+  /// All resolution field functions.
+  rfields: Vec<ResolutionFn>
+}
+
+impl Default for ReportTemplate {
+  fn default() -> Self {
+    return Self {
+      sfields: SFIELDS.to_vec(),
+      tfields: TFIELDS.to_vec(),
+      mfields: MFIELDS.to_vec(),
+      rfields: RFIELDS.to_vec()
+    };
+  }
+}
+
+impl ReportTemplate {
+  /// Computes the report from sales data.
+  pub fn compute(&self, data: &SalesPlus) -> Report {
+    let n = REPORTS_COMPUTED.fetch_add(1, Ordering::Relaxed) + 1;
+    log::info!("computing report (#{} so far)", n);
+    return Report {
+      filter: data.filter.as_ref().map(|f| f.to_string()),
+      stamp: stamp::stamp(data),
+      sfields: self.sfields.iter().map(|f| f(data)).collect(),
+      tfields: self.tfields.iter().map(|f| f(data)).collect(),
+      mfields: self.mfields.iter().map(|f| f(data)).collect(),
+      rfields: self.rfields.iter().map(|f| f(data)).collect(),
+      better_csv: data.gen_csv(),
+      better_status: data.gen_status(),
+      better_keys: data.gen_search_keys(),
+      better_traces: data.gen_traces(),
+      no_contact: data.gen_no_contact(),
+      narrowed: data.gen_narrowed(),
+      free: data.gen_free(),
+      seller_csv: data.gen_seller_csv()
+    }
+  }
+}
+
+/// A corresponding report, calculated from the skeleton and sales data.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+  /// The filter the sales went through, if any. These aren't the totals!
+  pub filter: Option<String>,
+  /// Context and generation info.
+  pub stamp: Vec<StringField>,
+  /// All string fields.
+  pub sfields: Vec<StringField>,
+  /// All table fields.
+  pub tfields: Vec<TableField>,
+  /// All matrix fields.
+  pub mfields: Vec<MatrixField>,
+  /// All resolution fields.
+  #[serde(default)]
+  pub rfields: Vec<ResolutionField>,
+  /// The "better" CSV.
+  pub better_csv: Vec<Vec<String>>,
+  /// How each line in the better CSV ended up.
+  pub better_status: Vec<RowStatus>,
+  /// What each line in the better CSV can be searched by.
+  pub better_keys: Vec<String>,
+  /// Why each line in the better CSV got resolved, if a solver did it.
+  pub better_traces: Vec<Option<String>>,
+  /// Which lines in the better CSV have no buyer e-mail nor username.
+  #[serde(default)]
+  pub no_contact: Vec<usize>,
+  /// Which lines in the better CSV a solver narrowed down without resolving.
+  #[serde(default)]
+  pub narrowed: Vec<usize>,
+  /// Which lines in the better CSV are worth nothing.
+  #[serde(default)]
+  pub free: Vec<usize>,
+  /// The per-seller summary CSV.
+  pub seller_csv: Vec<Vec<String>>
+}
+
+impl Report {
+  /// Writes a header, some rows and the report stamp as CSV text. For Excel,
+  /// lines end in CRLF and there's a BOM up front, or accents come out wrong.
+  /// Any failure fails the whole thing, so there's no half-written file.
+  fn csv_txt(
+    &self,
+    header: Vec<String>,
+    rows: &[Vec<String>],
+    excel: bool
+  ) -> Result<String, D4Error> {
+    let start = match excel {
+      true => "\u{feff}".as_bytes().to_vec(),
+      false => vec![]
+    };
+    let bytes = self.write_csv(start, header, rows, excel)?;
+    return String::from_utf8(bytes)
+      .map_err(|e| D4Error::Export(e.to_string()));
+  }
+
+  /// Same as csv_txt, into any sink, which is handed back when done.
+  fn write_csv<W: std::io::Write>(
+    &self,
+    sink: W,
+    header: Vec<String>,
+    rows: &[Vec<String>],
+    excel: bool
+  ) -> Result<W, D4Error> {
+    let export = |e: &dyn std::fmt::Display| D4Error::Export(e.to_string());
+    let terminator = match excel {
+      true => csv::Terminator::CRLF,
+      false => csv::Terminator::Any(b'\n')
+    };
+    let mut wr = csv::WriterBuilder::new()
+      .double_quote(true)
+      .flexible(true)
+      .delimiter(b',')
+      .has_headers(true)
+      .terminator(terminator)
+      .from_writer(sink);
+    wr.write_record(header).map_err(|e| export(&e))?;
+    for l in rows {
+      wr.write_record(l).map_err(|e| export(&e))?;
+    }
+    wr.write_record(&[stamp::csv_footer(&self.stamp)])
+      .map_err(|e| export(&e))?;
+    return wr.into_inner().map_err(|e| export(&e));
+  }
+
+  /// The better CSV as text, ready for downloading or saving.
+  pub fn better_csv_txt(&self, excel: bool) -> Result<String, D4Error> {
+    return self.csv_txt(
+      SalePlus::better_csv_header(),
+      &self.better_csv,
+      excel
+    );
+  }
+
+  /// The better CSV as text, with the buyers' personal data left out (see
+  /// anon::anonymize). Pick a new random salt for every export.
+  pub fn anon_csv_txt(
+    &self,
+    excel: bool,
+    salt: u64
+  ) -> Result<String, D4Error> {
+    return self.csv_txt(
+      SalePlus::better_csv_header(),
+      &anon::anonymize(&self.better_csv, salt),
+      excel
+    );
+  }
+
+  /// Just the better CSV lines with no way to reach the buyer, as text.
+  pub fn no_contact_csv_txt(&self, excel: bool) -> Result<String, D4Error> {
+    let rows: Vec<Vec<String>> = self.no_contact.iter()
+      .filter_map(|i| self.better_csv.get(*i))
+      .cloned()
+      .collect();
+    return self.csv_txt(SalePlus::better_csv_header(), &rows, excel);
+  }
+
+  /// The per-seller CSV as text, ready for downloading or saving.
+  pub fn seller_csv_txt(&self, excel: bool) -> Result<String, D4Error> {
+    return self.csv_txt(
+      SalesPlus::seller_csv_header(),
+      &self.seller_csv,
+      excel
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io;
+  use crate::context::SalesContext;
+  use crate::testing::{priced, sales_plus};
+
+  /// A sink that takes nothing, like a full disk.
+  struct Full;
+
+  impl io::Write for Full {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+      return Err(io::Error::other("disco cheio"));
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      return Ok(());
+    }
+  }
+
+  #[test]
+  fn csv_failures_come_out() {
+    let ctx = SalesContext::default();
+    let sales = vec![priced(0, 6500, None, &ctx)];
+    let report = ReportTemplate::default().compute(&sales_plus(ctx, sales));
+    let header = SalePlus::better_csv_header();
+    let txt = report.better_csv_txt(true).unwrap();
+    assert!(txt.starts_with('\u{feff}') && txt.contains("\r\n"));
+    let res = report.write_csv(Full, header, &report.better_csv, false);
+    match res {
+      Err(D4Error::Export(e)) => assert!(e.contains("disco cheio"), "{}", e),
+      Err(e) => panic!("expected an export error, got {}", e),
+      Ok(_) => panic!("expected an export error"),
+    }
+  }
+}
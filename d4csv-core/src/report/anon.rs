@@ -0,0 +1,144 @@
+//! Anonymized better CSVs, for handing to the venue or a sponsor without
+//! handing them buyers' personal data too.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::sale::plus::SalePlus;
+
+/// What happens to each better CSV column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Treatment {
+  /// Stays as it is.
+  Keep,
+  /// Becomes a short hash, so rows by the same buyer still match up.
+  Hash,
+  /// Goes away.
+  Blank
+}
+
+/// What happens to a column, by its translation key.
+fn treatment(key: &str) -> Treatment {
+  return match key {
+    "h_buyer_email" | "h_buyer_name" => Treatment::Hash,
+    "h_seller_email" | "h_card_name" | "h_card_pfx" | "h_card_sfx" => {
+      Treatment::Blank
+    },
+    _ => Treatment::Keep,
+  };
+}
+
+/// A short hash of a value, under some salt. Empty values stay empty, so
+/// missing ones don't all look like the same buyer.
+fn short_hash(value: &str, salt: u64) -> String {
+  if value.is_empty() {
+    return String::new();
+  }
+  let mut h = DefaultHasher::new();
+  salt.hash(&mut h);
+  value.hash(&mut h);
+  return format!("{:012x}", h.finish() >> 16);
+}
+
+/// The better CSV rows with buyer e-mails and usernames hashed, and card and
+/// seller e-mail columns blanked. Everything else, and the row count, stays
+/// the same. The same salt gives the same hashes, so use a fresh random one
+/// for every export and keep it nowhere, or the hashes can be matched
+/// against known e-mails.
+pub fn anonymize(rows: &[Vec<String>], salt: u64) -> Vec<Vec<String>> {
+  let treatments: Vec<Treatment> = SalePlus::BETTER_CSV_HEADER.iter()
+    .map(|k| treatment(k))
+    .collect();
+  return rows.iter()
+    .map(|row| {
+      row.iter()
+        .enumerate()
+        .map(|(i, v)| match treatments.get(i) {
+          Some(Treatment::Hash) => short_hash(v, salt),
+          Some(Treatment::Blank) => String::new(),
+          _ => v.clone(),
+        })
+        .collect()
+    })
+    .collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::rc::Rc;
+  use crate::context::SalesContext;
+  use crate::sale::Sale;
+  use crate::testing::{priced, sales_plus};
+
+  /// Where a column is in the better CSV.
+  fn col(key: &str) -> usize {
+    return SalePlus::BETTER_CSV_HEADER.iter()
+      .position(|k| *k == key)
+      .unwrap();
+  }
+
+  /// Two sales by the same buyer, one with a card and one through a seller
+  /// with an e-mail, plus one by someone else.
+  fn rows() -> Vec<Vec<String>> {
+    let ctx = SalesContext::default();
+    let sales = vec![
+      Sale {
+        buyer_email: Some("fulano@d4.com".to_owned()),
+        buyer_username: Some("fulano".to_owned()),
+        card_name: Some("FULANO DE TAL".to_owned()),
+        card_pfx: Some("412345".to_owned()),
+        card_sfx: Some("6789".to_owned()),
+        ..priced(0, 6500, None, &ctx)
+      },
+      Sale {
+        buyer_email: Some("fulano@d4.com".to_owned()),
+        seller_email: Some(Rc::from("beltrano@d4.com")),
+        ..priced(1, 7500, Some("beltrano"), &ctx)
+      },
+      priced(2, 6500, None, &ctx),
+    ];
+    return sales_plus(ctx, sales).gen_csv();
+  }
+
+  #[test]
+  fn personal_data_is_gone() {
+    let rows = rows();
+    let anon = anonymize(&rows, 42);
+    assert_eq!(anon.len(), rows.len());
+    let pii = [
+      "fulano@d4.com", "fulano", "FULANO DE TAL", "412345", "6789",
+      "beltrano@d4.com"
+    ];
+    for (row, before) in anon.iter().zip(&rows) {
+      assert_eq!(row.len(), before.len());
+      for v in row {
+        assert!(!pii.contains(&v.as_str()), "{} left in {:?}", v, row);
+      }
+    }
+    for key in ["h_seller_email", "h_card_name", "h_card_pfx", "h_card_sfx"] {
+      assert!(anon.iter().all(|r| r[col(key)].is_empty()), "{}", key);
+    }
+    // tickets, batches and values are what the export is for
+    for (i, key) in SalePlus::BETTER_CSV_HEADER.iter().enumerate() {
+      if treatment(key) == Treatment::Keep {
+        assert!(anon.iter().zip(&rows).all(|(a, b)| a[i] == b[i]), "{}", key);
+      }
+    }
+  }
+
+  #[test]
+  fn hashes_go_by_the_salt() {
+    let rows = rows();
+    let email = col("h_buyer_email");
+    let once = anonymize(&rows, 42);
+    assert_eq!(once, anonymize(&rows, 42));
+    assert_eq!(once[0][email], once[1][email]);
+    assert_ne!(once[0][email], once[2][email]);
+    assert!(!once[0][email].is_empty());
+    let other = anonymize(&rows, 43);
+    assert_ne!(once[0][email], other[0][email]);
+    assert_eq!(other[0][email], other[1][email]);
+    // no username, no hash
+    assert_eq!(once[1][col("h_buyer_name")], "");
+  }
+}
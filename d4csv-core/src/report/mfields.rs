@@ -0,0 +1,138 @@
+//! Matrix fields for the report: tables with rows and columns.
+
+use std::collections::HashMap;
+use itertools::Itertools;
+use crate::format::{int, money};
+use crate::i18n::{tr, trf};
+use crate::report::{MatrixField, MatrixFn};
+use crate::sale::kind::SaleKind;
+use crate::sale::plus::SalesPlus;
+use crate::sale::price_deriving;
+use crate::ticket::batch::{Batch, bp2iter};
+use crate::ticket::batchnum::BatchNum;
+
+/// All the functions below.
+pub static MFIELDS: &[MatrixFn] = &[
+  tickets_per_batch_and_kind,
+  batch_capacities,
+  collision_prices
+];
+
+/// Tickets (online, offline) per batch, from resolved sales only.
+pub fn tickets_per_batch(
+  sp: &SalesPlus
+) -> HashMap<BatchNum, (usize, usize)> {
+  let mut hm: HashMap<BatchNum, (usize, usize)> = HashMap::new();
+  sp.oks()
+    .for_each(|s| {
+      for ba in s.pricematch.as_ref().unwrap().amounts() {
+        let cell = hm.entry(ba.0.num).or_insert((0, 0));
+        match s.sale.sale_kind {
+          SaleKind::Online(_) => cell.0 += ba.1,
+          SaleKind::Offline(_) => cell.1 += ba.1,
+        }
+      }
+    });
+  return hm;
+}
+
+/// Tickets per batch (rows) and sale kind (columns).
+pub fn tickets_per_batch_and_kind(sp: &SalesPlus) -> MatrixField {
+  let hm = tickets_per_batch(sp);
+  let batches: Vec<Batch> = bp2iter(&sp.context.batches).collect();
+  let mut totals: (usize, usize) = (0, 0);
+  let mut rows: Vec<(String, Vec<String>)> = batches.into_iter()
+    .map(|b| {
+      let (on, off) = hm.get(&b.num).copied().unwrap_or((0, 0));
+      totals.0 += on;
+      totals.1 += off;
+      return (
+        b.to_string(),
+        vec![int(on), int(off), int(on + off)]
+      );
+    }).collect();
+  rows.push((
+    tr("total").to_owned(),
+    vec![
+      int(totals.0),
+      int(totals.1),
+      int(totals.0 + totals.1)
+    ]
+  ));
+  return MatrixField(
+    tr("tickets_per_batch_kind").to_owned(),
+    vec![
+      tr("online").to_owned(),
+      tr("offline").to_owned(),
+      tr("total").to_owned()
+    ],
+    rows
+  );
+}
+
+/// Tickets sold versus capacity, for the batches with a known capacity.
+/// Batches that sold more than they had are flagged.
+pub fn batch_capacities(sp: &SalesPlus) -> MatrixField {
+  let hm = tickets_per_batch(sp);
+  let mut nums: Vec<BatchNum> = sp.context.capacities.keys().copied()
+    .collect();
+  nums.sort();
+  let rows: Vec<(String, Vec<String>)> = nums.into_iter()
+    .map(|num| {
+      let cap = sp.context.capacities[&num];
+      let (on, off) = hm.get(&num).copied().unwrap_or((0, 0));
+      let sold = on + off;
+      let name = match sp.context.batches.get(&num) {
+        Some(price) => Batch { num, price: *price }.to_string(),
+        None => num.to_string(),
+      };
+      return (
+        name,
+        vec![
+          int(sold),
+          int(cap),
+          match sold > cap {
+            true => trf("overflowed", &[&int(sold - cap)]),
+            false => trf("left_over", &[&int(cap - sold)])
+          }
+        ]
+      );
+    }).collect();
+  return MatrixField(
+    tr("batch_capacities").to_owned(),
+    vec![
+      tr("sold").to_owned(),
+      tr("capacity").to_owned(),
+      tr("situation").to_owned()
+    ],
+    rows
+  );
+}
+
+/// Prices the context alone makes ambiguous (see
+/// price_deriving::collision_prices), each with its possible matches and how
+/// many sales came in at it.
+pub fn collision_prices(sp: &SalesPlus) -> MatrixField {
+  let cs = price_deriving::collision_prices(&sp.context);
+  let mut hits: HashMap<usize, usize> = HashMap::new();
+  for s in &sp.sales {
+    let price = s.sale.real_price();
+    if cs.contains_key(&price) {
+      *hits.entry(price).or_insert(0) += 1;
+    }
+  }
+  let rows: Vec<(String, Vec<String>)> = cs.iter()
+    .map(|(price, pms)| (
+      money(*price),
+      vec![
+        pms.iter().map(|pm| pm.to_string()).join(tr("or")),
+        int(hits.get(price).copied().unwrap_or(0))
+      ]
+    ))
+    .collect();
+  return MatrixField(
+    tr("collision_prices").to_owned(),
+    vec![tr("compositions").to_owned(), tr("h_sales").to_owned()],
+    rows
+  );
+}
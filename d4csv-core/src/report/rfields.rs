@@ -0,0 +1,59 @@
+//! Resolution fields for the report: how sales got resolved, broken down.
+
+use std::collections::{HashMap, HashSet};
+use crate::i18n::tr;
+use crate::report::{ResolutionField, ResolutionFn};
+use crate::report::table::Resolution;
+use crate::sale::plus::SalesPlus;
+use crate::sale::price_deriving::PricingCandidate;
+use crate::ticket::batch::bp2iter;
+use crate::ticket::batchnum::BatchNum;
+
+/// All the functions below.
+pub static RFIELDS: &[ResolutionFn] = &[
+  resolution_per_batch
+];
+
+/// Sales with tickets from each batch, by how they got resolved, to see
+/// which batch boundaries give the solvers trouble. A sale counts once for
+/// every batch it has tickets from. Unresolved sales are spread evenly over
+/// their candidates, so a sale that may be one of two matches counts half
+/// for each.
+pub fn resolution_per_batch(sp: &SalesPlus) -> ResolutionField {
+  let mut hm: HashMap<BatchNum, [f64; 3]> = HashMap::new();
+  for s in &sp.sales {
+    match (&s.pricematch, &s.pricecand) {
+      (Some(pm), _) => {
+        let r = match s.resolution_trace {
+          Some(_) => Resolution::Solved,
+          None => Resolution::Precise,
+        };
+        let nums: HashSet<BatchNum> = pm.amounts().iter()
+          .map(|ba| ba.0.num)
+          .collect();
+        for num in nums {
+          hm.entry(num).or_insert([0.0; 3])[r as usize] += 1.0;
+        }
+      },
+      (None, PricingCandidate::Ambiguous(hs)) => {
+        let share = 1.0 / (hs.len() as f64);
+        for pm in hs {
+          let nums: HashSet<BatchNum> = pm.amounts().iter()
+            .map(|ba| ba.0.num)
+            .collect();
+          for num in nums {
+            let cell = hm.entry(num).or_insert([0.0; 3]);
+            cell[Resolution::Ambiguous as usize] += share;
+          }
+        }
+      },
+      _ => {}
+    }
+  }
+  return ResolutionField(
+    tr("resolution_per_batch").to_owned(),
+    bp2iter(&sp.context.batches)
+      .map(|b| (b.to_string(), hm.get(&b.num).copied().unwrap_or([0.0; 3])))
+      .collect()
+  );
+}
@@ -0,0 +1,492 @@
+//! Basic string fields for the report.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use chrono::Timelike;
+use itertools::Itertools;
+use crate::format::{int, money};
+use crate::i18n::{tr, trf};
+use crate::report::{StringField, FieldFn};
+use crate::sale::kind::SaleKind;
+use crate::sale::plus::SalesPlus;
+use crate::ticket::batchnum::BatchNum;
+
+/// Quick sugar for making string fields.
+fn sf<T>(name: &str, value: T) -> StringField where T: Display {
+  return StringField(name.to_owned(), format!("{}", value));
+}
+
+/// A rounded percentage, or zero when there's nothing to take it of. Filters
+/// can leave a report with no sales at all.
+fn perc(part: usize, whole: usize) -> usize {
+  if whole == 0 { return 0; }
+  return ((part as f64) / (whole as f64) * 100.0).round() as usize;
+}
+
+/// All the functions below.
+pub static SFIELDS: &[FieldFn] = &[
+  total_sales,
+  duplicates,
+  total_ok,
+  ambiguous_sales,
+  evil_sales,
+  free_sales,
+  total_tickets,
+  online_tickets,
+  offline_tickets,
+  promo_tickets,
+  total_revenue,
+  average_ticket_value,
+  timestamp_quality,
+  clock_skew,
+  csv_header,
+  token_collisions,
+  fee_rounding,
+  fees_paid,
+  no_contact,
+  narrowed
+];
+
+/// Total sales in list.
+fn total_sales(sp: &SalesPlus) -> StringField {
+  return sf(tr("total_sales"), int(sp.sales.len()));
+}
+
+/// Sales dropped for repeating an earlier one, and by what.
+fn duplicates(sp: &SalesPlus) -> StringField {
+  return sf(
+    tr("duplicates"),
+    format!("{} ({})", int(sp.duplicates), sp.context.dedup_key)
+  );
+}
+
+/// Total sales for which we found a pricing match.
+fn total_ok(sp: &SalesPlus) -> StringField {
+  let nok = sp.oks().count();
+  return sf(
+    tr("decoded_sales"),
+    format!("{} ({}%)", int(nok), perc(nok, sp.sales.len()))
+  );
+}
+
+/// Total tickets sold. Zero-value sales count as the context says.
+fn total_tickets(sp: &SalesPlus) -> StringField {
+  return sf(
+    tr("total_tickets"),
+    int(
+      sp.sales.iter()
+        .filter_map(|s| s.tickets(&sp.context))
+        .sum::<usize>()
+    )
+  );
+}
+
+/// Total tickets sold online.
+fn online_tickets(sp: &SalesPlus) -> StringField {
+  return sf(
+    tr("online_tickets"),
+    int(
+      sp.sales.iter()
+        .filter_map(|s| {
+          if let SaleKind::Online((_, _)) = &s.sale.sale_kind {
+            return s.tickets(&sp.context);
+          }
+          return None;
+        }).sum::<usize>()
+    )
+  );
+}
+
+/// Total tickets sold offline.
+fn offline_tickets(sp: &SalesPlus) -> StringField {
+  return sf(
+    tr("offline_tickets"),
+    int(
+      sp.sales.iter()
+        .filter_map(|s| {
+          if let SaleKind::Offline(_) = &s.sale.sale_kind {
+            return s.tickets(&sp.context);
+          }
+          return None;
+        }).sum::<usize>()
+    )
+  );
+}
+
+/// Total promo tickets, and how much of all tickets they are. For checking
+/// the promo cap was respected.
+fn promo_tickets(sp: &SalesPlus) -> StringField {
+  let mut promos: usize = 0;
+  let mut total: usize = 0;
+  for s in sp.oks() {
+    for ba in s.pricematch.as_ref().unwrap().amounts() {
+      if ba.0.num == BatchNum::Promo {
+        promos += ba.1;
+      }
+      total += ba.1;
+    }
+  }
+  return sf(
+    tr("promo_tickets"),
+    format!("{} ({}%)", int(promos), perc(promos, total))
+  );
+}
+
+/// Money from all sales, resolved or not. Gross includes the online fees,
+/// net leaves them out.
+fn total_revenue(sp: &SalesPlus) -> StringField {
+  let cents: usize = sp.sales.iter().map(|s| sp.revenue.of(s)).sum();
+  return sf(
+    tr("total_revenue"),
+    format!("{} ({})", money(cents), sp.revenue)
+  );
+}
+
+/// Average money per ticket, over the resolved sales only. Same deal with
+/// the fees as the total.
+fn average_ticket_value(sp: &SalesPlus) -> StringField {
+  let (cents, tickets) = sp.oks()
+    .map(|s| (sp.revenue.of(s), s.pricematch.as_ref().unwrap().tickets()))
+    .fold((0, 0), |(c, t), (dc, dt)| (c + dc, t + dt));
+  let avg = cents.checked_div(tickets).unwrap_or(0);
+  return sf(
+    tr("average_ticket_value"),
+    format!("{} ({})", money(avg), sp.revenue)
+  );
+}
+
+/// Number of ambiguous sales.
+fn ambiguous_sales(sp: &SalesPlus) -> StringField {
+  return sf(
+    tr("ambiguous_sales"),
+    int(
+      sp.ambiguous()
+        .filter(|s| s.pricematch.is_none())
+        .count()
+    )
+  );
+}
+
+/// Number of unsolvable sales.
+fn evil_sales(sp: &SalesPlus) -> StringField {
+  return sf(tr("unsolvable_sales"), int(sp.villains().count()));
+}
+
+/// Number of zero-value sales: courtesies and tests, kept apart from the
+/// unsolvable ones.
+fn free_sales(sp: &SalesPlus) -> StringField {
+  return sf(tr("free_sales"), int(sp.frees().count()));
+}
+
+/// Timestamp sanity: how many sales share a timestamp with the previous one,
+/// and how many ended up before a sale that came earlier in the file. Lots of
+/// either means the dates are fishy, and the temporal solvers will suffer.
+fn timestamp_quality(sp: &SalesPlus) -> StringField {
+  let n = sp.sales.len();
+  let mut ties: usize = 0;
+  let mut moved: usize = 0;
+  for w in sp.sales.windows(2) {
+    if w[0].sale.when == w[1].sale.when {
+      ties += 1;
+    }
+    if w[1].sale.row < w[0].sale.row {
+      moved += 1;
+    }
+  }
+  let warn = if perc(ties, n) > 10 || perc(moved, n) > 10 {
+    tr("timestamp_warn")
+  } else {
+    ""
+  };
+  let detail = trf(
+    "timestamp_detail",
+    &[&int(ties), &perc(ties, n), &int(moved), &perc(moved, n)]
+  );
+  return sf(tr("timestamp_quality"), format!("{}{}", detail, warn));
+}
+
+/// How many sales each side needs before clock_skew dares to guess.
+static SKEW_MIN_SALES: usize = 20;
+/// How much better a shifted match has to be than no shift at all.
+static SKEW_MARGIN: f64 = 1.5;
+
+/// Compares the hours of the day online and offline sales happen at, to catch
+/// one side's clock being systematically off (UTC vs local, say). Finds the
+/// shift that best lines up both hourly histograms, and warns if it's clearly
+/// better than no shift at all.
+fn clock_skew(sp: &SalesPlus) -> StringField {
+  let mut online = [0f64; 24];
+  let mut offline = [0f64; 24];
+  for s in sp.sales.iter() {
+    let h = s.sale.when.hour() as usize;
+    match s.sale.sale_kind {
+      SaleKind::Online(_) => online[h] += 1.0,
+      SaleKind::Offline(_) => offline[h] += 1.0,
+    }
+  }
+  let (n_on, n_off): (f64, f64) = (online.iter().sum(), offline.iter().sum());
+  if n_on < SKEW_MIN_SALES as f64 || n_off < SKEW_MIN_SALES as f64 {
+    return sf(tr("clock_skew"), tr("clock_skew_few"));
+  }
+  // overlap between the (normalized) histograms, with offline shifted
+  let overlap = |shift: isize| -> f64 {
+    return (0..24).map(|h: isize| {
+      let o = offline[(h + shift).rem_euclid(24) as usize] / n_off;
+      online[h as usize] / n_on * o
+    }).sum();
+  };
+  let (best, score) = (-11..=12)
+    .map(|s| (s, overlap(s)))
+    .fold((0, overlap(0)), |acc, cur| if cur.1 > acc.1 { cur } else { acc });
+  if best != 0 && score > overlap(0) * SKEW_MARGIN {
+    let shift = format!("{:+}", best);
+    return sf(tr("clock_skew"), trf("clock_skew_warn", &[&shift]));
+  }
+  return sf(tr("clock_skew"), tr("clock_skew_none"));
+}
+
+/// Whether the header row looked like a sale. If it did, the CSV probably has
+/// no header, and the first sale was thrown away as one. Otherwise, whether
+/// its headers all meant something.
+fn csv_header(sp: &SalesPlus) -> StringField {
+  let unknown = sp.headers.unknown.join(", ");
+  return sf(tr("csv_header"), match sp.header_is_sale {
+    true => tr("csv_header_warn").to_owned(),
+    false if !sp.headers.missing.is_empty() => {
+      trf("csv_header_unmapped", &[&unknown])
+    },
+    false if !sp.headers.unknown.is_empty() => {
+      trf("csv_header_ignored", &[&unknown])
+    },
+    false => "ok".to_owned()
+  });
+}
+
+/// How many colliding tokens token_collisions lists before giving up.
+static COLLISION_EXAMPLES: usize = 5;
+
+/// Tokens shared by sales with different IDs. Each sale should have its own,
+/// so those may be double sales, or an export gone wrong. Blank tokens are
+/// common and mean nothing, so they're left out, and so are repeated rows of
+/// the same sale.
+fn token_collisions(sp: &SalesPlus) -> StringField {
+  let mut ids: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+  for s in sp.sales.iter() {
+    let token = s.sale.token.trim();
+    if token.is_empty() || token == "N/A" {
+      continue;
+    }
+    ids.entry(token).or_default().insert(s.sale.sale_id.as_str());
+  }
+  let bad: Vec<&str> = ids.iter()
+    .filter(|(_, v)| v.len() > 1)
+    .map(|(t, _)| *t)
+    .collect();
+  if bad.is_empty() {
+    return sf(tr("token_collisions"), tr("token_collisions_none"));
+  }
+  let mut shown = bad.iter().take(COLLISION_EXAMPLES).join(", ");
+  if bad.len() > COLLISION_EXAMPLES {
+    shown.push_str(", ...");
+  }
+  return sf(
+    tr("token_collisions"),
+    trf("token_collisions_warn", &[&int(bad.len()), &shown])
+  );
+}
+
+/// How far off resolved online sales are from their match with the fee on,
+/// all added up. Undoing the fee rounds down, so a sale a few cents off still
+/// resolves; lots of those (or a big total) suggest the fee is wrong.
+fn fee_rounding(sp: &SalesPlus) -> StringField {
+  let (mut total, mut off, mut n) = (0usize, 0usize, 0usize);
+  for s in sp.oks() {
+    if !matches!(s.sale.sale_kind, SaleKind::Online(_)) {
+      continue;
+    }
+    let diff = s.residual().unwrap().unsigned_abs();
+    n += 1;
+    total += diff;
+    if diff > 0 {
+      off += 1;
+    }
+  }
+  return sf(
+    tr("fee_rounding"),
+    trf("fee_rounding_detail", &[&money(total), &int(off), &int(n)])
+  );
+}
+
+/// The fees resolved sales paid, online and offline, all added up. The same
+/// amounts as the better CSV's fee column, so they sum to this.
+fn fees_paid(sp: &SalesPlus) -> StringField {
+  let (total, n) = sp.oks()
+    .filter(|s| s.sale.sale_kind.fee().is_some())
+    .filter_map(|s| s.fee_amount())
+    .fold((0, 0), |(t, n), f| (t + f, n + 1));
+  return sf(
+    tr("fees_paid"),
+    trf("fees_paid_detail", &[&money(total), &int(n)])
+  );
+}
+
+/// Sales with neither a buyer e-mail nor a username, so there's no one to
+/// follow up with.
+fn no_contact(sp: &SalesPlus) -> StringField {
+  let n = sp.sales.iter().filter(|s| !s.sale.has_contact()).count();
+  return sf(
+    tr("no_contact"),
+    format!("{} ({}%)", int(n), perc(n, sp.sales.len()))
+  );
+}
+
+/// Sales a solver narrowed down without resolving, and how many candidates
+/// they went from and to, all summed up.
+fn narrowed(sp: &SalesPlus) -> StringField {
+  let (n, before, after) = sp.partially_resolved()
+    .fold((0, 0, 0), |(n, b, a), s| {
+      (n + 1, b + s.initial_count, a + s.pricecand.count())
+    });
+  return sf(
+    tr("narrowed"),
+    trf("narrowed_detail", &[&int(n), &int(before), &int(after)])
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::context::SalesContext;
+  use crate::sale::Sale;
+  use crate::sale::ambiguity::AmbiguitySolver;
+  use crate::sale::plus::SalePlus;
+  use crate::testing::{priced, sale, sales_plus};
+  use crate::ticket::batch::iter2bp;
+
+  #[test]
+  fn fees_paid_adds_offline_fees() {
+    let ctx = SalesContext {
+      offline_fee: Some((1050, 1000)),
+      ..SalesContext::default()
+    };
+    let sales = vec![
+      priced(0, 6500, None, &ctx),
+      priced(1, 6500, Some("fulano"), &ctx)
+    ];
+    let sp = sales_plus(ctx, sales);
+    let detail = trf("fees_paid_detail", &[&money(975), &int(2)]);
+    assert_eq!(fees_paid(&sp).1, detail);
+  }
+
+  #[test]
+  fn fees_paid_is_the_fee_column_summed() {
+    let ctx = SalesContext {
+      offline_fee: Some((1050, 1000)),
+      ..SalesContext::default()
+    };
+    let no_fee = Sale {
+      sale_kind: SaleKind::Offline(None),
+      ..sale(3, 8500, Some("beltrano"), &ctx)
+    };
+    let sales = vec![
+      priced(0, 6500, None, &ctx),
+      priced(1, 7500, Some("fulano"), &ctx),
+      // 3x 1st batch, or a promo, a 1st and a 2nd
+      priced(2, 19500, None, &ctx),
+      no_fee,
+      priced(4, 15000, None, &ctx)
+    ];
+    let sp = sales_plus(ctx, sales);
+    assert_eq!(sp.oks().count(), 4);
+    let col = SalePlus::BETTER_CSV_HEADER.iter()
+      .position(|k| *k == "h_fee_amount")
+      .unwrap();
+    let cells: Vec<String> = sp.sales.iter()
+      .map(|s| s.gen_better_csv_line(&sp.context).swap_remove(col))
+      .filter(|c| !c.is_empty())
+      .collect();
+    assert_eq!(cells.len(), 4);
+    let total: usize = cells.iter()
+      .map(|c| c.replace('.', "").parse::<usize>().unwrap())
+      .sum();
+    assert_eq!(total, 650 + 375 + 1500);
+    let detail = trf("fees_paid_detail", &[&money(total), &int(3)]);
+    assert_eq!(fees_paid(&sp).1, detail);
+  }
+
+  #[test]
+  fn narrowed_but_not_resolved() {
+    // R$ 180 is 3x 1st batch, 2x 2nd or a 3rd
+    let ctx = SalesContext {
+      batches: iter2bp([6000, 9000, 18000], None),
+      has_promo: false,
+      solver: AmbiguitySolver::GlobalMonotonic,
+      ..SalesContext::default()
+    };
+    let sales = vec![
+      priced(0, 9000, None, &ctx),
+      priced(1, 18000, None, &ctx)
+    ];
+    let mut sp = sales_plus(ctx, sales);
+    assert_eq!(sp.partially_resolved().count(), 0);
+    assert_eq!(sp.sales[1].initial_count, 3);
+    // past the 1st batch, but the 2nd and 3rd are still up
+    let (_, solved) = sp.solve_ambiguities();
+    assert_eq!(solved, 0);
+    let partial: Vec<&SalePlus> = sp.partially_resolved().collect();
+    assert_eq!(partial.len(), 1);
+    assert_eq!(partial[0].sale.token, "t1");
+    assert_eq!(partial[0].pricecand.count(), 2);
+    assert_eq!(sp.gen_narrowed(), vec![1]);
+    assert_eq!(
+      narrowed(&sp).1,
+      trf("narrowed_detail", &[&int(1), &int(3), &int(2)])
+    );
+  }
+
+  #[test]
+  fn no_sales_no_nans() {
+    let sp = sales_plus(SalesContext::default(), vec![]);
+    let report = crate::report::ReportTemplate::default().compute(&sp);
+    for f in report.sfields.iter() {
+      assert!(!f.1.contains("NaN") && !f.1.contains("inf"), "{}", f.1);
+    }
+  }
+
+  #[test]
+  fn token_collisions_list_the_tokens() {
+    let ctx = SalesContext::default();
+    let with = |m: i64, token: &str, id: &str| Sale {
+      token: token.to_owned(),
+      sale_id: id.to_owned(),
+      ..sale(m, 7150, None, &ctx)
+    };
+    let clean = vec![
+      with(0, "t2", "c"),
+      with(1, "t2", "c"),
+      with(2, "", "d"),
+      with(3, "", "e"),
+      with(4, "N/A", "f"),
+      with(5, "N/A", "g")
+    ];
+    let sp = sales_plus(ctx.clone(), clean.clone());
+    assert_eq!(token_collisions(&sp).1, tr("token_collisions_none"));
+    let mut sales = clean;
+    sales.push(with(6, "t1", "a"));
+    sales.push(with(7, "t1", "b"));
+    let sp = sales_plus(ctx.clone(), sales.clone());
+    let one = trf("token_collisions_warn", &[&int(1), &"t1"]);
+    assert_eq!(token_collisions(&sp).1, one);
+    for (i, t) in ["t3", "t4", "t5", "t6", "t7", "t8"].iter().enumerate() {
+      let m = 8 + 2 * i as i64;
+      sales.push(with(m, t, "x"));
+      sales.push(with(m + 1, t, "y"));
+    }
+    let sp = sales_plus(ctx, sales);
+    let many = trf("token_collisions_warn", &[
+      &int(7), &"t1, t3, t4, t5, t6, ..."
+    ]);
+    assert_eq!(token_collisions(&sp).1, many);
+  }
+}
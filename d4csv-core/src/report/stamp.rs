@@ -0,0 +1,55 @@
+//! A "stamp" with everything that went into a report, so copies of it can be
+//! told apart later.
+
+use chrono::Utc;
+use crate::build_info;
+use crate::context::ContextInputData;
+use crate::format;
+use crate::i18n::{Lang, tr};
+use crate::report::StringField;
+use crate::sale::plus::SalesPlus;
+
+/// Computes the stamp fields: the filter (if any), generation time, rows
+/// read, gross or net, the build, the context, and whatever looked odd
+/// about it.
+pub fn stamp(sp: &SalesPlus) -> Vec<StringField> {
+  let mut v: Vec<StringField> = Vec::new();
+  if let Some(f) = &sp.filter {
+    v.push(StringField(tr("filter_active").to_owned(), f.to_string()));
+  }
+  v.push(StringField(
+    tr("generated_at").to_owned(),
+    Utc::now().to_rfc3339()
+  ));
+  v.push(StringField(tr("rows_read").to_owned(), format::int(sp.rows)));
+  v.push(StringField(tr("revenue_mode").to_owned(), sp.revenue.to_string()));
+  if sp.interrupted {
+    v.push(StringField(
+      tr("run_status").to_owned(),
+      tr("interrupted_by_time").to_owned()
+    ));
+  }
+  v.push(StringField(tr("build").to_owned(), build_info::describe()));
+  v.extend(
+    ContextInputData::from(sp.context.as_ref())
+      .describe()
+      .into_iter()
+      .map(|(k, val)| StringField(k.to_owned(), val))
+  );
+  v.extend(
+    sp.context.validate()
+      .into_iter()
+      .map(|w| StringField(tr("context_warning").to_owned(), w.to_string()))
+  );
+  return v;
+}
+
+/// Turns the stamp into a single comment-style line for the CSV footer. Also
+/// says which language the headers are in, since they follow the page.
+pub fn csv_footer(stamp: &[StringField]) -> String {
+  let parts: Vec<String> = stamp.iter()
+    .map(|f| format!("{}: {}", f.0, f.1))
+    .chain([format!("{}: {}", tr("headers_lang"), Lang::current())])
+    .collect();
+  return format!("# {}", parts.join(" | "));
+}
@@ -0,0 +1,97 @@
+//! What tables of rows, like the better CSV, need to know about their
+//! columns and rows to be sorted and styled.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::i18n::tr;
+
+/// How a column sorts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColumnKind {
+  /// Alphabetically. Also good for ISO-ish dates.
+  Text,
+  /// Numerically. Anything that isn't a number goes last.
+  Number
+}
+
+impl ColumnKind {
+  /// Compares two cells of a column of this kind.
+  pub fn cmp(&self, a: &str, b: &str) -> Ordering {
+    return match self {
+      ColumnKind::Text => a.cmp(b),
+      ColumnKind::Number => {
+        match (a.parse::<f64>().ok(), b.parse::<f64>().ok()) {
+          (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+          (Some(_), None) => Ordering::Less,
+          (None, Some(_)) => Ordering::Greater,
+          (None, None) => Ordering::Equal,
+        }
+      },
+    };
+  }
+}
+
+/// How a row's sale ended up, for styling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RowStatus {
+  /// Pricing is known.
+  Resolved,
+  /// Pricing is still ambiguous.
+  Ambiguous,
+  /// No pricing fits at all.
+  Villain,
+  /// Worth nothing: a courtesy or a test, not a pricing problem.
+  Free
+}
+
+impl RowStatus {
+  /// The CSS class for rows with this status.
+  pub fn class(&self) -> &'static str {
+    return match self {
+      RowStatus::Resolved => "row-resolved",
+      RowStatus::Ambiguous => "row-ambiguous",
+      RowStatus::Villain => "row-villain",
+      RowStatus::Free => "row-free",
+    };
+  }
+}
+
+/// How sales got resolved, for breaking counts down and coloring them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resolution {
+  /// Only one match fit the price.
+  Precise,
+  /// A solver picked among the matches.
+  Solved,
+  /// Still ambiguous.
+  Ambiguous
+}
+
+impl Display for Resolution {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      Resolution::Precise => "res_precise",
+      Resolution::Solved => "res_solved",
+      Resolution::Ambiguous => "res_ambiguous",
+    }));
+  }
+}
+
+impl Resolution {
+  /// All of them, in the order their counts are kept.
+  pub const ALL: [Resolution; 3] = [
+    Resolution::Precise,
+    Resolution::Solved,
+    Resolution::Ambiguous
+  ];
+
+  /// The CSS class for cells with counts of this.
+  pub fn class(&self) -> &'static str {
+    return match self {
+      Resolution::Precise => "cell-precise",
+      Resolution::Solved => "cell-solved",
+      Resolution::Ambiguous => "cell-ambiguous",
+    };
+  }
+}
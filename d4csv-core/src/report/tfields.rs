@@ -0,0 +1,208 @@
+//! Basic table fields for the report.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use chrono::Timelike;
+use crate::format::{int, money};
+use crate::i18n::{tr, trf};
+use crate::report::{TableField, TableFn};
+use crate::sale::kind::{SaleKind, Seller};
+use crate::sale::plus::{SalePlus, SalesPlus};
+use crate::ticket::batch::bp2iter;
+use crate::ticket::batchnum::BatchNum;
+
+/// Quick sugar for making table fields. Rows keep the iteration order.
+fn tf<K, V, I>(
+  name: &str,
+  rows: I
+) -> TableField where K: Display, V: Display, I: IntoIterator<Item = (K, V)> {
+  return TableField(
+    name.to_owned(),
+    rows.into_iter()
+      .map(|(k, v)| (k.to_string(), v.to_string()))
+      .collect()
+  );
+}
+
+/// All the functions below.
+pub static TFIELDS: &[TableFn] = &[
+  sales_per_seller,
+  sales_per_hour,
+  effective_fee,
+  fee_residuals,
+  batch_median_times,
+  commission_per_seller
+];
+
+/// Offline tickets per seller, by seller name.
+pub fn sales_per_seller(sp: &SalesPlus) -> TableField {
+  let mut bt: BTreeMap<String, usize> = BTreeMap::new();
+  sp.oks()
+    .for_each(|s| {
+      let t = s.pricematch.as_ref().unwrap().tickets();
+      if let Some(sn) = &s.sale.seller_name {
+        *bt.entry(Seller::offline(sn).to_string()).or_insert(0) += t;
+      }
+    });
+  return tf(
+    tr("tickets_per_seller"),
+    bt.into_iter().map(|(sn, t)| (sn, int(t)))
+  );
+}
+
+/// How far from the top residuals are bunched.
+static RESIDUAL_SPREAD: isize = 2;
+
+/// Resolved online sales by how many cents their value is off from the
+/// match with the fee on (see SalePlus::residual), from -2 to +2, with the
+/// rest bunched at the ends. Anything but a spike at 0 is a wrong fee.
+pub fn fee_residuals(sp: &SalesPlus) -> TableField {
+  let r = RESIDUAL_SPREAD;
+  let mut bt: BTreeMap<isize, usize> = (-r..=r).map(|c| (c, 0)).collect();
+  sp.oks()
+    .filter(|s| matches!(s.sale.sale_kind, SaleKind::Online(_)))
+    .filter_map(|s| s.residual())
+    .for_each(|c| *bt.entry(c.clamp(-r, r)).or_insert(0) += 1);
+  return tf(
+    tr("fee_residuals"),
+    bt.into_iter().map(|(c, n)| {
+      let label = match c {
+        c if c == -r => format!("≤ {}", c),
+        c if c == r => format!("≥ +{}", c),
+        c if c > 0 => format!("+{}", c),
+        c => c.to_string(),
+      };
+      return (label, int(n));
+    })
+  );
+}
+
+/// Tickets sold per hour of the day, for all 24 hours.
+pub fn sales_per_hour(sp: &SalesPlus) -> TableField {
+  let mut bt: BTreeMap<u32, usize> = (0..24).map(|h| (h, 0)).collect();
+  sp.oks()
+    .for_each(|s| {
+      let t = s.pricematch.as_ref().unwrap().tickets();
+      let h = s.sale.local_when(&sp.context).hour();
+      *bt.entry(h).or_insert(0) += t;
+    });
+  return tf(
+    tr("tickets_per_hour"),
+    bt.into_iter().map(|(h, t)| (format!("{:02}h", h), int(t)))
+  );
+}
+
+/// Implied online fee (sale value over matched price, or over the subtotal
+/// when the CSV has it), rounded to whole percents, and how many resolved
+/// online sales had it. A sanity check for the configured fee.
+pub fn effective_fee(sp: &SalesPlus) -> TableField {
+  let mut bt: BTreeMap<i64, usize> = BTreeMap::new();
+  sp.oks()
+    .filter(|s| matches!(s.sale.sale_kind, SaleKind::Online(_)))
+    .for_each(|s| {
+      let price = s.sale.subtotal
+        .unwrap_or(s.pricematch.as_ref().unwrap().price());
+      if price == 0 { return; }
+      let ratio = (s.sale.value as f64) / (price as f64);
+      let perc = ((ratio - 1.0) * 100.0).round() as i64;
+      *bt.entry(perc).or_insert(0) += 1;
+    });
+  return tf(
+    tr("effective_fee"),
+    bt.into_iter().map(|(p, n)| (format!("{}%", p), int(n)))
+  );
+}
+
+/// Median local time of the resolved sales with tickets from each batch, to
+/// see when each batch was "active". Sales with two batches count for both.
+pub fn batch_median_times(sp: &SalesPlus) -> TableField {
+  let mut hm: HashMap<BatchNum, Vec<&SalePlus>> = HashMap::new();
+  sp.oks()
+    .for_each(|s| {
+      for ba in s.pricematch.as_ref().unwrap().amounts() {
+        hm.entry(ba.0.num).or_default().push(s);
+      }
+    });
+  return tf(
+    tr("batch_median_times"),
+    bp2iter(&sp.context.batches).map(|b| {
+      // oks() keeps the sales' order, which is by date
+      let median = hm.get(&b.num)
+        .and_then(|v| v.get(v.len() / 2))
+        .map(|s| {
+          s.sale.local_when(&sp.context).format("%Y-%m-%d %H:%M").to_string()
+        });
+      return (b, median.unwrap_or_else(|| tr("no_sales_found").to_owned()));
+    })
+  );
+}
+
+/// What each offline seller gets, from their resolved sales, under the
+/// context's commission.
+pub fn commission_per_seller(sp: &SalesPlus) -> TableField {
+  // tickets, revenue (cents)
+  let mut bt: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+  sp.oks()
+    .filter(|s| matches!(s.sale.sale_kind, SaleKind::Offline(_)))
+    .for_each(|s| {
+      let name = match &s.sale.seller_name {
+        Some(sn) => Seller::offline(sn).to_string(),
+        None => return,
+      };
+      let acc = bt.entry(name).or_insert((0, 0));
+      acc.0 += s.pricematch.as_ref().unwrap().tickets();
+      acc.1 += sp.revenue.of(s);
+    });
+  let commission = sp.context.commission;
+  return tf(
+    &trf("commission_per_seller", &[&commission]),
+    bt.into_iter().map(|(sn, (t, r))| (sn, money(commission.of(t, r))))
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::context::SalesContext;
+  use crate::sale::commission::Commission;
+  use crate::testing::{priced, sale, sales_plus};
+
+  #[test]
+  fn sales_per_seller_is_sorted() {
+    let ctx = SalesContext::default();
+    let sales = vec![
+      priced(0, 13000, Some("Zé"), &ctx),
+      priced(1, 6500, Some("ana"), &ctx),
+      priced(2, 6500, Some("zé "), &ctx),
+      priced(3, 6500, Some("Bia"), &ctx),
+      priced(4, 6500, None, &ctx)
+    ];
+    let tf = sales_per_seller(&sales_plus(ctx, sales));
+    let rows: Vec<(&str, &str)> = tf.1.iter()
+      .map(|(k, v)| (k.as_str(), v.as_str()))
+      .collect();
+    assert_eq!(rows, vec![("ana", "1"), ("bia", "1"), ("zé", "3")]);
+  }
+
+  #[test]
+  fn ten_percent_commission() {
+    let ctx = SalesContext {
+      commission: Commission::Percent(10.0),
+      ..SalesContext::default()
+    };
+    let sales = vec![
+      priced(0, 6500, Some("Fulano"), &ctx),
+      priced(1, 7500, Some("fulano"), &ctx),
+      priced(2, 8500, Some("Beltrano"), &ctx),
+      // neither online nor unresolved sales pay anyone
+      priced(3, 6500, None, &ctx),
+      sale(4, 100, Some("Beltrano"), &ctx)
+    ];
+    let tf = commission_per_seller(&sales_plus(ctx, sales));
+    let rows: Vec<(&str, &str)> = tf.1.iter()
+      .map(|(k, v)| (k.as_str(), v.as_str()))
+      .collect();
+    let (r8, r14) = (money(850), money(1400));
+    assert_eq!(rows, vec![("beltrano", r8.as_str()), ("fulano", r14.as_str())]);
+  }
+}
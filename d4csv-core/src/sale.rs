@@ -0,0 +1,291 @@
+//! Ticket sale struct.
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::io::Read;
+use std::rc::Rc;
+
+use chrono::{
+  DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc
+};
+use csv::{StringRecord, StringRecordsIter};
+use serde::{Deserialize, Serialize};
+use crate::context::SalesContext;
+use crate::error::D4Error;
+use crate::sale::error::{ParseError, ParseErrorKind};
+use crate::sale::columns::{Column, ColumnMap};
+use crate::sale::interner::Interner;
+use crate::sale::kind::{SaleKind, Seller};
+
+pub mod kind;
+pub mod price_deriving;
+pub mod plus;
+pub mod ambiguity;
+pub mod filter;
+pub mod revenue;
+pub mod error;
+pub mod diff;
+pub mod interner;
+pub mod commission;
+pub mod columns;
+pub mod dedup;
+
+static NA: &str = "N/A";
+
+fn non_na<'a>(o: Option<&&'a str>) -> Option<&'a str> {
+  if let Some(s) = o {
+    if s != &NA && !s.is_empty() {
+      return Some(s);
+    }
+  }
+  return None;
+}
+
+fn field_or_na(o: Option<&&str>) -> Option<String> {
+  return non_na(o).map(|s| s.to_owned());
+}
+
+/// Parses a sale date. Tries the context's format first (assuming the
+/// context's time zone if it has no offset in it), then RFC 3339.
+fn parse_when(
+  s: &str,
+  ctx: &SalesContext
+) -> Result<DateTime<Utc>, Box<dyn Error>> {
+  let fmt = ctx.date_format.as_str();
+  if !fmt.is_empty() {
+    if let Ok(dt) = DateTime::parse_from_str(s, fmt) {
+      return Ok(dt.into());
+    }
+    if let Ok(ndt) = NaiveDateTime::parse_from_str(s, fmt) {
+      if let Some(dt) = ctx.tz().from_local_datetime(&ndt).single() {
+        return Ok(dt.into());
+      }
+    }
+  }
+  return Ok(DateTime::parse_from_rfc3339(s)?.into());
+}
+
+/// A sale, as from the CSV.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sale {
+  /// Position of this sale's record in the CSV (0 is the first data row).
+  pub row: usize,
+  /// Sale date and time.
+  pub when: DateTime<Utc>,
+  /// Buyer e-mail address.
+  pub buyer_email: Option<String>,
+  /// Buyer username.
+  pub buyer_username: Option<String>,
+  /// Sale value in cents.
+  pub value: usize,
+  /// Value before the fee in cents, if the CSV had it. When it's there, it
+  /// is the real price, with no fee to undo.
+  #[serde(default)]
+  pub subtotal: Option<usize>,
+  /// Seller data (online or offline).
+  pub sale_kind: SaleKind,
+  /// Seller name (absent when online)
+  pub seller_name: Option<Rc<str>>,
+  /// Seller ID string (no idea where it comes from).
+  pub seller_id: Option<Rc<str>>,
+  /// Seller email (absent when online).
+  pub seller_email: Option<Rc<str>>,
+  /// Seller name, normalized like Seller::offline does, so grouping sales
+  /// by seller doesn't redo it every time.
+  #[serde(default)]
+  pub seller_key: Option<Rc<str>>,
+  /// Token (also no idea).
+  pub token: String,
+  /// Sale ID (what?).
+  pub sale_id: String,
+  /// Card name (it's all blank?).
+  pub card_name: Option<String>,
+  /// Card prefix.
+  pub card_pfx: Option<String>,
+  /// Card suffix.
+  pub card_sfx: Option<String>
+}
+
+impl Sale {
+  /// Compare sales' dates. Useful for Vec::sort_by and such.
+  pub fn cmp_dates(&self, other: &Self) -> Ordering {
+    return self.when.cmp(&other.when);
+  }
+  
+  /// Return the "real price", after undoing fees and such. A subtotal from
+  /// the CSV beats undoing the configured fee.
+  pub fn real_price(&self) -> usize {
+    return self.subtotal
+      .unwrap_or_else(|| self.sale_kind.undo_fee(self.value));
+  }
+
+  /// The fee actually paid in cents, if the CSV had the subtotal to tell.
+  pub fn fee_paid(&self) -> Option<usize> {
+    return self.subtotal.map(|st| self.value.saturating_sub(st));
+  }
+}
+
+impl TryFrom<(StringRecord, &SalesContext)> for Sale {
+  type Error = D4Error;
+
+  fn try_from(
+    (r, ctx): (StringRecord, &SalesContext)
+  ) -> Result<Self, Self::Error> {
+    return Self::from_record(
+      &r,
+      ctx,
+      &ColumnMap::default(),
+      &mut Interner::default()
+    );
+  }
+}
+
+impl Sale {
+  /// Makes a sale out of a CSV record, with the fields where the map says.
+  /// The seller fields come from a pool, so sales by the same seller share
+  /// them.
+  pub fn from_record(
+    r: &StringRecord,
+    ctx: &SalesContext,
+    cols: &ColumnMap,
+    names: &mut Interner
+  ) -> Result<Self, D4Error> {
+    let v: Vec<&str> = r.iter().collect();
+    if v.len() != cols.width() {
+      return Err(ParseError::at(
+        ParseErrorKind::Columns(v.len(), cols.width()),
+        r,
+        format!("expected {} columns, got {}", cols.width(), v.len())
+      ).into());
+    }
+    // the width check above makes these all there
+    let get = |c: Column| v.get(cols.index(c));
+    let val: f64 = v[cols.index(Column::Value)].parse()
+      .map_err(|e| ParseError::at(ParseErrorKind::Value, r, e))?;
+    let when = parse_when(v[cols.index(Column::Date)], ctx)
+      .map_err(|e| ParseError::at(ParseErrorKind::Date, r, e))?;
+    let subtotal = match cols.subtotal().and_then(|i| non_na(v.get(i))) {
+      Some(st) => {
+        let st: f64 = st.parse()
+          .map_err(|e| ParseError::at(ParseErrorKind::Value, r, e))?;
+        Some((st * 100.0).round() as usize)
+      },
+      None => None,
+    };
+    let seller_name = non_na(get(Column::SellerName));
+    return Ok(Self {
+      row: 0,
+      when,
+      buyer_email: field_or_na(get(Column::BuyerEmail)),
+      buyer_username: field_or_na(get(Column::BuyerName)),
+      value: (val * 100.0).round() as usize,
+      subtotal,
+      sale_kind: {
+        let status = get(Column::Status).unwrap();
+        if ctx.forced_kind.is_online(status) {
+          SaleKind::Online(ctx.online_fee)
+        } else {
+          SaleKind::Offline(ctx.offline_fee)
+        }
+      },
+      seller_name: seller_name.map(|s| names.intern(s)),
+      seller_id: non_na(get(Column::SellerId)).map(|s| names.intern(s)),
+      seller_email: non_na(get(Column::SellerEmail))
+        .map(|s| names.intern(s)),
+      seller_key: seller_name
+        .map(|s| names.intern(&Seller::normalize(s))),
+      token: get(Column::Token).unwrap().to_string(),
+      sale_id: get(Column::Id).unwrap().to_string(),
+      card_name: field_or_na(get(Column::CardName)),
+      card_pfx: field_or_na(get(Column::CardPfx)),
+      card_sfx: field_or_na(get(Column::CardSfx)),
+    });
+  }
+}
+
+impl Sale {
+  /// Returns a vec of sales, sorted by date. The records are taken to be
+  /// laid out like the D4 export.
+  pub fn parse_csv<'r, R: Read>(
+    records: StringRecordsIter<'r, R>,
+    ctx: &SalesContext
+  ) -> (Vec<Sale>, Vec<D4Error>) {
+    let cols = ColumnMap::default();
+    let mut sv: Vec<Sale> = Vec::new();
+    let mut ev: Vec<D4Error> = Vec::new();
+    let mut names = Interner::default();
+    for (i, recres) in records.enumerate() {
+      match recres {
+        Ok(rec) => {
+          match Sale::from_record(&rec, ctx, &cols, &mut names) {
+            Ok(s) => sv.push(Sale { row: i, ..s }),
+            Err(e) => ev.push(e),
+          }
+        },
+        Err(e) => ev.push(e.into()),
+      }
+    }
+    sv.sort_by(Sale::cmp_dates);
+    return (sv, ev);
+  }
+
+  /// When this sale happened, in the event's time zone.
+  pub fn local_when(&self, ctx: &SalesContext) -> DateTime<FixedOffset> {
+    return self.when.with_timezone(&ctx.tz());
+  }
+
+  /// The day this sale happened, in the event's time zone.
+  pub fn local_day(&self, ctx: &SalesContext) -> NaiveDate {
+    return self.local_when(ctx).date_naive();
+  }
+
+  /// Whether there's any way to reach the buyer: an e-mail or a username.
+  /// "N/A" and empty ones were already left out when parsing.
+  pub fn has_contact(&self) -> bool {
+    return self.buyer_email.is_some() || self.buyer_username.is_some();
+  }
+
+  /// Infer the seller, if at all possible. Online sales may be split by day,
+  /// depending on the context.
+  pub fn seller(&self, ctx: &SalesContext) -> Option<Seller> {
+    return match (&self.sale_kind, &self.seller_name) {
+      (SaleKind::Online(_), _) if ctx.online_per_day => {
+        Some(Seller::OnlineDay(self.local_day(ctx)))
+      },
+      (SaleKind::Online(_), _) => Some(Seller::Online),
+      // (SaleKind::Online(_), Some(_)) => None,
+      (SaleKind::Offline(_), None) => None,
+      (SaleKind::Offline(_), Some(s)) => Some(match &self.seller_key {
+        Some(key) => Seller::Offline(Rc::clone(key)),
+        // hand-made sales may not have the key
+        None => Seller::offline(s),
+      }),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_the_context_date_format() {
+    let ctx = SalesContext {
+      date_format: "%d/%m/%Y %H:%M".to_owned(),
+      ..SalesContext::default()
+    };
+    // no offset in it, so it's taken to be in the event's (UTC-3)
+    let when = parse_when("31/12/2022 23:59", &ctx).unwrap();
+    let utc = Utc.with_ymd_and_hms(2023, 1, 1, 2, 59, 0).unwrap();
+    assert_eq!(when, utc);
+    assert_eq!(parse_when("2022-12-31T23:59:00-03:00", &ctx).unwrap(), utc);
+    let with_offset = SalesContext {
+      date_format: "%d/%m/%Y %H:%M %z".to_owned(),
+      ..ctx
+    };
+    let when = parse_when("31/12/2022 23:59 +0000", &with_offset).unwrap();
+    assert_eq!(when, utc - chrono::Duration::hours(3));
+    let rfc_only = SalesContext::default();
+    assert!(parse_when("31/12/2022 23:59", &rfc_only).is_err());
+  }
+}
@@ -0,0 +1,526 @@
+//! Implements ways to resolve ambiguities in pricing candidates.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::i18n::{tr, trf};
+use crate::sale::kind::Seller;
+use crate::sale::plus::SalesPlus;
+use crate::sale::price_deriving::{PricingCandidate, PricingMatch};
+use crate::ticket::batch::Batch;
+use crate::ticket::batchnum::BatchNum;
+
+/// How many of the latest resolved sales PopularityTieBreak looks at.
+pub static POPULARITY_WINDOW: usize = 20;
+
+/// A function that resolves ambiguities.
+pub type AmbiguitySolverFn = fn(&mut SalesPlus) -> usize;
+
+/// Defines a way to resolve ambiguities.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub enum AmbiguitySolver {
+  /// Does nothing.
+  DoNothing,
+  /// Resolves ambiguities by looking behind in time.
+  TemporalLookbehind,
+  /// Resolves ambiguities by looking behind in time, but accounting for
+  /// different sellers (batch changes can be asynchronous.)
+  SellerLookBehind,
+  /// Like SellerLookBehind, but first anchors the ambiguous sales before the
+  /// first resolved one to that sale's starting batch.
+  AnchorLeading,
+  /// Like SellerLookBehind, but when that gets stuck, picks the candidate
+  /// ending in the batch most of the latest resolved sales ended in.
+  PopularityTieBreak,
+  /// Assumes batches only ever go forward across the whole sale stream, no
+  /// matter the seller, and drops candidates that would mean going back.
+  /// Only fit for online-only events.
+  GlobalMonotonic
+}
+
+impl Default for AmbiguitySolver {
+  /// Returns the "best" ambiguity solver currently available.
+  fn default() -> Self {
+    return Self::SellerLookBehind;
+  }
+}
+
+impl Display for AmbiguitySolver {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      AmbiguitySolver::DoNothing => "solver_nothing",
+      AmbiguitySolver::TemporalLookbehind => "solver_temporal",
+      AmbiguitySolver::SellerLookBehind => "solver_seller",
+      AmbiguitySolver::AnchorLeading => "solver_anchor",
+      AmbiguitySolver::PopularityTieBreak => "solver_popularity",
+      AmbiguitySolver::GlobalMonotonic => "solver_global",
+    }));
+  }
+}
+
+/// Explains a resolution: who did it, which sale it leaned on (by token), and
+/// which candidates were thrown out.
+fn trace<'a, T>(
+  solver: AmbiguitySolver,
+  anchor: Option<&str>,
+  picked: &PricingMatch,
+  cands: T
+) -> String where T: IntoIterator<Item = &'a PricingMatch> {
+  let dropped = cands.into_iter()
+    .filter(|pm| *pm != picked)
+    .map(|pm| format!("{:#}", pm))
+    .join(" | ");
+  return match anchor {
+    Some(token) => trf("trace_anchored", &[&solver, &token, &dropped]),
+    None => trf("trace_free", &[&solver, &dropped])
+  };
+}
+
+/// Implementation of the DoNothing solver.
+fn do_nothing(_sp: &mut SalesPlus) -> usize {
+  return 0;
+}
+
+/// Implementation of the TemporalLookbehind solver.
+fn temporal_lookbehind(sp: &mut SalesPlus) -> usize {
+  let mut batch: Option<(Batch, String)> = None;
+  let mut res: usize = 0;
+  for sp in sp.sales.iter_mut() {
+    if let Some(pm) = &sp.pricematch {
+      // we're now sure of the batch
+      batch = Some((pm.batch_after(), sp.sale.token.clone()));
+    } else if let Some((b, token)) = &batch {
+      // we can use the known batch to solve an ambiguity
+      if let PricingCandidate::Ambiguous(hs) = &sp.pricecand {
+        let mut compat: HashSet<PricingMatch> = hs.clone().into_iter()
+          .filter(|pc| pc.batch_after() == *b)
+          .collect();
+        match compat.len() {
+          0 => continue,
+          1 => {
+            let pm = compat.drain().next().unwrap();
+            let why = trace(
+              AmbiguitySolver::TemporalLookbehind, Some(token), &pm, hs
+            );
+            sp.resolve(pm, why);
+            res += 1;
+          },
+          _ => sp.pricecand = PricingCandidate::Ambiguous(compat)
+        }
+      }
+    }
+  }
+  return res;
+}
+
+/// Implementation of the SellerLookBehind solver.
+fn seller_lookbehind(sp: &mut SalesPlus) -> usize {
+  let mut total: usize = 0;
+  let ctx = &sp.context;
+  let sellers: HashSet<Seller> = sp.sales.iter()
+    .filter_map(|s| { s.sale.seller(ctx) })
+    .collect();
+  for seller in sellers {
+    let theirs = sp.sales.iter_mut().filter(|s| {
+      if let Some(slr) = &s.sale.seller(ctx) {
+        return slr == &seller;
+      }
+      return false;
+    });
+    let mut batches: Option<HashSet<Batch>> = None;
+    let mut accbatches: HashSet<Batch> = HashSet::new();
+    // token of the latest resolved sale, which the next ones lean on
+    let mut anchor = String::new();
+    for sale in theirs {
+      if let Some(pm) = &sale.pricematch {
+        accbatches.extend(pm.batches());
+        batches = Some(pm.batches());
+        anchor = sale.sale.token.clone();
+      } else if let Some(ref bhs) = batches {
+        if let PricingCandidate::Ambiguous(cands) = sale.pricecand.clone() {
+          // remove candidates without batches in common to the above
+          let newcands: HashSet<&PricingMatch> = cands.iter()
+            .filter(|pcm| {
+              !pcm.batches().is_disjoint(&accbatches)
+            }).collect();
+          if !newcands.is_empty() {
+            // ambiguity diminished (maybe)
+            // log::info!("{:#?} virou {:#?}", cands, newcands);
+            if newcands.len() == 1 {
+              // ambiguity resolved!
+              let pm = (**newcands.iter().next().unwrap()).clone();
+              let why = trace(
+                AmbiguitySolver::SellerLookBehind, Some(&anchor), &pm, &cands
+              );
+              sale.resolve(pm, why);
+              total += 1;
+            } else {
+              // try for no new batches.
+              let nonews: HashSet<&&PricingMatch> = newcands.iter()
+                .filter(|pm| pm.batches().is_subset(bhs))
+                .collect();
+              if nonews.len() == 1 {
+                // only one with no new batches. nice!
+                let pm = (***nonews.iter().next().unwrap()).clone();
+                let why = trace(
+                  AmbiguitySolver::SellerLookBehind, Some(&anchor), &pm, &cands
+                );
+                sale.resolve(pm, why);
+                total += 1;
+              }
+            }
+            // write the new candidates anyway
+            sale.pricecand = PricingCandidate::from_iter(
+              newcands.into_iter().cloned()
+            );
+          }
+        }
+      }
+    }
+  }
+  return total;
+}
+
+/// Implementation of the AnchorLeading solver.
+fn anchor_leading(sp: &mut SalesPlus) -> usize {
+  let mut res: usize = 0;
+  // the leading sales can't have been in a later batch than the first sale
+  // we're sure about
+  let anchor: Option<(Batch, String)> = sp.sales.iter()
+    .filter_map(|s| {
+      s.pricematch.as_ref().map(|pm| (pm.batch_before(), s.sale.token.clone()))
+    })
+    .next();
+  if let Some((b, token)) = anchor {
+    for sale in sp.sales.iter_mut() {
+      if sale.pricematch.is_some() { break; }
+      if let PricingCandidate::Ambiguous(hs) = &sale.pricecand {
+        let mut compat: HashSet<PricingMatch> = hs.clone().into_iter()
+          .filter(|pc| pc.batch_after() == b)
+          .collect();
+        match compat.len() {
+          0 => continue,
+          1 => {
+            let pm = compat.drain().next().unwrap();
+            let why = trace(
+              AmbiguitySolver::AnchorLeading, Some(&token), &pm, hs
+            );
+            sale.resolve(pm, why);
+            res += 1;
+          },
+          _ => sale.pricecand = PricingCandidate::Ambiguous(compat)
+        }
+      }
+    }
+  }
+  return res + seller_lookbehind(sp);
+}
+
+/// The most common batch in a window, if there's only one.
+fn window_mode(window: &VecDeque<Batch>) -> Option<Batch> {
+  let mut counts: HashMap<Batch, usize> = HashMap::new();
+  for b in window {
+    *counts.entry(*b).or_insert(0) += 1;
+  }
+  let top = counts.values().copied().max()?;
+  let mut tops = counts.into_iter().filter(|(_, n)| *n == top);
+  return match (tops.next(), tops.next()) {
+    (Some((b, _)), None) => Some(b),
+    _ => None
+  };
+}
+
+/// Picks, for each ambiguous sale, the only candidate ending in the batch most
+/// of the latest window resolved sales ended in. Sales resolved here join the
+/// window too.
+fn popularity_pass(sp: &mut SalesPlus, window_len: usize) -> usize {
+  let mut window: VecDeque<Batch> = VecDeque::with_capacity(window_len);
+  let mut res: usize = 0;
+  for sale in sp.sales.iter_mut() {
+    if sale.pricematch.is_none() {
+      if let (PricingCandidate::Ambiguous(hs), Some(b)) = (
+        &sale.pricecand, window_mode(&window)
+      ) {
+        let mut compat = hs.iter().filter(|pc| pc.batch_after() == b);
+        if let (Some(pm), None) = (compat.next(), compat.next()) {
+          let pm = pm.clone();
+          let why = trace(AmbiguitySolver::PopularityTieBreak, None, &pm, hs);
+          sale.resolve(pm, why);
+          res += 1;
+        }
+      }
+    }
+    if let Some(pm) = &sale.pricematch {
+      if window.len() == window_len {
+        window.pop_front();
+      }
+      window.push_back(pm.batch_after());
+    }
+  }
+  return res;
+}
+
+/// Implementation of the PopularityTieBreak solver. Continuity goes first,
+/// popularity is only a last resort.
+fn popularity_tie_break(sp: &mut SalesPlus) -> usize {
+  let res = seller_lookbehind(sp);
+  if res > 0 {
+    return res;
+  }
+  return popularity_pass(sp, POPULARITY_WINDOW);
+}
+
+/// The lowest and highest numbered batches in a match, if it has any. Promo
+/// tickets are left out: they get sold along with any batch.
+fn numbered_span(pm: &PricingMatch) -> Option<(BatchNum, BatchNum)> {
+  let nums = pm.batches().into_iter()
+    .map(|b| b.num)
+    .filter(|n| *n != BatchNum::Promo);
+  return nums.minmax().into_option();
+}
+
+/// Implementation of the GlobalMonotonic solver. Each resolved sale bounds
+/// the others: the ones before it can't have reached a later batch than its
+/// first, and the ones after it can't be back in an earlier batch than its
+/// last.
+fn global_monotonic(sp: &mut SalesPlus) -> usize {
+  // the lowest batch of the next resolved sale, for each sale
+  let mut ceilings: Vec<Option<(BatchNum, String)>> =
+    vec![None; sp.sales.len()];
+  let mut next: Option<(BatchNum, String)> = None;
+  for (i, sale) in sp.sales.iter().enumerate().rev() {
+    ceilings[i] = next.clone();
+    if let Some((lo, _)) = sale.pricematch.as_ref().and_then(numbered_span) {
+      next = Some((lo, sale.sale.token.clone()));
+    }
+  }
+  // and the highest batch of the latest resolved one, as we go
+  let mut floor: Option<(BatchNum, String)> = None;
+  let mut res: usize = 0;
+  for (sale, ceiling) in sp.sales.iter_mut().zip(ceilings) {
+    if let PricingCandidate::Ambiguous(hs) = &sale.pricecand {
+      if sale.pricematch.is_none() {
+        let mut compat: HashSet<PricingMatch> = hs.iter()
+          .filter(|pc| match numbered_span(pc) {
+            Some((lo, hi)) => {
+              floor.as_ref().map(|(f, _)| lo >= *f).unwrap_or(true)
+                && ceiling.as_ref().map(|(c, _)| hi <= *c).unwrap_or(true)
+            },
+            None => true,
+          })
+          .cloned()
+          .collect();
+        match compat.len() {
+          0 => {},
+          1 => {
+            let pm = compat.drain().next().unwrap();
+            let anchor = floor.as_ref().or(ceiling.as_ref())
+              .map(|(_, t)| t.as_str());
+            let why = trace(AmbiguitySolver::GlobalMonotonic, anchor, &pm, hs);
+            sale.resolve(pm, why);
+            res += 1;
+          },
+          n if n < hs.len() => {
+            sale.pricecand = PricingCandidate::Ambiguous(compat);
+          },
+          _ => {}
+        }
+      }
+    }
+    if let Some((_, hi)) = sale.pricematch.as_ref().and_then(numbered_span) {
+      if floor.as_ref().map(|(f, _)| hi > *f).unwrap_or(true) {
+        floor = Some((hi, sale.sale.token.clone()));
+      }
+    }
+  }
+  return res;
+}
+
+impl TryFrom<&str> for AmbiguitySolver {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    return match s.to_lowercase().as_str() {
+      "nothing" => Ok(AmbiguitySolver::DoNothing),
+      "temporal" => Ok(AmbiguitySolver::TemporalLookbehind),
+      "seller" => Ok(AmbiguitySolver::SellerLookBehind),
+      "anchor" => Ok(AmbiguitySolver::AnchorLeading),
+      "popularity" => Ok(AmbiguitySolver::PopularityTieBreak),
+      "global" => Ok(AmbiguitySolver::GlobalMonotonic),
+      _ => Err(())
+    };
+  }
+}
+
+impl AmbiguitySolver {
+  pub fn name(&self) -> &'static str {
+    return match self {
+      AmbiguitySolver::DoNothing => "nothing",
+      AmbiguitySolver::TemporalLookbehind => "temporal",
+      AmbiguitySolver::SellerLookBehind => "seller",
+      AmbiguitySolver::AnchorLeading => "anchor",
+      AmbiguitySolver::PopularityTieBreak => "popularity",
+      AmbiguitySolver::GlobalMonotonic => "global",
+    };
+  }
+
+  pub fn available() -> impl Iterator<Item = Self> {
+    return [
+      Self::DoNothing,
+      Self::TemporalLookbehind,
+      Self::SellerLookBehind,
+      Self::AnchorLeading,
+      Self::PopularityTieBreak,
+      Self::GlobalMonotonic
+    ].into_iter();
+  }
+}
+
+impl From<AmbiguitySolver> for AmbiguitySolverFn {
+  fn from(solv: AmbiguitySolver) -> Self {
+    return match solv {
+      AmbiguitySolver::DoNothing => do_nothing,
+      AmbiguitySolver::TemporalLookbehind => temporal_lookbehind,
+      AmbiguitySolver::SellerLookBehind => seller_lookbehind,
+      AmbiguitySolver::AnchorLeading => anchor_leading,
+      AmbiguitySolver::PopularityTieBreak => popularity_tie_break,
+      AmbiguitySolver::GlobalMonotonic => global_monotonic,
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::context::SalesContext;
+  use crate::sale::price_deriving::BatchAmount;
+  use crate::testing::{priced, sales_plus};
+  use crate::ticket::batch::iter2bp;
+
+  #[test]
+  fn seller_lookbehind_keeps_to_known_batches() {
+    let ctx = SalesContext::default();
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    // R$ 195 is 3x 1st batch, or a promo, a 1st and a 2nd
+    let sales = vec![
+      priced(0, 6500, Some("fulano"), &ctx),
+      priced(1, 19500, Some("fulano"), &ctx)
+    ];
+    let mut sp = sales_plus(ctx, sales);
+    assert_eq!(sp.sales[1].pricecand.count(), 2);
+    assert_eq!(seller_lookbehind(&mut sp), 1);
+    let pm = sp.sales[1].pricematch.as_ref().unwrap();
+    assert_eq!(pm.batches(), HashSet::from([first]));
+  }
+
+  #[test]
+  fn anchor_leading_goes_by_the_first_known_batch() {
+    let ctx = SalesContext::default();
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    let sales = vec![
+      priced(0, 19500, None, &ctx),
+      priced(1, 19500, None, &ctx),
+      priced(2, 6500, None, &ctx)
+    ];
+    let mut sp = sales_plus(ctx, sales);
+    assert!(sp.sales[..2].iter().all(|s| s.pricecand.count() == 2));
+    assert_eq!(anchor_leading(&mut sp), 2);
+    for s in &sp.sales[..2] {
+      assert_eq!(
+        s.pricematch,
+        Some(PricingMatch::Multiple(BatchAmount(first, 3)))
+      );
+    }
+  }
+
+  #[test]
+  fn online_per_day_looks_behind_within_the_day() {
+    for per_day in [false, true] {
+      let ctx = SalesContext {
+        online_per_day: per_day,
+        ..SalesContext::default()
+      };
+      let sales = vec![
+        priced(0, 6500, None, &ctx),
+        priced(24 * 60, 19500, None, &ctx)
+      ];
+      let mut sp = sales_plus(ctx, sales);
+      let sellers: Vec<String> = sp.sales.iter()
+        .filter_map(|s| s.sale.seller(&sp.context))
+        .map(|s| s.to_string())
+        .collect();
+      let want = match per_day {
+        true => vec!["Online 2022-10-01", "Online 2022-10-02"],
+        false => vec!["Online", "Online"]
+      };
+      assert_eq!(sellers, want);
+      // yesterday's 1st batch sale only counts when online is one seller
+      assert_eq!(seller_lookbehind(&mut sp), !per_day as usize);
+    }
+  }
+
+  #[test]
+  fn popularity_goes_by_the_window() {
+    let ctx = SalesContext::default();
+    let second = Batch { num: BatchNum::Numbered(2), price: 7500 };
+    let sales = vec![
+      priced(0, 6500, None, &ctx),
+      priced(1, 6500, None, &ctx),
+      priced(2, 7500, None, &ctx),
+      priced(3, 7500, None, &ctx),
+      priced(4, 19500, None, &ctx)
+    ];
+    let sp = sales_plus(ctx, sales);
+    // the last two sales ended in the 2nd batch
+    let mut two = sp.clone();
+    assert_eq!(popularity_pass(&mut two, 2), 1);
+    let pm = two.sales[4].pricematch.as_ref().unwrap();
+    assert_eq!(pm.batch_after(), second);
+    // the last four are a tie, so there's no telling
+    let mut four = sp;
+    assert_eq!(popularity_pass(&mut four, 4), 0);
+  }
+
+  #[test]
+  fn global_monotonic_never_goes_back() {
+    // R$ 180 is 3x 1st batch or 2x 2nd
+    let ctx = SalesContext {
+      batches: iter2bp([6000, 9000], None),
+      has_promo: false,
+      ..SalesContext::default()
+    };
+    let second = Batch { num: BatchNum::Numbered(2), price: 9000 };
+    // someone else already sold from the 2nd batch, so it's past the 1st
+    let sales = vec![
+      priced(0, 9000, Some("fulano"), &ctx),
+      priced(1, 18000, None, &ctx)
+    ];
+    let mut sp = sales_plus(ctx, sales);
+    assert_eq!(sp.sales[1].pricecand.count(), 2);
+    assert_eq!(global_monotonic(&mut sp), 1);
+    assert_eq!(
+      sp.sales[1].pricematch,
+      Some(PricingMatch::Multiple(BatchAmount(second, 2)))
+    );
+    assert!(sp.sales[1].resolution_trace.is_some());
+  }
+
+  #[test]
+  fn global_monotonic_looks_ahead_too() {
+    let ctx = SalesContext::default();
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    // a promo, a 1st and a 2nd would mean the 1st batch came back later
+    let sales = vec![
+      priced(0, 19500, None, &ctx),
+      priced(1, 6500, Some("fulano"), &ctx)
+    ];
+    let mut sp = sales_plus(ctx, sales);
+    assert_eq!(global_monotonic(&mut sp), 1);
+    assert_eq!(
+      sp.sales[0].pricematch,
+      Some(PricingMatch::Multiple(BatchAmount(first, 3)))
+    );
+  }
+}
@@ -0,0 +1,327 @@
+//! Finding the sale fields in the CSV by their headers. Exports don't always
+//! spell them the same ("Data Compra", "DataCompra", "data_compra"), so the
+//! headers are normalized before being looked up among the known aliases.
+//! When some field can't be found, columns are taken by position, like the
+//! D4 export lays them out. Optional fields are only ever found by header.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use crate::i18n::tr;
+
+/// The fields of a sale, in the order the D4 export has them.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize,
+  Deserialize
+)]
+pub enum Column {
+  /// When the sale happened.
+  Date,
+  /// The buyer's e-mail.
+  BuyerEmail,
+  /// The buyer's username.
+  BuyerName,
+  /// How much was paid, in reais.
+  Value,
+  /// Whether it was online or offline.
+  Status,
+  /// The point of sale's name.
+  SellerName,
+  /// The point of sale's ID.
+  SellerId,
+  /// The point of sale's e-mail.
+  SellerEmail,
+  /// The sale's token.
+  Token,
+  /// The sale's ID.
+  Id,
+  /// The name on the card.
+  CardName,
+  /// The card's first digits.
+  CardPfx,
+  /// The card's last digits.
+  CardSfx,
+  /// How much was paid before the fee, in reais. Optional: only some exports
+  /// have it, next to the fee-inclusive value.
+  Subtotal
+}
+
+impl Display for Column {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      Column::Date => "h_date",
+      Column::BuyerEmail => "h_buyer_email",
+      Column::BuyerName => "h_buyer_name",
+      Column::Value => "h_value",
+      Column::Status => "h_status",
+      Column::SellerName => "h_seller_name",
+      Column::SellerId => "h_seller_id",
+      Column::SellerEmail => "h_seller_email",
+      Column::Token => "h_token",
+      Column::Id => "h_id",
+      Column::CardName => "h_card_name",
+      Column::CardPfx => "h_card_pfx",
+      Column::CardSfx => "h_card_sfx",
+      Column::Subtotal => "h_subtotal",
+    }));
+  }
+}
+
+impl TryFrom<&str> for Column {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    return Column::every()
+      .find(|c| c.name() == s.to_lowercase())
+      .ok_or(());
+  }
+}
+
+impl Column {
+  /// All the required ones, in the export's order.
+  pub const ALL: [Column; 13] = [
+    Column::Date,
+    Column::BuyerEmail,
+    Column::BuyerName,
+    Column::Value,
+    Column::Status,
+    Column::SellerName,
+    Column::SellerId,
+    Column::SellerEmail,
+    Column::Token,
+    Column::Id,
+    Column::CardName,
+    Column::CardPfx,
+    Column::CardSfx
+  ];
+
+  /// The ones that may be missing.
+  pub const OPTIONAL: [Column; 1] = [Column::Subtotal];
+
+  /// Required ones first, then optional ones.
+  pub fn every() -> impl Iterator<Item = Column> {
+    return Column::ALL.into_iter().chain(Column::OPTIONAL);
+  }
+
+  /// A short name, for URLs and the command line.
+  pub fn name(&self) -> &'static str {
+    return match self {
+      Column::Date => "date",
+      Column::BuyerEmail => "buyer_email",
+      Column::BuyerName => "buyer_name",
+      Column::Value => "value",
+      Column::Status => "status",
+      Column::SellerName => "seller_name",
+      Column::SellerId => "seller_id",
+      Column::SellerEmail => "seller_email",
+      Column::Token => "token",
+      Column::Id => "id",
+      Column::CardName => "card_name",
+      Column::CardPfx => "card_first",
+      Column::CardSfx => "card_last",
+      Column::Subtotal => "subtotal",
+    };
+  }
+
+  /// Headers known to mean this field, already normalized. Both the D4
+  /// export's names and the ones in our own CSVs are here.
+  pub fn aliases(&self) -> &'static [&'static str] {
+    return match self {
+      Column::Date => &[
+        "datacompra", "datadacompra", "data", "datahora", "purchasedate",
+        "date"
+      ],
+      Column::BuyerEmail => &[
+        "emailusuarioassociado", "emailusuario", "emailcomprador",
+        "buyeremail", "email"
+      ],
+      Column::BuyerName => &[
+        "nomeusuarioassociado", "nomeusuario", "nomecomprador", "usuario",
+        "buyername", "username"
+      ],
+      Column::Value => &[
+        "valordacompra", "valorcompra", "valor", "purchasevalue", "value"
+      ],
+      Column::Status => &["status", "tipo", "tipovenda"],
+      Column::SellerName => &[
+        "nomevendedor", "vendedor", "pontodevenda", "sellername", "seller"
+      ],
+      Column::SellerId => &["idvendedor", "sellerid"],
+      Column::SellerEmail => &["emailvendedor", "selleremail"],
+      Column::Token => &["token"],
+      Column::Id => &["id", "idcompra", "idvenda", "saleid"],
+      Column::CardName => &["nomecartao", "nomenocartao", "cardname"],
+      Column::CardPfx => &[
+        "primdigitoscartao", "primeirosdigitoscartao", "cardfirstdigits"
+      ],
+      Column::CardSfx => &[
+        "ultdigitoscartao", "ultimosdigitoscartao", "cardlastdigits"
+      ],
+      Column::Subtotal => &[
+        "subtotal", "valorsemtaxa", "valorsemtaxas", "valorbase",
+        "valorliquido", "netvalue"
+      ],
+    };
+  }
+
+  /// Which field a header means: the manual mappings first, then the known
+  /// aliases. The manual mappings are keyed by normalized header.
+  pub fn recognize(
+    header: &str,
+    manual: &HashMap<String, Column>
+  ) -> Option<Self> {
+    let norm = normalize_header(header);
+    if let Some(c) = manual.get(&norm) {
+      return Some(*c);
+    }
+    return Column::every()
+      .find(|c| c.aliases().contains(&norm.as_str()));
+  }
+}
+
+/// Lowercases a header and drops accents, spaces, underscores and such, so
+/// "Data Compra", "DataCompra" and "data_compra" all come out the same.
+pub fn normalize_header(s: &str) -> String {
+  return s.chars()
+    .flat_map(|c| c.to_lowercase())
+    .filter_map(|c| match c {
+      'á' | 'à' | 'â' | 'ã' | 'ä' => Some('a'),
+      'é' | 'è' | 'ê' | 'ë' => Some('e'),
+      'í' | 'ì' | 'î' | 'ï' => Some('i'),
+      'ó' | 'ò' | 'ô' | 'õ' | 'ö' => Some('o'),
+      'ú' | 'ù' | 'û' | 'ü' => Some('u'),
+      'ç' => Some('c'),
+      'ñ' => Some('n'),
+      c if c.is_alphanumeric() => Some(c),
+      _ => None,
+    })
+    .collect();
+}
+
+/// Where each field is in a record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnMap {
+  /// Index of each required field's column, in Column::ALL's order.
+  pos: [usize; 13],
+  /// Index of the subtotal column, if there's one.
+  subtotal: Option<usize>,
+  /// How many columns a record should have.
+  width: usize
+}
+
+impl Default for ColumnMap {
+  /// The D4 export's layout.
+  fn default() -> Self {
+    return Self {
+      pos: std::array::from_fn(|i| i),
+      subtotal: None,
+      width: Column::ALL.len()
+    };
+  }
+}
+
+impl ColumnMap {
+  /// Maps the fields by header. Falls back to the default layout when some
+  /// required field has no header for it; the HeaderInfo says what went
+  /// unmatched.
+  pub fn from_headers(
+    headers: &StringRecord,
+    manual: &HashMap<String, Column>
+  ) -> (Self, HeaderInfo) {
+    let mut found: HashMap<Column, usize> = HashMap::new();
+    let mut info = HeaderInfo::default();
+    for (i, h) in headers.iter().enumerate() {
+      match Column::recognize(h, manual) {
+        Some(c) => {
+          found.entry(c).or_insert(i);
+        },
+        None => info.unknown.push(h.to_owned()),
+      }
+    }
+    info.missing = Column::ALL.iter()
+      .filter(|c| !found.contains_key(c))
+      .copied()
+      .collect();
+    info.optional = Column::OPTIONAL.iter()
+      .filter(|c| !found.contains_key(c))
+      .copied()
+      .collect();
+    if !info.missing.is_empty() {
+      return (Self::default(), info);
+    }
+    let pos = Column::ALL.map(|c| found[&c]);
+    let subtotal = found.get(&Column::Subtotal).copied();
+    return (Self { pos, subtotal, width: headers.len() }, info);
+  }
+
+  /// Where a required field is.
+  pub fn index(&self, c: Column) -> usize {
+    return self.pos[c as usize];
+  }
+
+  /// Where the subtotal is, if there's one.
+  pub fn subtotal(&self) -> Option<usize> {
+    return self.subtotal;
+  }
+
+  /// How many columns a record should have.
+  pub fn width(&self) -> usize {
+    return self.width;
+  }
+}
+
+/// What didn't match when mapping the headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderInfo {
+  /// Headers that don't mean any known field, as they were.
+  pub unknown: Vec<String>,
+  /// Fields no header meant. If there's any, the columns were taken by
+  /// position.
+  pub missing: Vec<Column>,
+  /// Optional fields no header meant.
+  #[serde(default)]
+  pub optional: Vec<Column>
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn header_spellings() {
+    let none = HashMap::new();
+    let spellings = [
+      ("Data Compra", Column::Date),
+      ("DataCompra", Column::Date),
+      ("data_compra", Column::Date),
+      (" DATA-COMPRA ", Column::Date),
+      ("E-mail Usuário", Column::BuyerEmail),
+      ("Valor sem taxa", Column::Subtotal),
+      ("Últ. Dígitos Cartão", Column::CardSfx),
+      ("ID Compra", Column::Id)
+    ];
+    for (h, c) in spellings {
+      assert_eq!(Column::recognize(h, &none), Some(c), "{}", h);
+    }
+    assert_eq!(Column::recognize("Observações", &none), None);
+    let manual = HashMap::from([
+      (normalize_header("Observações"), Column::Token)
+    ]);
+    assert_eq!(Column::recognize("observacoes", &manual), Some(Column::Token));
+  }
+
+  #[test]
+  fn maps_shuffled_headers() {
+    let mut headers: Vec<String> = Column::ALL.iter()
+      .map(|c| c.aliases()[0].to_uppercase())
+      .collect();
+    headers.reverse();
+    headers.push("Extra".to_owned());
+    let rec = StringRecord::from(headers.clone());
+    let (map, info) = ColumnMap::from_headers(&rec, &HashMap::new());
+    assert!(info.missing.is_empty());
+    assert_eq!(info.unknown, vec!["Extra".to_owned()]);
+    assert_eq!(map.index(Column::ALL[0]), Column::ALL.len() - 1);
+    assert_eq!(map.width(), headers.len());
+  }
+}
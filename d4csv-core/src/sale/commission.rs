@@ -0,0 +1,47 @@
+//! What offline points of sale get paid for selling. Online sales go through
+//! the ticketing site, so there's nobody to pay there.
+
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::format;
+use crate::i18n::{tr, trf};
+
+/// A commission rate for offline sellers.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Commission {
+  /// Nobody gets anything.
+  Nothing,
+  /// A fixed amount per resolved ticket, in cents.
+  PerTicket(usize),
+  /// A percentage of the money taken, 10.0 meaning 10%.
+  Percent(f64)
+}
+
+impl Default for Commission {
+  fn default() -> Self {
+    return Self::Nothing;
+  }
+}
+
+impl Display for Commission {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      Commission::Nothing => write!(f, "{}", tr("none_f")),
+      Commission::PerTicket(c) => {
+        write!(f, "{}", trf("commission_per_ticket", &[&format::money(*c)]))
+      },
+      Commission::Percent(p) => write!(f, "{}%", p),
+    };
+  }
+}
+
+impl Commission {
+  /// How much a seller gets, in cents, for some tickets and money taken.
+  pub fn of(&self, tickets: usize, revenue: usize) -> usize {
+    return match self {
+      Commission::Nothing => 0,
+      Commission::PerTicket(c) => c * tickets,
+      Commission::Percent(p) => ((revenue as f64) * p / 100.0).round() as usize,
+    };
+  }
+}
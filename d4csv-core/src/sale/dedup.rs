@@ -0,0 +1,154 @@
+//! Dropping sales that show up more than once, like when two exports get
+//! pasted together. What makes two lines the same sale depends on the
+//! export: some have unique sale IDs, some only unique tokens.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::i18n::tr;
+use crate::sale::{NA, Sale};
+
+/// Which field tells sales apart when dropping repeated ones.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub enum DedupKey {
+  /// Sales with the same ID are the same sale.
+  SaleId,
+  /// Sales with the same token are the same sale.
+  Token,
+  /// Every line is a sale of its own.
+  None
+}
+
+impl Default for DedupKey {
+  /// Nothing gets dropped unless asked for.
+  fn default() -> Self {
+    return Self::None;
+  }
+}
+
+impl Display for DedupKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      DedupKey::SaleId => "dedup_id",
+      DedupKey::Token => "dedup_token",
+      DedupKey::None => "dedup_none",
+    }));
+  }
+}
+
+impl TryFrom<&str> for DedupKey {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    return DedupKey::available()
+      .find(|dk| dk.name() == s.to_lowercase())
+      .ok_or(());
+  }
+}
+
+impl DedupKey {
+  /// A short name, for URLs and the command line.
+  pub fn name(&self) -> &'static str {
+    return match self {
+      DedupKey::SaleId => "id",
+      DedupKey::Token => "token",
+      DedupKey::None => "none",
+    };
+  }
+
+  pub fn available() -> impl Iterator<Item = Self> {
+    return [Self::None, Self::SaleId, Self::Token].into_iter();
+  }
+
+  /// What a sale is told apart by, if anything. Empty and "N/A" values
+  /// tell nothing, so those sales are never dropped.
+  fn of<'s>(&self, sale: &'s Sale) -> Option<&'s str> {
+    let k = match self {
+      DedupKey::SaleId => sale.sale_id.as_str(),
+      DedupKey::Token => sale.token.as_str(),
+      DedupKey::None => return None,
+    };
+    return match k.is_empty() || k == NA {
+      true => None,
+      false => Some(k),
+    };
+  }
+}
+
+/// Keeps only the first of each group of sales with the same key, in the
+/// order they came. Returns what's left, and how many were dropped.
+pub fn dedup(sales: Vec<Sale>, key: DedupKey) -> (Vec<Sale>, usize) {
+  if key == DedupKey::None {
+    return (sales, 0);
+  }
+  let before = sales.len();
+  let mut seen: HashSet<String> = HashSet::new();
+  let kept: Vec<Sale> = sales.into_iter()
+    .filter(|s| match key.of(s) {
+      Some(k) => seen.insert(k.to_owned()),
+      None => true,
+    })
+    .collect();
+  let dropped = before - kept.len();
+  return (kept, dropped);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::context::SalesContext;
+  use crate::testing::sale;
+
+  /// A sale with some ID and token.
+  fn keyed(minutes: i64, id: &str, token: &str) -> Sale {
+    return Sale {
+      sale_id: id.to_owned(),
+      token: token.to_owned(),
+      ..sale(minutes, 7150, None, &SalesContext::default())
+    };
+  }
+
+  /// The same sale pasted twice, then one sharing only the ID, one sharing
+  /// only the token, and two with nothing to tell them by.
+  fn sales() -> Vec<Sale> {
+    return vec![
+      keyed(0, "i0", "t0"),
+      keyed(0, "i0", "t0"),
+      keyed(1, "i0", "t1"),
+      keyed(2, "i2", "t0"),
+      keyed(3, NA, ""),
+      keyed(4, NA, ""),
+    ];
+  }
+
+  /// Which rows made it.
+  fn rows(key: DedupKey) -> (Vec<usize>, usize) {
+    let (kept, dropped) = dedup(sales(), key);
+    return (kept.iter().map(|s| s.row).collect(), dropped);
+  }
+
+  #[test]
+  fn by_sale_id() {
+    assert_eq!(rows(DedupKey::SaleId), (vec![0, 2, 3, 4], 2));
+  }
+
+  #[test]
+  fn by_token() {
+    assert_eq!(rows(DedupKey::Token), (vec![0, 1, 3, 4], 2));
+  }
+
+  #[test]
+  fn by_nothing() {
+    assert_eq!(rows(DedupKey::None), (vec![0, 0, 1, 2, 3, 4], 0));
+  }
+
+  #[test]
+  fn names_go_both_ways() {
+    for dk in DedupKey::available() {
+      assert_eq!(DedupKey::try_from(dk.name()), Ok(dk));
+    }
+    assert_eq!(DedupKey::try_from("TOKEN"), Ok(DedupKey::Token));
+    assert_eq!(DedupKey::try_from("email"), Err(()));
+  }
+}
@@ -0,0 +1,108 @@
+//! Comparing two runs over the same sales, to see what changing the context
+//! (or the solver) did to them. Sales are matched by their IDs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use crate::i18n::tr;
+use crate::sale::plus::{SalePlus, SalesPlus};
+
+/// How a sale's resolution changed from one run to the next.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Change {
+  /// It wasn't resolved, and now it is.
+  Resolved,
+  /// It was resolved, and now it isn't.
+  Unresolved,
+  /// It was resolved both times, but not the same way.
+  Different,
+  /// It wasn't resolved either time, but the candidates aren't the same.
+  Candidates
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      Change::Resolved => "change_resolved",
+      Change::Unresolved => "change_unresolved",
+      Change::Different => "change_different",
+      Change::Candidates => "change_candidates",
+    }));
+  }
+}
+
+impl Change {
+  /// How a sale changed between runs, if it did.
+  pub fn between(before: &SalePlus, after: &SalePlus) -> Option<Self> {
+    return match (&before.pricematch, &after.pricematch) {
+      (None, Some(_)) => Some(Change::Resolved),
+      (Some(_), None) => Some(Change::Unresolved),
+      (Some(b), Some(a)) if b != a => Some(Change::Different),
+      (None, None) if before.pricecand != after.pricecand => {
+        Some(Change::Candidates)
+      },
+      _ => None,
+    };
+  }
+}
+
+/// A sale that changed, and what it was decoded as each time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaleChange {
+  /// The sale's ID, as in the CSV.
+  pub sale_id: String,
+  /// What happened to it.
+  pub change: Change,
+  /// Its decoding in the earlier run.
+  pub before: String,
+  /// Its decoding in the later run.
+  pub after: String
+}
+
+/// Everything that changed from one run to another.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunDiff {
+  /// The sales that changed, in the later run's order.
+  pub changes: Vec<SaleChange>,
+  /// How many sales were only in the earlier run.
+  pub gone: usize,
+  /// How many sales are only in the later run.
+  pub new: usize
+}
+
+impl RunDiff {
+  /// Compares two runs, sale by sale.
+  pub fn between(before: &SalesPlus, after: &SalesPlus) -> Self {
+    let mut old: HashMap<&str, &SalePlus> = before.sales.iter()
+      .map(|s| (s.sale.sale_id.as_str(), s))
+      .collect();
+    let mut diff = Self::default();
+    for a in &after.sales {
+      let b = match old.remove(a.sale.sale_id.as_str()) {
+        Some(b) => b,
+        None => {
+          diff.new += 1;
+          continue;
+        }
+      };
+      if let Some(change) = Change::between(b, a) {
+        diff.changes.push(SaleChange {
+          sale_id: a.sale.sale_id.clone(),
+          change,
+          before: b.decoding(),
+          after: a.decoding()
+        });
+      }
+    }
+    diff.gone = old.len();
+    return diff;
+  }
+
+  /// How many sales changed in each way.
+  pub fn counts(&self) -> BTreeMap<Change, usize> {
+    let mut bt: BTreeMap<Change, usize> = BTreeMap::new();
+    for sc in &self.changes {
+      *bt.entry(sc.change).or_insert(0) += 1;
+    }
+    return bt;
+  }
+}
@@ -0,0 +1,137 @@
+//! What can go wrong when turning CSV lines into sales, and grouping those
+//! mistakes so a wrong delimiter doesn't print the same thing 8000 times.
+
+use std::error::Error;
+use std::fmt::Display;
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use crate::error::D4Error;
+use crate::i18n::{tr, trf};
+
+/// How many line numbers are shown for each group before collapsing.
+pub static EXAMPLE_LINES: usize = 5;
+
+/// The kinds of parsing mistakes. Errors are grouped by these.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ParseErrorKind {
+  /// Wrong number of columns. Holds how many there were, and how many there
+  /// should have been.
+  Columns(usize, usize),
+  /// The sale value isn't a number.
+  Value,
+  /// The date doesn't fit the format.
+  Date,
+  /// The CSV reader itself gave up on the line.
+  Csv,
+  /// Anything else. Holds the message, so different ones aren't lumped.
+  Other(String)
+}
+
+impl Display for ParseErrorKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      ParseErrorKind::Columns(n, w) => {
+        write!(f, "{}", trf("err_columns", &[n, w]))
+      },
+      ParseErrorKind::Value => write!(f, "{}", tr("err_value")),
+      ParseErrorKind::Date => write!(f, "{}", tr("err_date")),
+      ParseErrorKind::Csv => write!(f, "{}", tr("err_csv")),
+      ParseErrorKind::Other(s) => write!(f, "{}", s),
+    };
+  }
+}
+
+/// A CSV line that didn't make a sale.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseError {
+  /// What went wrong.
+  pub kind: ParseErrorKind,
+  /// The line in the CSV text (1 is the first), if known.
+  pub line: Option<u64>,
+  /// The details, straight from whoever complained.
+  pub detail: String
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(l) = self.line {
+      write!(f, "{}", trf("line_n", &[&l]))?;
+    }
+    return match self.detail.is_empty() {
+      true => write!(f, "{}", self.kind),
+      false => write!(f, "{}: {}", self.kind, self.detail)
+    };
+  }
+}
+
+impl Error for ParseError {}
+
+impl ParseError {
+  /// An error about some record, with its line if the reader knew it.
+  pub fn at<T>(kind: ParseErrorKind, r: &StringRecord, detail: T) -> Self
+  where T: Display {
+    return Self {
+      kind,
+      line: r.position().map(|p| p.line()),
+      detail: detail.to_string()
+    };
+  }
+}
+
+impl From<csv::Error> for ParseError {
+  fn from(e: csv::Error) -> Self {
+    return Self {
+      kind: ParseErrorKind::Csv,
+      line: e.position().map(|p| p.line()),
+      detail: e.to_string()
+    };
+  }
+}
+
+impl From<&D4Error> for ParseError {
+  /// Keeps line errors as they are, and makes others into Other.
+  fn from(e: &D4Error) -> Self {
+    if let D4Error::Line(pe) = e {
+      return pe.clone();
+    }
+    return Self {
+      kind: ParseErrorKind::Other(e.to_string()),
+      line: None,
+      detail: String::new()
+    };
+  }
+}
+
+/// Errors of the same kind, together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorGroup {
+  /// What they have in common.
+  pub kind: ParseErrorKind,
+  /// How many there were.
+  pub count: usize,
+  /// The first one, to show the details.
+  pub example: String,
+  /// All the known lines, in order of appearance.
+  pub lines: Vec<u64>
+}
+
+/// Groups errors by kind, keeping the order in which kinds first appeared.
+pub fn group_errors(ve: &[D4Error]) -> Vec<ErrorGroup> {
+  let mut groups: Vec<ErrorGroup> = Vec::new();
+  for e in ve {
+    let pe = ParseError::from(e);
+    match groups.iter_mut().find(|g| g.kind == pe.kind) {
+      Some(g) => {
+        g.count += 1;
+        g.lines.extend(pe.line);
+      },
+      None => groups.push(ErrorGroup {
+        kind: pe.kind,
+        count: 1,
+        example: pe.detail,
+        lines: pe.line.into_iter().collect()
+      }),
+    }
+  }
+  return groups;
+}
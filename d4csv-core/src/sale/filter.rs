@@ -0,0 +1,68 @@
+//! Picking which sales a report talks about. Filters apply after solving, so
+//! the resolutions still come from the whole data.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::context::SalesContext;
+use crate::i18n::{tr, trf};
+use crate::sale::plus::SalePlus;
+
+/// A filter by seller and by (local) day.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SalesFilter {
+  /// Names of the sellers to keep. Empty keeps everyone.
+  pub sellers: BTreeSet<String>,
+  /// First day to keep, inclusive.
+  pub from: Option<NaiveDate>,
+  /// Last day to keep, inclusive.
+  pub until: Option<NaiveDate>
+}
+
+impl SalesFilter {
+  /// Whether this filter keeps everything.
+  pub fn is_empty(&self) -> bool {
+    return self.sellers.is_empty()
+      && self.from.is_none()
+      && self.until.is_none();
+  }
+
+  /// Whether a sale passes the filter.
+  pub fn matches(&self, s: &SalePlus, ctx: &SalesContext) -> bool {
+    if !self.sellers.is_empty() {
+      match s.sale.seller(ctx) {
+        Some(slr) if self.sellers.contains(&slr.to_string()) => {},
+        _ => return false,
+      }
+    }
+    let day = s.sale.local_day(ctx);
+    if self.from.map(|d| day < d).unwrap_or(false) {
+      return false;
+    }
+    if self.until.map(|d| day > d).unwrap_or(false) {
+      return false;
+    }
+    return true;
+  }
+}
+
+impl Display for SalesFilter {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let mut parts: Vec<String> = Vec::new();
+    if !self.sellers.is_empty() {
+      parts.push(trf("filter_sellers", &[&self.sellers.iter().join(", ")]));
+    }
+    if let Some(d) = self.from {
+      parts.push(trf("filter_from", &[&d.format("%Y-%m-%d")]));
+    }
+    if let Some(d) = self.until {
+      parts.push(trf("filter_until", &[&d.format("%Y-%m-%d")]));
+    }
+    if parts.is_empty() {
+      return write!(f, "{}", tr("none_m"));
+    }
+    return write!(f, "{}", parts.join("; "));
+  }
+}
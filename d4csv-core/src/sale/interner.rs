@@ -0,0 +1,34 @@
+//! A pool of shared strings. A big export has thousands of sales but only a
+//! handful of sellers, so their names are stored once and shared.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Hands out one shared copy of each distinct string it's given.
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+  /// Every string handed out so far.
+  pool: HashSet<Rc<str>>
+}
+
+impl Interner {
+  /// The shared copy of a string, made on the first time it's seen.
+  pub fn intern(&mut self, s: &str) -> Rc<str> {
+    if let Some(rc) = self.pool.get(s) {
+      return Rc::clone(rc);
+    }
+    let rc: Rc<str> = Rc::from(s);
+    self.pool.insert(Rc::clone(&rc));
+    return rc;
+  }
+
+  /// How many distinct strings are in the pool.
+  pub fn len(&self) -> usize {
+    return self.pool.len();
+  }
+
+  /// Whether nothing was interned yet.
+  pub fn is_empty(&self) -> bool {
+    return self.pool.is_empty();
+  }
+}
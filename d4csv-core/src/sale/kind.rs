@@ -0,0 +1,183 @@
+//! Seller abstractions: online or... somewhere.
+
+use std::fmt::Display;
+use std::rc::Rc;
+use chrono::NaiveDate;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::i18n::tr;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SaleKind {
+  /// Online sale, with some integer fraction as the fee.
+  Online((usize, usize)),
+  /// Face-to-face sale, by someone, maybe with a cash-handling fee as an
+  /// integer fraction too.
+  Offline(Option<(usize, usize)>)
+}
+
+impl SaleKind {
+  /// The fee, if there's any.
+  pub fn fee(&self) -> Option<(usize, usize)> {
+    return match self {
+      Self::Online(f) => Some(*f),
+      Self::Offline(f) => *f,
+    };
+  }
+
+  /// Apply the fee, if there's any.
+  pub fn apply_fee(&self, price: usize) -> usize {
+    if let Some((k, d)) = self.fee() {
+      return price * k / d;
+    } else {
+      return price;
+    }
+  }
+
+  /// Undo the fee, if there's any.
+  pub fn undo_fee(&self, price: usize) -> usize {
+    if let Some((k, d)) = self.fee() {
+      return price * d / k;
+    } else {
+      return price;
+    }
+  }
+}
+
+impl Display for SaleKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "Paga {}", match self {
+      SaleKind::Online(_) => "Online",
+      SaleKind::Offline(_) => "Físico",
+    });
+  }
+}
+
+/// Whether sales are taken as online or offline by their status column, or
+/// all forced to one of them. Forcing is for debugging fee trouble, or
+/// getting by when the status column is garbled.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub enum ForcedKind {
+  /// Go by the status column.
+  Detect,
+  /// Every sale is online, fee and all.
+  Online,
+  /// Every sale is offline.
+  Offline
+}
+
+impl Default for ForcedKind {
+  fn default() -> Self {
+    return Self::Detect;
+  }
+}
+
+impl Display for ForcedKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      ForcedKind::Detect => "kind_detect",
+      ForcedKind::Online => "kind_online",
+      ForcedKind::Offline => "kind_offline",
+    }));
+  }
+}
+
+impl TryFrom<&str> for ForcedKind {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    return ForcedKind::available()
+      .find(|fk| fk.name() == s.to_lowercase())
+      .ok_or(());
+  }
+}
+
+impl ForcedKind {
+  /// A short name, for URLs and the command line.
+  pub fn name(&self) -> &'static str {
+    return match self {
+      ForcedKind::Detect => "detect",
+      ForcedKind::Online => "online",
+      ForcedKind::Offline => "offline",
+    };
+  }
+
+  pub fn available() -> impl Iterator<Item = Self> {
+    return [Self::Detect, Self::Online, Self::Offline].into_iter();
+  }
+
+  /// Whether a sale with this status is online.
+  pub fn is_online(&self, status: &str) -> bool {
+    return match self {
+      ForcedKind::Detect => status.contains("Online"),
+      ForcedKind::Online => true,
+      ForcedKind::Offline => false,
+    };
+  }
+}
+
+/// An alternative version of SaleKind -- more suited to store actual seller
+/// information.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Seller {
+  /// Onlne sale.
+  Online,
+  /// Online sale, split by (local) day.
+  OnlineDay(NaiveDate),
+  /// Offline sale, containing the (normalized) seller name.
+  Offline(Rc<str>)
+}
+
+impl Seller {
+  /// Normalizes a seller name (trimmed, inner spaces squeezed, lowercased)
+  /// so "Fulano " and "fulano" are the same person.
+  pub fn normalize(name: &str) -> String {
+    return name.split_whitespace().join(" ").to_lowercase();
+  }
+
+  /// An offline seller, with the name normalized.
+  pub fn offline(name: &str) -> Self {
+    return Self::Offline(Rc::from(Self::normalize(name)));
+  }
+}
+
+impl Display for Seller {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      Seller::Online => write!(f, "Online"),
+      Seller::OnlineDay(d) => write!(f, "Online {}", d.format("%Y-%m-%d")),
+      Seller::Offline(name) => write!(f, "{}", name),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn same_seller_however_typed() {
+    assert_eq!(Seller::normalize("  Fulano   de  Tal "), "fulano de tal");
+    assert_eq!(
+      Seller::offline("FULANO\tde Tal"),
+      Seller::offline("fulano de tal")
+    );
+    assert_ne!(Seller::offline("fulano"), Seller::offline("fulana"));
+    assert_eq!(Seller::offline(" Zé ").to_string(), "zé");
+  }
+
+  #[test]
+  fn fees_come_and_go() {
+    let online = SaleKind::Online((1100, 1000));
+    assert_eq!(online.apply_fee(6500), 7150);
+    assert_eq!(online.undo_fee(7150), 6500);
+    let cash = SaleKind::Offline(Some((105, 100)));
+    assert_eq!(cash.apply_fee(6500), 6825);
+    assert_eq!(cash.undo_fee(6825), 6500);
+    let free = SaleKind::Offline(None);
+    assert_eq!(free.fee(), None);
+    assert_eq!(free.apply_fee(6500), 6500);
+    assert_eq!(free.undo_fee(6500), 6500);
+  }
+}
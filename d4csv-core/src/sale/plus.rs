@@ -0,0 +1,692 @@
+//! Structs for storing sale data and extra context and derived info.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+use std::rc::Rc;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::budget::Budget;
+use crate::context::SalesContext;
+use crate::i18n::tr;
+use crate::sale::Sale;
+use crate::sale::ambiguity::AmbiguitySolverFn;
+use crate::sale::columns::HeaderInfo;
+use crate::sale::filter::SalesFilter;
+use crate::sale::kind::Seller;
+use crate::sale::revenue::Revenue;
+use crate::report::table::{ColumnKind, RowStatus};
+use crate::ticket::batch::Batch;
+use crate::ticket::batchnum::BatchNum;
+use crate::sale::price_deriving::{
+  PricingCandidate, PricingMatch, PricingCandidateCache
+};
+
+/// Sale plus inferred data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SalePlus {
+  /// The sale itself.
+  pub sale: Sale,
+  /// The price candidate inferred from price alone. Can be calculated
+  /// immediately.
+  pub pricecand: PricingCandidate,
+  /// The price match resolved from adjacencies and extra info.
+  pub pricematch: Option<PricingMatch>,
+  /// Why a solver resolved it like that, if a solver did.
+  pub resolution_trace: Option<String>,
+  /// How many candidates pricing came up with, before any solver shrank
+  /// them.
+  #[serde(default)]
+  pub initial_count: usize
+}
+
+impl SalePlus {
+  /// Resolve this sale's pricing inference, and remember why.
+  pub fn resolve(&mut self, pm: PricingMatch, why: String) {
+    self.pricematch = Some(pm);
+    self.resolution_trace = Some(why);
+  }
+
+  /// Generate a line for the "better CSV", with the date in the context's
+  /// time zone.
+  pub fn gen_better_csv_line(&self, ctx: &SalesContext) -> Vec<String> {
+    let mut v: Vec<String> = Vec::new();
+    let p = |vr: &mut Vec<String>, s: &dyn Display| {
+      vr.push(s.to_string());
+    };
+    let ps = |vr: &mut Vec<String>, s: Option<&str>| {
+      vr.push(s.unwrap_or("").to_owned());
+    };
+    // add fields one by one
+    p(&mut v, &self.sale.local_when(ctx));
+    ps(&mut v, self.sale.buyer_email.as_deref());
+    ps(&mut v, self.sale.buyer_username.as_deref());
+    p(&mut v, &(self.sale.value as f64 / 100.0));
+    p(&mut v, &self.sale.sale_kind);
+    ps(&mut v, self.sale.seller_name.as_deref());
+    ps(&mut v, self.sale.seller_id.as_deref());
+    ps(&mut v, self.sale.seller_email.as_deref());
+    p(&mut v, &self.sale.token);
+    p(&mut v, &self.sale.sale_id);
+    ps(&mut v, self.sale.card_name.as_deref());
+    ps(&mut v, self.sale.card_pfx.as_deref());
+    ps(&mut v, self.sale.card_sfx.as_deref());
+    // now the extra fields!
+    // is this resolved? or at least narrowed down?
+    p(&mut v, &{
+      if self.pricematch.is_some() {
+        tr("yes")
+      } else if self.is_partial() {
+        tr("partial")
+      } else {
+        tr("no")
+      }
+    });
+    // if resolved, tell ya the batches, and if not, what they may be
+    ps(&mut v, self.pricematch.as_ref().map(|pm| pm.to_string()).as_deref());
+    p(&mut v, &self.alternatives());
+    // and what each ticket cost, and the fee, for reconciling
+    ps(&mut v, self.ticket_price().map(cents_csv).as_deref());
+    ps(&mut v, self.fee_amount().map(cents_csv).as_deref());
+    ps(&mut v, self.residual().map(|r| r.to_string()).as_deref());
+    return v;
+  }
+
+  /// How many cents the value is off from the match's price with the fee
+  /// on, if resolved. Undoing the fee rounds down, so this can be a few
+  /// cents over zero and the sale still match; a pile of non-zero ones
+  /// means the fee is configured wrong. With a subtotal from the CSV, that
+  /// is what gets compared to the price, since no fee was undone.
+  pub fn residual(&self) -> Option<isize> {
+    let price = self.pricematch.as_ref()?.price();
+    let (paid, expected) = match self.sale.subtotal {
+      Some(st) => (st, price),
+      None => (self.sale.value, self.sale.sale_kind.apply_fee(price)),
+    };
+    return Some((paid as isize) - (expected as isize));
+  }
+
+  /// What each ticket effectively cost in cents (value over tickets), if
+  /// resolved.
+  pub fn ticket_price(&self) -> Option<usize> {
+    let tickets = self.pricematch.as_ref()?.tickets();
+    if tickets == 0 { return None; }
+    return Some(((self.sale.value as f64) / (tickets as f64)).round() as usize);
+  }
+
+  /// The fee paid in cents (value minus real price), if resolved. That's
+  /// the online fee, or the offline one where there is one; sales with no
+  /// fee paid 0.
+  pub fn fee_amount(&self) -> Option<usize> {
+    self.pricematch.as_ref()?;
+    return match self.sale.sale_kind.fee() {
+      Some(_) => Some(self.sale.value.saturating_sub(self.sale.real_price())),
+      None => Some(0),
+    };
+  }
+
+  /// The batches, if resolved. Otherwise, all the candidates.
+  pub fn decoding(&self) -> String {
+    if let Some(pm) = &self.pricematch {
+      return pm.to_string();
+    }
+    return match &self.pricecand {
+      PricingCandidate::Precise(pm) => pm.to_string(),
+      _ => self.alternatives(),
+    };
+  }
+
+  /// What an unresolved sale may still be: the candidates left, sorted, or
+  /// the tragedy marker if there never were any. Blank once resolved.
+  pub fn alternatives(&self) -> String {
+    if self.pricematch.is_some() {
+      return String::new();
+    }
+    return match &self.pricecand {
+      PricingCandidate::Ambiguous(hs) => {
+        hs.iter()
+          .map(|g| g.to_string())
+          .sorted()
+          .join(tr("or"))
+      },
+      PricingCandidate::NoMatch if self.is_free() => {
+        tr("free_sale").to_owned()
+      },
+      PricingCandidate::NoMatch => tr("tragedy").to_owned(),
+      PricingCandidate::Precise(_) => String::new(),
+    };
+  }
+
+  /// Whether this sale was worth nothing: a courtesy or a test.
+  pub fn is_free(&self) -> bool {
+    return self.sale.value == 0;
+  }
+
+  /// How many tickets this sale was, if known. Zero-value sales are one
+  /// ticket or none, as the context says.
+  pub fn tickets(&self, ctx: &SalesContext) -> Option<usize> {
+    if let Some(pm) = &self.pricematch {
+      return Some(pm.tickets());
+    }
+    return match self.is_free() {
+      true => Some(ctx.free_tickets as usize),
+      false => None
+    };
+  }
+
+  /// Whether a solver narrowed the candidates down without getting to one.
+  pub fn is_partial(&self) -> bool {
+    return self.pricematch.is_none()
+      && matches!(self.pricecand, PricingCandidate::Ambiguous(_))
+      && self.pricecand.count() < self.initial_count;
+  }
+
+  /// Translation keys for the better CSV header.
+  pub(crate) const BETTER_CSV_HEADER: [&'static str; 19] = [
+    "h_date",
+    "h_buyer_email",
+    "h_buyer_name",
+    "h_value",
+    "h_status",
+    "h_seller_name",
+    "h_seller_id",
+    "h_seller_email",
+    "h_token",
+    "h_id",
+    "h_card_name",
+    "h_card_pfx",
+    "h_card_sfx",
+    "h_resolved",
+    "h_decoding",
+    "h_alternatives",
+    "h_ticket_price",
+    "h_fee_amount",
+    "h_residual"
+  ];
+
+  /// Returns the header for the better CSV, in the language in use.
+  pub fn better_csv_header() -> Vec<String> {
+    return Self::BETTER_CSV_HEADER.iter()
+      .map(|k| tr(k).to_owned())
+      .collect();
+  }
+
+  /// How each better CSV column sorts.
+  pub fn better_csv_kinds() -> Vec<ColumnKind> {
+    return Self::BETTER_CSV_HEADER.iter()
+      .map(|k| match *k {
+        "h_value" | "h_ticket_price" | "h_fee_amount" | "h_residual" => {
+          ColumnKind::Number
+        },
+        _ => ColumnKind::Text,
+      }).collect();
+  }
+
+  /// What a search looks into: token, sale ID, buyer e-mail and username,
+  /// lowercased and one per line.
+  pub fn search_key(&self) -> String {
+    return [
+      Some(&self.sale.token),
+      Some(&self.sale.sale_id),
+      self.sale.buyer_email.as_ref(),
+      self.sale.buyer_username.as_ref()
+    ].iter()
+      .flatten()
+      .map(|s| s.to_lowercase())
+      .join("\n");
+  }
+
+  /// How this sale's pricing ended up.
+  pub fn status(&self) -> RowStatus {
+    if self.pricematch.is_some() {
+      return RowStatus::Resolved;
+    }
+    if self.is_free() {
+      return RowStatus::Free;
+    }
+    return match self.pricecand {
+      PricingCandidate::NoMatch => RowStatus::Villain,
+      _ => RowStatus::Ambiguous,
+    };
+  }
+
+  // If resolved, returns the batches contained in the candidate.
+  //pub(crate)
+}
+
+impl AsRef<Sale> for SalePlus {
+  fn as_ref(&self) -> &Sale {
+    return &self.sale;
+  }
+}
+
+impl From<(Sale, PricingCandidate)> for SalePlus {
+  fn from((s, cnd): (Sale, PricingCandidate)) -> Self {
+    return Self {
+      initial_count: cnd.count(),
+      sale: s,
+      pricecand: cnd.clone(),
+      pricematch: match cnd {
+        PricingCandidate::Precise(pm) => Some(pm),
+        PricingCandidate::Ambiguous(_) => None,
+        PricingCandidate::NoMatch => None,
+      },
+      resolution_trace: None
+    };
+  }
+}
+
+/// Below this fraction of resolved sales, the context is probably wrong.
+pub static SUSPICIOUS_OK_RATIO: f64 = 0.1;
+
+/// Stores loads of sales, and resolves pricing ambiguities.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SalesPlus {
+  /// A vec full of SalePlus.
+  pub sales: Vec<SalePlus>,
+  /// The context, shared with the pricing cache and any filtered copies.
+  pub context: Rc<SalesContext>,
+  /// How many CSV rows went in, including the ones that failed to parse.
+  pub rows: usize,
+  /// The filter these sales went through, if any.
+  pub filter: Option<SalesFilter>,
+  /// Whether the CSV header looked like a sale.
+  pub header_is_sale: bool,
+  /// Which CSV headers didn't match a sale field.
+  #[serde(default)]
+  pub headers: HeaderInfo,
+  /// How many sales were dropped for repeating an earlier one, by the
+  /// context's dedup key.
+  #[serde(default)]
+  pub duplicates: usize,
+  /// Whether the run ran out of time, so these are partial results.
+  #[serde(default)]
+  pub interrupted: bool,
+  /// Whether the report counts money with or without the fees.
+  #[serde(default)]
+  pub revenue: Revenue
+}
+
+impl AsRef<Vec<SalePlus>> for SalesPlus {
+  fn as_ref(&self) -> &Vec<SalePlus> {
+    return &self.sales;
+  }
+}
+
+impl SalesPlus {
+  /// Convert a vector of sales into a SalesPlus, using a caching dude to save
+  /// time on pricing inference. The dude gets warmed up with every distinct
+  /// value first.
+  pub fn from_sales<T>(
+    iter: T, ctx: Rc<SalesContext>
+  ) -> Self where T: Iterator<Item = Sale> {
+    let sales: Vec<Sale> = iter.collect();
+    let mut sp = Self::new(Rc::clone(&ctx));
+    let mut dude = PricingCandidateCache::from(ctx);
+    dude.warm(sales.iter().map(|s| s.real_price()));
+    for sale in sales {
+      sp.push_sale(sale, &mut dude);
+    }
+    sp.rows = sp.sales.len();
+    log::info!("pricing cache: {}", dude.stats());
+    return sp;
+  }
+
+  /// An empty SalesPlus, to be filled with push_sale.
+  pub fn new(ctx: Rc<SalesContext>) -> Self {
+    return Self {
+      sales: Vec::new(),
+      context: ctx,
+      rows: 0,
+      filter: None,
+      header_is_sale: false,
+      headers: HeaderInfo::default(),
+      duplicates: 0,
+      interrupted: false,
+      revenue: Revenue::default()
+    };
+  }
+
+  /// A copy with only the sales that pass a filter. Resolutions are kept
+  /// as they are, so solve before filtering.
+  pub fn filtered(&self, f: &SalesFilter) -> Self {
+    return Self {
+      sales: self.sales.iter()
+        .filter(|s| f.matches(s, &self.context))
+        .cloned()
+        .collect(),
+      context: Rc::clone(&self.context),
+      rows: self.rows,
+      filter: Some(f.clone()),
+      header_is_sale: self.header_is_sale,
+      headers: self.headers.clone(),
+      duplicates: self.duplicates,
+      interrupted: self.interrupted,
+      revenue: self.revenue
+    };
+  }
+
+  /// Names of all sellers, for picking from.
+  pub fn seller_names(&self) -> BTreeSet<String> {
+    return self.sales.iter()
+      .filter_map(|s| s.sale.seller(&self.context))
+      .map(|slr| slr.to_string())
+      .collect();
+  }
+
+  /// Infers the pricing of a single sale and appends it. Sales must come in
+  /// sorted by date, and the cache must be made from the same context.
+  pub fn push_sale(
+    &mut self, sale: Sale, dude: &mut PricingCandidateCache
+  ) {
+    self.push_sale_within(sale, dude, &Budget::unlimited());
+  }
+
+  /// Same as push_sale, but leaves the sale out and returns false if the
+  /// budget runs out while pricing it.
+  pub fn push_sale_within(
+    &mut self,
+    sale: Sale,
+    dude: &mut PricingCandidateCache,
+    budget: &Budget
+  ) -> bool {
+    return match dude.from_price_within(sale.real_price(), budget) {
+      Some(pc) => {
+        self.sales.push(SalePlus::from((sale, pc)));
+        true
+      },
+      None => false
+    };
+  }
+
+  /// Returns an iterator over all sales with ambiguous pricing conclusions.
+  pub fn ambiguous(&self) -> impl Iterator<Item = &SalePlus> {
+    return self.sales.iter()
+      .filter(|sp| matches!(sp.pricecand, PricingCandidate::Ambiguous(_)));
+  }
+
+  /// Returns an iterator over all sales a solver narrowed down, but not to a
+  /// single candidate.
+  pub fn partially_resolved(&self) -> impl Iterator<Item = &SalePlus> {
+    return self.sales.iter()
+      .filter(|sp| sp.is_partial());
+  }
+
+  /// Returns an iterator over all sales with no pricing conclusions,
+  /// leaving out the zero-value ones.
+  pub fn villains(&self) -> impl Iterator<Item = &SalePlus> {
+    return self.sales.iter()
+      .filter(|sp| {
+        matches!(sp.pricecand, PricingCandidate::NoMatch) && !sp.is_free()
+      });
+  }
+
+  /// Returns an iterator over all zero-value sales.
+  pub fn frees(&self) -> impl Iterator<Item = &SalePlus> {
+    return self.sales.iter()
+      .filter(|sp| sp.is_free());
+  }
+
+  /// Returns an iterator over all sales with a precise pricing conclusion.
+  pub fn oks(&self) -> impl Iterator<Item = &SalePlus> {
+    return self.sales.iter()
+      .filter(|s| s.pricematch.is_some());
+  }
+
+  /// Whether so few sales got resolved that the context is the likely
+  /// culprit, rather than the sales themselves.
+  pub fn looks_misconfigured(&self) -> bool {
+    if self.sales.is_empty() { return false; }
+    let ratio = self.oks().count() as f64 / self.sales.len() as f64;
+    return ratio < SUSPICIOUS_OK_RATIO;
+  }
+
+  /// Generates the "better" CSV dude.
+  pub fn gen_csv(&self) -> Vec<Vec<String>> {
+    return self.sales.iter()
+      .map(|s| s.gen_better_csv_line(&self.context))
+      .collect();
+  }
+
+  /// The status of each line in the "better" CSV.
+  pub fn gen_status(&self) -> Vec<RowStatus> {
+    return self.sales.iter()
+      .map(|s| s.status())
+      .collect();
+  }
+
+  /// Why each line in the "better" CSV got resolved, if a solver did it.
+  pub fn gen_traces(&self) -> Vec<Option<String>> {
+    return self.sales.iter()
+      .map(|s| s.resolution_trace.clone())
+      .collect();
+  }
+
+  /// Which lines in the "better" CSV have no way to reach the buyer.
+  pub fn gen_no_contact(&self) -> Vec<usize> {
+    return self.sales.iter()
+      .positions(|s| !s.sale.has_contact())
+      .collect();
+  }
+
+  /// Which lines in the "better" CSV are worth nothing.
+  pub fn gen_free(&self) -> Vec<usize> {
+    return self.sales.iter()
+      .positions(|s| s.is_free())
+      .collect();
+  }
+
+  /// Which lines in the "better" CSV a solver narrowed down without
+  /// resolving.
+  pub fn gen_narrowed(&self) -> Vec<usize> {
+    return self.sales.iter()
+      .positions(|s| s.is_partial())
+      .collect();
+  }
+
+  /// The search key of each line in the "better" CSV.
+  pub fn gen_search_keys(&self) -> Vec<String> {
+    return self.sales.iter()
+      .map(|s| s.search_key())
+      .collect();
+  }
+
+  /// Generates the per-seller summary CSV: name, sales, tickets, revenue,
+  /// tickets per batch and commission. Tickets, batches and commissions only
+  /// count resolved sales; the revenue counts all of them, gross or net as
+  /// picked. Only offline sellers get a commission.
+  pub fn gen_seller_csv(&self) -> Vec<Vec<String>> {
+    // sales, tickets, revenue (cents), resolved revenue (cents), tickets per
+    // batch
+    type Acc = (usize, usize, usize, usize, BTreeMap<BatchNum, (Batch, usize)>);
+    let mut hm: HashMap<Seller, Acc> = HashMap::new();
+    for s in &self.sales {
+      let seller = match s.sale.seller(&self.context) {
+        Some(slr) => slr,
+        None => continue,
+      };
+      let acc = hm.entry(seller).or_insert((0, 0, 0, 0, BTreeMap::new()));
+      acc.0 += 1;
+      acc.2 += self.revenue.of(s);
+      acc.1 += s.tickets(&self.context).unwrap_or(0);
+      if let Some(pm) = &s.pricematch {
+        acc.3 += self.revenue.of(s);
+        for ba in pm.amounts() {
+          acc.4.entry(ba.0.num).or_insert((ba.0, 0)).1 += ba.1;
+        }
+      }
+    }
+    let mut rows: Vec<(Seller, Acc)> = hm.into_iter().collect();
+    rows.sort_by_key(|(slr, _)| slr.to_string());
+    return rows.into_iter()
+      .map(|(slr, (sales, tickets, revenue, resolved, batches))| vec![
+        slr.to_string(),
+        sales.to_string(),
+        tickets.to_string(),
+        (revenue as f64 / 100.0).to_string(),
+        batches.into_iter()
+          .map(|(_, (b, n))| format!("{}x {}", n, b))
+          .join("; "),
+        match slr {
+          Seller::Offline(_) => {
+            let c = self.context.commission.of(tickets, resolved);
+            (c as f64 / 100.0).to_string()
+          },
+          _ => String::new()
+        }
+      ]).collect();
+  }
+
+  /// Returns the header for the seller CSV.
+  pub fn seller_csv_header() -> Vec<String> {
+    return [
+      "h_seller",
+      "h_sales",
+      "h_tickets",
+      "h_revenue",
+      "h_batches",
+      "h_commission"
+    ].iter().map(|k| tr(k).to_owned()).collect();
+  }
+
+  /// Applies the selected ambiguity solver once.
+  pub fn run_solver(&mut self) -> usize {
+    let f: AmbiguitySolverFn = self.context.solver.into();
+    return f(self);
+  }
+
+  /// Applies the selected ambiguity solver until done.
+  /// Returns (passes, solveds).
+  pub fn solve_ambiguities(&mut self) -> (usize, usize) {
+    let mut passes: usize = 0;
+    let mut solves: usize = 0;
+    loop {
+      let thispass = self.run_solver();
+      passes += 1;
+      solves += thispass;
+      if thispass == 0 { break; }
+    }
+    return (passes, solves);
+  }
+}
+
+/// Writes cents as reais for the CSVs, with a dot and always two decimals,
+/// like "71.50".
+fn cents_csv(cents: usize) -> String {
+  return format!("{}.{:02}", cents / 100, cents % 100);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::{NaiveDate, TimeZone, Utc};
+  use crate::testing::{priced, sale, sales_plus};
+
+  #[test]
+  fn fee_amount_counts_offline_fees() {
+    let ctx = SalesContext {
+      offline_fee: Some((1050, 1000)),
+      ..SalesContext::default()
+    };
+    let sales = vec![
+      priced(0, 6500, None, &ctx),
+      priced(1, 6500, Some("fulano"), &ctx)
+    ];
+    let sp = sales_plus(ctx, sales);
+    assert_eq!(sp.sales[0].fee_amount(), Some(650));
+    assert_eq!(sp.sales[1].fee_amount(), Some(325));
+  }
+
+  #[test]
+  fn offline_fee_is_undone_before_decoding() {
+    let ctx = SalesContext {
+      offline_fee: Some((1050, 1000)),
+      ..SalesContext::default()
+    };
+    // one 1st batch ticket, plus 5% for the cash
+    let sales = vec![sale(0, 6825, Some("fulano"), &ctx)];
+    let sp = sales_plus(ctx, sales);
+    let pm = sp.sales[0].pricematch.as_ref().unwrap();
+    assert_eq!((pm.price(), pm.tickets()), (6500, 1));
+    assert_eq!(sp.sales[0].residual(), Some(0));
+    // without the fee, that's no price at all
+    let free = SalesContext::default();
+    let sales = vec![sale(0, 6825, Some("fulano"), &free)];
+    let sp = sales_plus(free, sales);
+    assert_eq!(sp.sales[0].pricecand, PricingCandidate::NoMatch);
+  }
+
+  #[test]
+  fn fee_amount_is_zero_without_a_fee() {
+    let ctx = SalesContext::default();
+    let sales = vec![priced(0, 6500, Some("fulano"), &ctx)];
+    let sp = sales_plus(ctx, sales);
+    assert_eq!(sp.sales[0].fee_amount(), Some(0));
+    assert_eq!(sp.sales[0].residual(), Some(0));
+  }
+
+  #[test]
+  fn residual_goes_by_the_subtotal() {
+    let ctx = SalesContext::default();
+    // the gateway charged a bit more than the context says, but the subtotal
+    // is spot on
+    let s = Sale {
+      value: 7200,
+      subtotal: Some(6500),
+      ..priced(0, 6500, None, &ctx)
+    };
+    let sp = sales_plus(ctx, vec![s]);
+    assert_eq!(sp.sales[0].residual(), Some(0));
+    assert_eq!(sp.sales[0].fee_amount(), Some(700));
+  }
+
+  #[test]
+  fn late_nights_count_for_the_local_day() {
+    let ctx = SalesContext {
+      online_per_day: true,
+      ..SalesContext::default()
+    };
+    // 23:30 on the 19th, in São Paulo
+    let s = Sale {
+      when: Utc.with_ymd_and_hms(2023, 5, 20, 2, 30, 0).unwrap(),
+      ..priced(0, 6500, None, &ctx)
+    };
+    let day = |d: u32| NaiveDate::from_ymd_opt(2023, 5, d).unwrap();
+    assert_eq!(s.local_day(&ctx), day(19));
+    let sp = sales_plus(ctx.clone(), vec![s.clone()]);
+    assert_eq!(sp.gen_seller_csv()[0][0], "Online 2023-05-19");
+    let on = |d: u32| SalesFilter {
+      from: Some(day(d)),
+      until: Some(day(d)),
+      ..SalesFilter::default()
+    };
+    assert_eq!(sp.filtered(&on(19)).sales.len(), 1);
+    assert_eq!(sp.filtered(&on(20)).sales.len(), 0);
+    // in UTC, it's the next day already
+    let utc = SalesContext { utc_offset: 0, ..ctx };
+    assert_eq!(s.local_day(&utc), day(20));
+    let sp = sales_plus(utc, vec![s]);
+    assert_eq!(sp.gen_seller_csv()[0][0], "Online 2023-05-20");
+  }
+
+  #[test]
+  fn misconfigured_under_a_tenth_resolved() {
+    let ctx = SalesContext::default();
+    let healthy: Vec<Sale> = (0..10)
+      .map(|m| priced(m, 6500, None, &ctx))
+      .collect();
+    assert!(!sales_plus(ctx.clone(), healthy).looks_misconfigured());
+    // R$ 1,00 is nothing in this context
+    let villains = |n: i64| (0..n).map(|m| sale(m, 100, None, &ctx));
+    let all_bad: Vec<Sale> = villains(10).collect();
+    assert!(sales_plus(ctx.clone(), all_bad).looks_misconfigured());
+    let one_in_ten: Vec<Sale> = villains(9)
+      .chain([priced(9, 6500, None, &ctx)])
+      .collect();
+    assert!(!sales_plus(ctx.clone(), one_in_ten).looks_misconfigured());
+    let one_in_eleven: Vec<Sale> = villains(10)
+      .chain([priced(10, 6500, None, &ctx)])
+      .collect();
+    assert!(sales_plus(ctx.clone(), one_in_eleven).looks_misconfigured());
+    assert!(!sales_plus(ctx, vec![]).looks_misconfigured());
+  }
+}
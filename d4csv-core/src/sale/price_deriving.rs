@@ -0,0 +1,1141 @@
+//! Here we implement the first step of deriving ticket amounts from batch
+//! prices and sale values.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashSet, HashMap};
+use std::fmt::Display;
+use std::mem::size_of;
+use std::rc::Rc;
+use std::ops::Range;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use crate::budget::{Budget, CHECK_EVERY};
+use crate::context::SalesContext;
+use crate::format;
+use crate::ticket::batch::{Batch, bp2iter};
+use crate::ticket::batchnum::BatchNum;
+
+#[derive(
+  Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub struct BatchAmount(pub Batch, pub usize);
+
+impl From<(Batch, usize)> for BatchAmount {
+  fn from((b, a): (Batch, usize)) -> Self {
+    return Self(b, a);
+  }
+}
+
+impl Display for BatchAmount {
+  /// "2x 2º lote (R$ 65,00)". With "{:#}", the subtotal goes along, as in
+  /// "2x 2º lote (R$ 65,00) = R$ 130,00".
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if f.alternate() {
+      let sub = ba_price(self).map(format::money).unwrap_or("?".to_owned());
+      return write!(f, "{}x {} = {}", self.1, self.0, sub);
+    }
+    return write!(f, "{}x {}", self.1, self.0);
+  }
+}
+
+/// Total price of a BatchAmount, or None if it doesn't fit in a usize (which
+/// is only 32 bits on wasm).
+fn ba_price(ba: &BatchAmount) -> Option<usize> {
+  return ba.0.price.checked_mul(ba.1);
+}
+
+/// Iterator for BatchAmounts within some amount range.
+fn ba_iter(
+  batch: Batch,
+  range: Range<usize>
+) -> impl Iterator<Item = BatchAmount> + Clone {
+  return range.into_iter().map(move |n| (batch, n).into());
+}
+
+/// How many promo + turn-of-batch combinations all_priced is willing to try
+/// for a single price. Those grow fast, and they're rare anyway.
+pub static PROMO_TURN_CAP: usize = 200_000;
+
+/// How many multi-batch combinations all_priced is willing to try for a
+/// single price and span.
+pub static MULTI_BATCH_CAP: usize = 200_000;
+
+/// Most tickets a purchase is taken to have when looking for prices that are
+/// ambiguous no matter what. Past this, nearly every price collides.
+pub static COLLISION_MAX_TICKETS: usize = 4;
+
+/// Prices (in cents, without the fee) that two or more matches add up to,
+/// with those matches.
+pub type Collisions = BTreeMap<usize, Vec<PricingMatch>>;
+
+thread_local! {
+  /// The last context collisions were looked for in, and what was found.
+  static COLLISIONS: RefCell<Option<(SalesContext, Rc<Collisions>)>> =
+    const { RefCell::new(None) };
+}
+
+/// A match for a price and some kind of sale.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PricingMatch {
+  /// A multiple of a batch.
+  Multiple(BatchAmount),
+  /// Some promos and the following batch.
+  PromoCombo(BatchAmount, BatchAmount),
+  /// Turn-of-batch purchase. Those are hairy.
+  TurnOfBatch(BatchAmount, BatchAmount),
+  /// Some promos, plus a turn from the 1st batch to the 2nd. Even hairier.
+  PromoPlusTurn(BatchAmount, BatchAmount, BatchAmount),
+  /// A big purchase spanning three or more contiguous numbered batches, in
+  /// order. Only generated when the context allows spans that long.
+  MultiBatch(Vec<BatchAmount>)
+}
+
+impl Display for PricingMatch {
+  /// The amounts, in order, as in "1x lote promocional (R$ 50,00) + 1x 1º
+  /// lote (R$ 55,00)". That's what goes in the CSV. With "{:#}", each amount
+  /// comes with its subtotal and the whole thing with its total, for when
+  /// someone has to check the math.
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let amounts = self.amounts();
+    if !f.alternate() {
+      return write!(f, "{}", amounts.iter().join(" + "));
+    }
+    let total = self.checked_price()
+      .map(format::money)
+      .unwrap_or("?".to_owned());
+    if let [ba] = amounts.as_slice() {
+      return write!(f, "{:#}", ba);
+    }
+    let parts = amounts.iter().map(|ba| format!("[{:#}]", ba)).join(" + ");
+    return write!(f, "{} = {}", parts, total);
+  }
+}
+
+impl TryFrom<&str> for PricingMatch {
+  type Error = ();
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    let part = |p: &str| -> Option<BatchAmount> {
+      let (n, rest) = p.split_once('x')?;
+      let (num, price) = rest.split_once('@')?;
+      let batch = Batch {
+        num: BatchNum::try_from(num.to_owned()).ok()?,
+        price: price.parse().ok()?
+      };
+      return Some(BatchAmount(batch, n.parse().ok()?));
+    };
+    let bas: Vec<BatchAmount> = s.split('+')
+      .map(part)
+      .collect::<Option<Vec<BatchAmount>>>()
+      .ok_or(())?;
+    let promo = |ba: &BatchAmount| ba.0.num == BatchNum::Promo;
+    return match bas.as_slice() {
+      [ba] => Ok(Self::Multiple(*ba)),
+      [p, ba] if promo(p) && !promo(ba) => Ok(Self::PromoCombo(*p, *ba)),
+      [ba1, ba2] if !promo(ba1) && !promo(ba2) => {
+        Ok(Self::TurnOfBatch(*ba1, *ba2))
+      },
+      [p, ba1, ba2] if promo(p) && !promo(ba1) && !promo(ba2) => {
+        Ok(Self::PromoPlusTurn(*p, *ba1, *ba2))
+      },
+      bas if bas.len() > 2 && !bas.iter().any(promo) => {
+        Ok(Self::MultiBatch(bas.to_vec()))
+      },
+      _ => Err(()),
+    };
+  }
+}
+
+impl PricingMatch {
+  /// Returns the sum price of this match, or None if it overflows.
+  pub fn checked_price(&self) -> Option<usize> {
+    return self.amounts().iter()
+      .try_fold(0usize, |acc, ba| acc.checked_add(ba_price(ba)?));
+  }
+
+  /// Returns the sum price of this match. Matches from all_priced always fit,
+  /// since they add up to a real price.
+  pub fn price(&self) -> usize {
+    return self.checked_price().unwrap_or(usize::MAX);
+  }
+
+  /// Returns the number of tickets in this match.
+  pub fn tickets(&self) -> usize {
+    return match self {
+      PricingMatch::Multiple(ba) => ba.1,
+      PricingMatch::PromoCombo(pba, ba) => pba.1 + ba.1,
+      PricingMatch::TurnOfBatch(ba1, ba2) => ba1.1 + ba2.1,
+      PricingMatch::PromoPlusTurn(pba, ba1, ba2) => pba.1 + ba1.1 + ba2.1,
+      PricingMatch::MultiBatch(bas) => bas.iter().map(|ba| ba.1).sum(),
+    }
+  }
+
+  /// The amounts of each batch sold in this operation.
+  pub fn amounts(&self) -> Vec<BatchAmount> {
+    return match self {
+      PricingMatch::Multiple(ba) => vec![*ba],
+      PricingMatch::PromoCombo(pba, ba) => vec![*pba, *ba],
+      PricingMatch::TurnOfBatch(ba1, ba2) => vec![*ba1, *ba2],
+      PricingMatch::PromoPlusTurn(pba, ba1, ba2) => vec![*pba, *ba1, *ba2],
+      PricingMatch::MultiBatch(bas) => bas.clone(),
+    };
+  }
+
+  /// How many promo tickets are in this match.
+  pub fn promos(&self) -> usize {
+    return self.amounts().iter()
+      .filter(|ba| ba.0.num == BatchNum::Promo)
+      .map(|ba| ba.1)
+      .sum();
+  }
+
+  /// Whether this match keeps to the context's promo limit, which is per
+  /// sale, whatever else was bought along.
+  pub fn within_promo_limit(&self, ctx: &SalesContext) -> bool {
+    return ctx.promo_limit.map(|l| self.promos() <= l).unwrap_or(true);
+  }
+
+  /// The batch after this was sold.
+  pub fn batch_after(&self) -> Batch {
+    return match self {
+      PricingMatch::Multiple(ba) => ba.0,
+      PricingMatch::PromoCombo(_, ba) => ba.0,
+      PricingMatch::TurnOfBatch(_, ba) => ba.0,
+      PricingMatch::PromoPlusTurn(_, _, ba) => ba.0,
+      PricingMatch::MultiBatch(bas) => bas.last().unwrap().0,
+    };
+  }
+
+  /// The batch before this was sold.
+  pub fn batch_before(&self) -> Batch {
+    return match self {
+      PricingMatch::Multiple(ba) => ba.0,
+      PricingMatch::PromoCombo(pba, _) => pba.0,
+      PricingMatch::TurnOfBatch(ba, _) => ba.0,
+      PricingMatch::PromoPlusTurn(pba, _, _) => pba.0,
+      PricingMatch::MultiBatch(bas) => bas.first().unwrap().0,
+    };
+  }
+
+  /// All batches sold in this operation.
+  pub fn batches(&self) -> HashSet<Batch> {
+    return match self {
+      PricingMatch::Multiple(ba) => vec![ba.0],
+      PricingMatch::PromoCombo(pba, ba) => vec![pba.0, ba.0],
+      PricingMatch::TurnOfBatch(ba1, ba2) => vec![ba1.0, ba2.0],
+      PricingMatch::PromoPlusTurn(pba, ba1, ba2) => {
+        vec![pba.0, ba1.0, ba2.0]
+      },
+      PricingMatch::MultiBatch(bas) => bas.iter().map(|ba| ba.0).collect(),
+    }.into_iter().collect();
+  }
+  
+  /// A short code for this match, like "1xpromo@5500+1x1@6500" (amount,
+  /// batch and price in cents, for each part), for links. The shape tells
+  /// the kind of match, so TryFrom reads it back as the same.
+  pub fn code(&self) -> String {
+    return self.amounts().iter()
+      .map(|ba| format!("{}x{}@{}", ba.1, String::from(ba.0.num), ba.0.price))
+      .join("+");
+  }
+
+  /// Returns all pricing matches for a certain price in cents.
+  pub fn all_priced(price: usize, ctx: &SalesContext) -> Vec<Self> {
+    return Self::all_priced_within(price, ctx, &Budget::unlimited())
+      .unwrap_or_default();
+  }
+
+  /// Same as all_priced, but gives up with None once the budget runs out.
+  pub fn all_priced_within(
+    price: usize,
+    ctx: &SalesContext,
+    budget: &Budget
+  ) -> Option<Vec<Self>> {
+    let mut v: Vec<Self> = Vec::new();
+    let mut tick: usize = 0;
+    let mut out_of_time = || {
+      tick += 1;
+      return tick.is_multiple_of(CHECK_EVERY) && budget.exceeded();
+    };
+    // min price
+    let mp: usize = match ctx.batches.values().copied().min() {
+      Some(k) => k,
+      // no minimum, return nothin'
+      None => return Some(v),
+    };
+    // worst-case amount and range. a free batch has no worst case, so
+    // there's no point in trying
+    let w = match price.checked_div(mp).and_then(|a| a.checked_add(5)) {
+      Some(w) => w,
+      None => return Some(v),
+    };
+    let wr: Range<usize> = Range { start: 1, end: w };
+    // this returns an iterator with all batches' ranges
+    let allba: Vec<BatchAmount> = bp2iter(&ctx.batches)
+      .flat_map(|b| ba_iter(b, wr.clone()))
+      .collect();
+    // first, all multiple matches. promos alone count against the limit
+    // too
+    allba.iter()
+      .filter_map(|ba| {
+        if ba_price(ba) == Some(price) {
+          return Some(Self::Multiple(*ba));
+        } else {
+          return None;
+        }
+      })
+      .filter(|pm| pm.within_promo_limit(ctx))
+      .for_each(|pm| v.push(pm));
+    // next, all promo combos (if there's a promo batch at all -- it's marked
+    // explicitly, so it doesn't have to be the cheapest one -- and combos
+    // weren't turned off)
+    let combos = ctx.has_promo && ctx.enable_promo_combo;
+    let opt_promo: Option<Batch> = match combos {
+      true => ctx.batches.get(&BatchNum::Promo)
+        .map(|p| Batch { num: BatchNum::Promo, price: *p }),
+      false => None
+    };
+    // up to the limit, inclusive: a limit of 1 still allows one promo
+    let pr: Range<usize> = Range {
+      start: 1,
+      end: ctx.promo_limit.map(|l| l.saturating_add(1)).unwrap_or(w)
+    };
+    // all non-promo
+    let bi = || allba.iter()
+      .filter(|ba| ba.0.num > BatchNum::Promo);
+    if let Some(promo) = opt_promo {
+      // all promo amounts
+      let pi = ba_iter(promo, pr.clone());
+      // all combinations
+      for (pba, ba) in pi.cartesian_product(bi()) {
+        if out_of_time() { return None; }
+        if ba.0.num.inum() != 1 { continue; }
+        let cand = Self::PromoCombo(pba, *ba);
+        // the range above already stops at the limit, but the limit is what
+        // matters, not how the range was built
+        if !cand.within_promo_limit(ctx) { continue; }
+        if cand.checked_price() == Some(price) {
+          v.push(cand);
+        }
+      }
+    }
+    // finally, all non-promo adjacent combos. a promo -> 1st batch purchase
+    // is a PromoCombo (which respects the promo limit), never a TurnOfBatch,
+    // or the same purchase would show up twice as different matches
+    let turns = bp2iter(&ctx.batches)
+      .cartesian_product(bp2iter(&ctx.batches))
+      .filter_map(|(b1, b2)| {
+        if b1.num == BatchNum::Promo { return None; }
+        if (b2.num.inum() as isize) - (b1.num.inum() as isize) == 1 {
+          return Some(
+            ba_iter(b1, wr.clone())
+              .cartesian_product(ba_iter(b2, wr.clone()))
+          );
+        } else {
+          return None;
+        }
+      }).flatten();
+    for (ba1, ba2) in turns {
+      if out_of_time() { return None; }
+      let cand = Self::TurnOfBatch(ba1, ba2);
+      if cand.checked_price() == Some(price) {
+        v.push(cand);
+      }
+    }
+    // and promos plus a 1st -> 2nd batch turn, if it's not too many to try
+    let first = ctx.batches.get(&BatchNum::Numbered(1))
+      .map(|p| Batch { num: BatchNum::Numbered(1), price: *p });
+    let second = ctx.batches.get(&BatchNum::Numbered(2))
+      .map(|p| Batch { num: BatchNum::Numbered(2), price: *p });
+    if let (Some(promo), Some(b1), Some(b2)) = (opt_promo, first, second) {
+      let tries = pr.len().checked_mul(wr.len())
+        .and_then(|t| t.checked_mul(wr.len()));
+      if tries.map(|t| t <= PROMO_TURN_CAP).unwrap_or(false) {
+        let triples = ba_iter(promo, pr.clone())
+          .cartesian_product(ba_iter(b1, wr.clone()))
+          .cartesian_product(ba_iter(b2, wr.clone()));
+        for ((pba, ba1), ba2) in triples {
+          if out_of_time() { return None; }
+          let cand = Self::PromoPlusTurn(pba, ba1, ba2);
+          if !cand.within_promo_limit(ctx) { continue; }
+          if cand.checked_price() == Some(price) {
+            v.push(cand);
+          }
+        }
+      } else {
+        log::debug!("skipping promo + turn for {}: {:?} tries", price, tries);
+      }
+    }
+    // and, if allowed, runs of three or more numbered batches. all but the
+    // last batch's amounts are tried, and the last one has to make up the
+    // rest of the price exactly
+    let numbered: Vec<Batch> = bp2iter(&ctx.batches)
+      .filter(|b| b.num != BatchNum::Promo)
+      .collect();
+    for span in 3..=ctx.max_span {
+      for run in numbered.windows(span) {
+        let contiguous = run.windows(2)
+          .all(|p| p[1].num.inum() == p[0].num.inum() + 1);
+        if !contiguous { continue; }
+        let tries = (1..span).try_fold(1usize, |t, _| t.checked_mul(wr.len()));
+        if !tries.map(|t| t <= MULTI_BATCH_CAP).unwrap_or(false) {
+          log::debug!("skipping {}-spans for {}: {:?}", span, price, tries);
+          continue;
+        }
+        let (last, head) = run.split_last().unwrap();
+        let heads = head.iter()
+          .map(|b| ba_iter(*b, wr.clone()))
+          .multi_cartesian_product();
+        for bas in heads {
+          if out_of_time() { return None; }
+          let sum = bas.iter()
+            .try_fold(0usize, |acc, ba| acc.checked_add(ba_price(ba)?));
+          let rest = match sum {
+            Some(s) if s < price => price - s,
+            _ => continue,
+          };
+          if last.price == 0 || rest % last.price != 0 { continue; }
+          let mut bas = bas;
+          bas.push((*last, rest / last.price).into());
+          v.push(Self::MultiBatch(bas));
+        }
+      }
+    }
+    debug_assert!(
+      v.iter().all(|pm| pm.within_promo_limit(ctx)),
+      "a match for {} goes over the promo limit",
+      price
+    );
+    return Some(v);
+  }
+}
+
+/// Prices the context alone makes ambiguous: the ones that two or more
+/// matches of up to COLLISION_MAX_TICKETS tickets add up to, whatever the
+/// solvers do. Manual rules settle their prices, so those never collide.
+/// Only depends on the pricing, so the last answer is kept around for as
+/// long as the context prices the same.
+pub fn collision_prices(ctx: &SalesContext) -> Rc<Collisions> {
+  let cached = COLLISIONS.with(|c| {
+    return c.borrow().as_ref()
+      .filter(|(old, _)| old.prices_like(ctx))
+      .map(|(_, cs)| Rc::clone(cs));
+  });
+  if let Some(cs) = cached {
+    return cs;
+  }
+  let prices: Vec<usize> = ctx.batches.values().copied().collect();
+  let totals: BTreeSet<usize> = (1..=COLLISION_MAX_TICKETS)
+    .flat_map(|n| prices.iter().combinations_with_replacement(n))
+    .filter_map(|c| c.into_iter().try_fold(0usize, |t, p| t.checked_add(*p)))
+    .filter(|t| *t > 0)
+    .collect();
+  let mut cs = Collisions::new();
+  for total in totals {
+    let matches: Vec<PricingMatch> = match PricingCandidate::from_price(
+      total, ctx
+    ) {
+      PricingCandidate::Ambiguous(hs) => hs.into_iter()
+        .filter(|pm| pm.tickets() <= COLLISION_MAX_TICKETS)
+        .sorted_by_cached_key(|pm| (pm.tickets(), pm.to_string()))
+        .collect(),
+      _ => continue,
+    };
+    if matches.len() > 1 {
+      cs.insert(total, matches);
+    }
+  }
+  let cs = Rc::new(cs);
+  COLLISIONS.with(|c| {
+    *c.borrow_mut() = Some((ctx.clone(), Rc::clone(&cs)));
+  });
+  return cs;
+}
+
+/// All possible matches for a given price.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PricingCandidate {
+  /// Only one match, nice!
+  Precise(PricingMatch),
+  /// Kinda ambiguous!
+  Ambiguous(HashSet<PricingMatch>),
+  /// No matches? Goddamnit.
+  NoMatch
+}
+
+impl FromIterator<PricingMatch> for PricingCandidate {
+  fn from_iter<T: IntoIterator<Item = PricingMatch>>(iter: T) -> Self {
+    let mut hs: HashSet<PricingMatch> = iter.into_iter().collect();
+    return match hs.len() {
+      0 => Self::NoMatch,
+      1 => Self::Precise(hs.drain().nth(0).unwrap()),
+      _ => Self::Ambiguous(hs)
+    };
+  }
+}
+
+impl PricingCandidate {
+  /// All candidates for a price. A manual rule in the context wins outright.
+  pub fn from_price(price: usize, ctx: &SalesContext) -> Self {
+    return Self::from_price_within(price, ctx, &Budget::unlimited())
+      .unwrap_or(Self::NoMatch);
+  }
+
+  /// Same as from_price, but gives up with None once the budget runs out.
+  pub fn from_price_within(
+    price: usize,
+    ctx: &SalesContext,
+    budget: &Budget
+  ) -> Option<Self> {
+    if let Some(pm) = ctx.overrides.get(&price) {
+      return Some(Self::Precise(pm.clone()));
+    }
+    return PricingMatch::all_priced_within(price, ctx, budget)
+      .map(PricingCandidate::from_iter);
+  }
+}
+
+impl PricingCandidate {
+  /// How many matches are in here.
+  pub fn count(&self) -> usize {
+    return match self {
+      PricingCandidate::Precise(_) => 1,
+      PricingCandidate::Ambiguous(hs) => hs.len(),
+      PricingCandidate::NoMatch => 0,
+    };
+  }
+
+  /// Roughly how much memory this takes, in bytes. Ignores the hash set's
+  /// spare room, so it's a floor more than anything.
+  pub fn approx_bytes(&self) -> usize {
+    let pm_bytes = |pm: &PricingMatch| size_of::<PricingMatch>() + match pm {
+      PricingMatch::MultiBatch(bas) => bas.len() * size_of::<BatchAmount>(),
+      _ => 0
+    };
+    return size_of::<Self>() + match self {
+      PricingCandidate::Precise(pm) => pm_bytes(pm),
+      PricingCandidate::Ambiguous(hs) => hs.iter().map(pm_bytes).sum(),
+      PricingCandidate::NoMatch => 0,
+    };
+  }
+}
+
+/// How a cache is doing, for logging.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+  /// Distinct prices stored.
+  pub entries: usize,
+  /// Matches stored, over all prices.
+  pub candidates: usize,
+  /// Lookups that were already there.
+  pub hits: usize,
+  /// Lookups that had to be computed.
+  pub misses: usize,
+  /// Entries thrown out to stay under the cap.
+  pub evictions: usize,
+  /// Roughly how much memory the entries take.
+  pub approx_bytes: usize
+}
+
+impl Display for CacheStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(
+      f,
+      "{} prices, {} candidates, {} hits, {} misses, {} evicted, ~{} KiB",
+      self.entries,
+      self.candidates,
+      self.hits,
+      self.misses,
+      self.evictions,
+      self.approx_bytes / 1024
+    );
+  }
+}
+
+/// A caching pricing generator so we avoid re-computing all candidates for a
+/// given price more than once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PricingCandidateCache {
+  /// Inner storage of pricing candidates for a given price, each with the
+  /// tick it was last used at.
+  store: HashMap<usize, (PricingCandidate, usize)>,
+  /// The sales' context, shared with them.
+  ctx: Rc<SalesContext>,
+  /// Most prices to keep, if any. The least recently used go first.
+  #[serde(default)]
+  cap: Option<usize>,
+  /// Goes up on every lookup, to tell which entries are stale.
+  #[serde(default)]
+  tick: usize,
+  /// Hits, misses and evictions so far.
+  #[serde(default)]
+  counts: (usize, usize, usize)
+}
+
+impl From<Rc<SalesContext>> for PricingCandidateCache {
+  fn from(ctx: Rc<SalesContext>) -> Self {
+    return Self {
+      store: HashMap::new(),
+      ctx,
+      cap: None,
+      tick: 0,
+      counts: (0, 0, 0)
+    };
+  }
+}
+
+impl PricingCandidateCache {
+  /// Keeps at most this many prices around. Only matters for files where
+  /// nearly every sale has its own value, which is a bad sign anyway.
+  pub fn with_cap(mut self, cap: usize) -> Self {
+    self.cap = Some(cap.max(1));
+    return self;
+  }
+
+  /// Computes the pricing candidates if absent (manual rules first)
+  pub fn from_price(&mut self, price: usize) -> PricingCandidate {
+    return self.from_price_within(price, &Budget::unlimited())
+      .unwrap_or(PricingCandidate::NoMatch);
+  }
+
+  /// Same as from_price, but gives up with None once the budget runs out.
+  /// Nothing gets cached then, since the candidates may be incomplete.
+  pub fn from_price_within(
+    &mut self,
+    price: usize,
+    budget: &Budget
+  ) -> Option<PricingCandidate> {
+    self.tick += 1;
+    if let Some((pc, used)) = self.store.get_mut(&price) {
+      *used = self.tick;
+      self.counts.0 += 1;
+      return Some(pc.clone());
+    }
+    self.counts.1 += 1;
+    let pc = PricingCandidate::from_price_within(price, &self.ctx, budget)?;
+    if let Some(cap) = self.cap {
+      while self.store.len() >= cap {
+        let stalest = self.store.iter()
+          .min_by_key(|(_, (_, used))| *used)
+          .map(|(p, _)| *p);
+        match stalest {
+          Some(p) => self.store.remove(&p),
+          None => break,
+        };
+        self.counts.2 += 1;
+      }
+    }
+    self.store.insert(price, (pc.clone(), self.tick));
+    return Some(pc);
+  }
+
+  /// Computes the candidates for a bunch of prices up front, each distinct
+  /// one once, so the per-sale lookups afterwards are all hits.
+  pub fn warm<T>(&mut self, prices: T) where T: Iterator<Item = usize> {
+    self.warm_within(prices, &Budget::unlimited());
+  }
+
+  /// Same as warm, but stops once the budget runs out. Returns whether it
+  /// got through all of them.
+  pub fn warm_within<T>(
+    &mut self,
+    prices: T,
+    budget: &Budget
+  ) -> bool where T: Iterator<Item = usize> {
+    let distinct: BTreeSet<usize> = prices.collect();
+    for price in distinct {
+      if self.store.contains_key(&price) { continue; }
+      if self.from_price_within(price, budget).is_none() {
+        return false;
+      }
+    }
+    return true;
+  }
+
+  /// How big this cache got and how well it's doing.
+  pub fn stats(&self) -> CacheStats {
+    return CacheStats {
+      entries: self.store.len(),
+      candidates: self.store.values().map(|(pc, _)| pc.count()).sum(),
+      hits: self.counts.0,
+      misses: self.counts.1,
+      evictions: self.counts.2,
+      approx_bytes: self.store.values()
+        .map(|(pc, _)| size_of::<(usize, usize)>() + pc.approx_bytes())
+        .sum()
+    };
+  }
+
+  /// Whether this cache is good for a context, i.e. it prices things the
+  /// same way.
+  pub fn fits(&self, ctx: &SalesContext) -> bool {
+    return self.ctx.prices_like(ctx);
+  }
+
+  /// How many distinct prices have been computed so far.
+  pub fn len(&self) -> usize {
+    return self.store.len();
+  }
+
+  /// Whether nothing has been computed yet.
+  pub fn is_empty(&self) -> bool {
+    return self.store.is_empty();
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+  use crate::testing::{priced, sales_plus};
+  use crate::ticket::batch::iter2bp;
+
+  /// Contexts with R$ 20 to R$ 50 tickets and up to four numbered batches,
+  /// cheap enough to enumerate and small enough that none of the caps on
+  /// combinations kick in for the matches below.
+  fn contexts() -> impl Strategy<Value = SalesContext> {
+    let cents = (40usize..=100).prop_map(|c| c * 50);
+    return (
+      proptest::option::of(cents.clone()),
+      proptest::collection::vec(cents, 1..=4),
+      proptest::option::of(1usize..=3),
+      any::<bool>(),
+      2usize..=4
+    ).prop_map(|(promo, rows, promo_limit, combo, max_span)| {
+      let has_promo = promo.is_some();
+      return SalesContext {
+        batches: iter2bp(promo.into_iter().chain(rows), has_promo.then_some(0)),
+        has_promo,
+        promo_limit,
+        enable_promo_combo: combo,
+        max_span,
+        ..SalesContext::default()
+      };
+    });
+  }
+
+  /// Every kind of match the context allows, one ticket per batch.
+  fn shapes(ctx: &SalesContext) -> Vec<PricingMatch> {
+    let one = |b: Batch| BatchAmount(b, 1);
+    let all: Vec<Batch> = bp2iter(&ctx.batches).collect();
+    let numbered: Vec<Batch> = all.iter()
+      .filter(|b| b.num != BatchNum::Promo)
+      .copied()
+      .collect();
+    let mut v: Vec<PricingMatch> = all.iter()
+      .map(|b| PricingMatch::Multiple(one(*b)))
+      .collect();
+    for t in numbered.windows(2) {
+      v.push(PricingMatch::TurnOfBatch(one(t[0]), one(t[1])));
+    }
+    for span in 3..=ctx.max_span {
+      for run in numbered.windows(span) {
+        v.push(PricingMatch::MultiBatch(run.iter().map(|b| one(*b)).collect()));
+      }
+    }
+    let promo = all.first().filter(|b| b.num == BatchNum::Promo);
+    if let (Some(p), true) = (promo, ctx.enable_promo_combo) {
+      v.push(PricingMatch::PromoCombo(one(*p), one(numbered[0])));
+      if let Some(b2) = numbered.get(1) {
+        v.push(PricingMatch::PromoPlusTurn(
+          one(*p), one(numbered[0]), one(*b2)
+        ));
+      }
+    }
+    return v;
+  }
+
+  /// A match shaped like some other, with other amounts. Promos are kept to
+  /// the limit.
+  fn reamount(
+    pm: &PricingMatch,
+    amounts: &[usize],
+    ctx: &SalesContext
+  ) -> PricingMatch {
+    let limit = ctx.promo_limit.unwrap_or(usize::MAX);
+    let bas: Vec<BatchAmount> = pm.amounts().iter().zip(amounts)
+      .map(|(ba, a)| match ba.0.num {
+        BatchNum::Promo => BatchAmount(ba.0, (*a).min(limit)),
+        _ => BatchAmount(ba.0, *a)
+      })
+      .collect();
+    return match pm {
+      PricingMatch::Multiple(_) => PricingMatch::Multiple(bas[0]),
+      PricingMatch::PromoCombo(_, _) => {
+        PricingMatch::PromoCombo(bas[0], bas[1])
+      },
+      PricingMatch::TurnOfBatch(_, _) => {
+        PricingMatch::TurnOfBatch(bas[0], bas[1])
+      },
+      PricingMatch::PromoPlusTurn(_, _, _) => {
+        PricingMatch::PromoPlusTurn(bas[0], bas[1], bas[2])
+      },
+      PricingMatch::MultiBatch(_) => PricingMatch::MultiBatch(bas),
+    };
+  }
+
+  /// A context, and some match it allows, of up to three tickets per batch.
+  fn context_and_match() -> impl Strategy<Value = (SalesContext, PricingMatch)>
+  {
+    return contexts()
+      .prop_flat_map(|ctx| {
+        let shape = proptest::sample::select(shapes(&ctx));
+        let amounts = proptest::collection::vec(1usize..=3, 4);
+        return (Just(ctx), shape, amounts);
+      })
+      .prop_map(|(ctx, shape, amounts)| {
+        let pm = reamount(&shape, &amounts, &ctx);
+        return (ctx, pm);
+      });
+  }
+
+  proptest! {
+    // shrink hard, so a failure shows the smallest context and price
+    #![proptest_config(ProptestConfig {
+      max_shrink_iters: 10_000,
+      ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn all_priced_finds_every_match((ctx, pm) in context_and_match()) {
+      let price = pm.price();
+      let all = PricingMatch::all_priced(price, &ctx);
+      prop_assert!(all.contains(&pm), "{} not among {:?}", pm, all);
+    }
+
+    #[test]
+    fn all_priced_adds_up(ctx in contexts(), price in 1usize..=80_000) {
+      for pm in PricingMatch::all_priced(price, &ctx) {
+        prop_assert_eq!(pm.price(), price, "{}", pm);
+      }
+    }
+
+    #[test]
+    fn promo_combos_keep_to_the_limit(
+      ctx in contexts(),
+      price in (1usize..=200).prop_map(|p| p * 500)
+    ) {
+      let limit = ctx.promo_limit.unwrap_or(usize::MAX);
+      for pm in PricingMatch::all_priced(price, &ctx) {
+        if let PricingMatch::PromoCombo(pba, _) = pm {
+          prop_assert!(pba.1 <= limit, "{} over {}", pm, limit);
+        }
+        prop_assert!(pm.promos() <= limit, "{} over {}", pm, limit);
+      }
+    }
+
+    #[test]
+    fn cache_is_transparent(
+      ctx in contexts(),
+      prices in proptest::collection::vec(
+        (1usize..=40).prop_map(|p| p * 500), 1..40
+      ),
+      cap in proptest::option::of(1usize..=8)
+    ) {
+      let ctx = Rc::new(ctx);
+      let mut cache = PricingCandidateCache::from(ctx.clone());
+      if let Some(c) = cap {
+        cache = cache.with_cap(c);
+      }
+      for p in prices {
+        let uncached = PricingCandidate::from_price(p, &ctx);
+        prop_assert_eq!(cache.from_price(p), uncached, "price {}", p);
+      }
+    }
+  }
+
+  #[test]
+  fn promo_limit_is_inclusive() {
+    let promo = Batch { num: BatchNum::Promo, price: 5500 };
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    let ctx = SalesContext::default();
+    assert_eq!(ctx.promo_limit, Some(1));
+    assert_eq!(
+      PricingMatch::all_priced(12000, &ctx),
+      vec![PricingMatch::PromoCombo(
+        BatchAmount(promo, 1), BatchAmount(first, 1)
+      )]
+    );
+    let ctx = SalesContext { promo_limit: Some(2), ..ctx };
+    assert_eq!(PricingMatch::all_priced(17500, &ctx).len(), 1);
+    assert!(PricingMatch::all_priced(23000, &ctx).is_empty());
+  }
+
+  #[test]
+  fn one_promo_per_sale() {
+    let promo = Batch { num: BatchNum::Promo, price: 5500 };
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    let three_promos = PricingMatch::PromoCombo(
+      BatchAmount(promo, 3), BatchAmount(first, 1)
+    );
+    // R$ 230, which only 3 promos and a 1st batch add up to
+    let price = three_promos.price();
+    let ctx = SalesContext::default();
+    let has_promo_combo = |ctx: &SalesContext| {
+      return PricingMatch::all_priced(price, ctx).iter()
+        .any(|pm| matches!(pm, PricingMatch::PromoCombo(_, _)));
+    };
+    assert!(!has_promo_combo(&ctx));
+    let ctx = SalesContext { promo_limit: Some(3), ..ctx };
+    assert!(PricingMatch::all_priced(price, &ctx).contains(&three_promos));
+    let ctx = SalesContext { promo_limit: None, ..ctx };
+    assert!(has_promo_combo(&ctx));
+  }
+
+  #[test]
+  fn promo_above_first_batch() {
+    let ctx = SalesContext {
+      batches: iter2bp([7000, 5500, 6500], Some(0)),
+      ..SalesContext::default()
+    };
+    let promo = Batch { num: BatchNum::Promo, price: 7000 };
+    let first = Batch { num: BatchNum::Numbered(1), price: 5500 };
+    assert_eq!(
+      PricingMatch::all_priced(7000, &ctx),
+      vec![PricingMatch::Multiple(BatchAmount(promo, 1))]
+    );
+    assert_eq!(
+      PricingMatch::all_priced(12500, &ctx),
+      vec![PricingMatch::PromoCombo(
+        BatchAmount(promo, 1), BatchAmount(first, 1)
+      )]
+    );
+  }
+
+  #[test]
+  fn promo_to_first_is_never_a_turn() {
+    for combo in [true, false] {
+      let ctx = SalesContext {
+        enable_promo_combo: combo,
+        ..SalesContext::default()
+      };
+      // a promo and a 1st batch ticket
+      let all = PricingMatch::all_priced(12000, &ctx);
+      assert!(
+        !all.iter().any(|pm| matches!(pm, PricingMatch::TurnOfBatch(..)))
+      );
+      assert_eq!(all.len(), combo as usize);
+    }
+  }
+
+  #[test]
+  fn no_combos_no_combo_ambiguity() {
+    let ctx = SalesContext {
+      batches: iter2bp([4000, 6000, 8000], Some(0)),
+      ..SalesContext::default()
+    };
+    // a promo and two 1st batch tickets, or two 2nd batch ones
+    assert_eq!(PricingCandidate::from_price(16000, &ctx).count(), 2);
+    let ctx = SalesContext { enable_promo_combo: false, ..ctx };
+    let second = Batch { num: BatchNum::Numbered(2), price: 8000 };
+    assert_eq!(
+      PricingCandidate::from_price(16000, &ctx),
+      PricingCandidate::Precise(PricingMatch::Multiple(BatchAmount(second, 2)))
+    );
+  }
+
+  #[test]
+  fn promo_plus_turn_only() {
+    let ctx = SalesContext {
+      batches: iter2bp([5000, 7000, 9100], Some(0)),
+      ..SalesContext::default()
+    };
+    let promo = Batch { num: BatchNum::Promo, price: 5000 };
+    let first = Batch { num: BatchNum::Numbered(1), price: 7000 };
+    let second = Batch { num: BatchNum::Numbered(2), price: 9100 };
+    let pm = PricingMatch::PromoPlusTurn(
+      BatchAmount(promo, 1), BatchAmount(first, 1), BatchAmount(second, 1)
+    );
+    assert_eq!(
+      PricingCandidate::from_price(21100, &ctx),
+      PricingCandidate::Precise(pm.clone())
+    );
+    assert_eq!(pm.price(), 21100);
+    assert_eq!(pm.tickets(), 3);
+    assert_eq!(pm.batches(), HashSet::from([promo, first, second]));
+    assert_eq!(pm.batch_before(), promo);
+    assert_eq!(pm.batch_after(), second);
+  }
+
+  #[test]
+  fn huge_prices_dont_overflow() {
+    let big = usize::MAX / 3;
+    let ctx = SalesContext {
+      batches: iter2bp([big, big + 1, big + 2, big + 3], Some(0)),
+      promo_limit: None,
+      max_span: 4,
+      ..SalesContext::default()
+    };
+    for price in [usize::MAX, usize::MAX - 1, big * 3, big + 1] {
+      for pm in PricingMatch::all_priced(price, &ctx) {
+        assert_eq!(pm.checked_price(), Some(price));
+      }
+    }
+    let first = Batch { num: BatchNum::Numbered(1), price: big + 1 };
+    let pm = PricingMatch::Multiple(BatchAmount(first, 4));
+    assert_eq!(pm.checked_price(), None);
+    assert_eq!(pm.price(), usize::MAX);
+  }
+
+  #[test]
+  fn overrides_come_first() {
+    let mut ctx = SalesContext::default();
+    assert_eq!(PricingCandidate::from_price(19500, &ctx).count(), 2);
+    let first = Batch { num: BatchNum::Numbered(1), price: 6500 };
+    let three = PricingMatch::Multiple(BatchAmount(first, 3));
+    ctx.overrides.insert(19500, three.clone());
+    let precise = PricingCandidate::Precise(three.clone());
+    assert_eq!(PricingCandidate::from_price(19500, &ctx), precise);
+    let mut cache = PricingCandidateCache::from(Rc::new(ctx.clone()));
+    assert_eq!(cache.from_price(19500), precise);
+    let sale = priced(0, 19500, None, &ctx);
+    let sp = sales_plus(ctx, vec![sale]);
+    assert_eq!(sp.sales[0].pricematch, Some(three));
+  }
+
+  #[test]
+  fn shows_batch_prices() {
+    let promo = Batch { num: BatchNum::Promo, price: 5500 };
+    let second = Batch { num: BatchNum::Numbered(2), price: 123456 };
+    let promo_name = format!("{} (R$ 55,00)", BatchNum::Promo);
+    let second_name = format!("{} (R$ 1.234,56)", BatchNum::Numbered(2));
+    assert_eq!(promo.to_string(), promo_name);
+    assert_eq!(second.to_string(), second_name);
+    let pm = PricingMatch::PromoCombo(
+      BatchAmount(promo, 1), BatchAmount(second, 2)
+    );
+    assert_eq!(
+      pm.to_string(),
+      format!("1x {} + 2x {}", promo_name, second_name)
+    );
+    assert_eq!(
+      format!("{:#}", pm),
+      format!(
+        "[1x {} = R$ 55,00] + [2x {} = R$ 2.469,12] = R$ 2.524,12",
+        promo_name, second_name
+      )
+    );
+  }
+
+  #[test]
+  fn shows_every_kind_of_match() {
+    let b = |n: usize, price: usize| {
+      return Batch { num: BatchNum::Numbered(n), price };
+    };
+    let promo = Batch { num: BatchNum::Promo, price: 5500 };
+    let (b1, b2, b3) = (b(1, 6500), b(2, 7500), b(3, 8500));
+    let (p, n1, n2, n3) = (
+      format!("{} (R$ 55,00)", BatchNum::Promo),
+      format!("{} (R$ 65,00)", BatchNum::Numbered(1)),
+      format!("{} (R$ 75,00)", BatchNum::Numbered(2)),
+      format!("{} (R$ 85,00)", BatchNum::Numbered(3))
+    );
+    let cases = [
+      (
+        PricingMatch::Multiple(BatchAmount(b1, 3)),
+        format!("3x {}", n1),
+        format!("3x {} = R$ 195,00", n1)
+      ),
+      (
+        PricingMatch::TurnOfBatch(BatchAmount(b1, 1), BatchAmount(b2, 2)),
+        format!("1x {} + 2x {}", n1, n2),
+        format!(
+          "[1x {} = R$ 65,00] + [2x {} = R$ 150,00] = R$ 215,00", n1, n2
+        )
+      ),
+      (
+        PricingMatch::PromoPlusTurn(
+          BatchAmount(promo, 1), BatchAmount(b1, 1), BatchAmount(b2, 1)
+        ),
+        format!("1x {} + 1x {} + 1x {}", p, n1, n2),
+        format!(
+          "[1x {} = R$ 55,00] + [1x {} = R$ 65,00] + [1x {} = R$ 75,00] \
+          = R$ 195,00",
+          p, n1, n2
+        )
+      ),
+      (
+        PricingMatch::MultiBatch(vec![
+          BatchAmount(b1, 1), BatchAmount(b2, 1), BatchAmount(b3, 2)
+        ]),
+        format!("1x {} + 1x {} + 2x {}", n1, n2, n3),
+        format!(
+          "[1x {} = R$ 65,00] + [1x {} = R$ 75,00] + [2x {} = R$ 170,00] \
+          = R$ 310,00",
+          n1, n2, n3
+        )
+      ),
+    ];
+    for (pm, concise, verbose) in cases {
+      assert_eq!(pm.to_string(), concise);
+      assert_eq!(format!("{:#}", pm), verbose);
+    }
+  }
+
+  #[test]
+  fn codes_go_both_ways() {
+    let b = |n: usize, price: usize| {
+      return BatchAmount(Batch { num: n.into(), price }, 2);
+    };
+    let all = [
+      PricingMatch::Multiple(b(1, 6500)),
+      PricingMatch::PromoCombo(b(0, 5500), b(1, 6500)),
+      PricingMatch::TurnOfBatch(b(1, 6500), b(2, 7500)),
+      PricingMatch::PromoPlusTurn(b(0, 5500), b(1, 6500), b(2, 7500)),
+      PricingMatch::MultiBatch(vec![b(1, 6500), b(2, 7500), b(3, 8500)]),
+    ];
+    assert_eq!(all[1].code(), "2xpromo@5500+2x1@6500");
+    for pm in all {
+      assert_eq!(PricingMatch::try_from(pm.code().as_str()), Ok(pm));
+    }
+    for bad in ["", "2x1", "2x0@6500", "1x1@6500+1xpromo@5500", "x1@1"] {
+      assert_eq!(PricingMatch::try_from(bad), Err(()), "{}", bad);
+    }
+  }
+
+  #[test]
+  fn three_batch_span() {
+    let ctx = SalesContext {
+      batches: iter2bp([6000, 7100, 8300], None),
+      has_promo: false,
+      ..SalesContext::default()
+    };
+    assert_eq!(ctx.max_span, 2);
+    assert_eq!(
+      PricingCandidate::from_price(21400, &ctx),
+      PricingCandidate::NoMatch
+    );
+    let ctx = SalesContext { max_span: 3, ..ctx };
+    let bas: Vec<BatchAmount> = bp2iter(&ctx.batches)
+      .map(|b| BatchAmount(b, 1))
+      .collect();
+    let pm = PricingMatch::MultiBatch(bas);
+    assert_eq!(
+      PricingCandidate::from_price(21400, &ctx),
+      PricingCandidate::Precise(pm.clone())
+    );
+    assert_eq!(pm.tickets(), 3);
+    assert_eq!(pm.batches().len(), 3);
+    assert_eq!(pm.batch_before().num, BatchNum::Numbered(1));
+    assert_eq!(pm.batch_after().num, BatchNum::Numbered(3));
+  }
+
+  #[test]
+  fn lone_online_promo_is_a_multiple() {
+    let promo = Batch { num: BatchNum::Promo, price: 5500 };
+    let lone = PricingMatch::Multiple(BatchAmount(promo, 1));
+    for combo in [true, false] {
+      let ctx = SalesContext {
+        enable_promo_combo: combo,
+        ..SalesContext::default()
+      };
+      let sale = priced(0, 5500, None, &ctx);
+      assert_eq!(sale.value, 6050);
+      let sp = sales_plus(ctx, vec![sale]);
+      assert_eq!(
+        sp.sales[0].pricecand,
+        PricingCandidate::Precise(lone.clone())
+      );
+    }
+  }
+}
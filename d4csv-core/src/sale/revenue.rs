@@ -0,0 +1,52 @@
+//! Whether money in the report is counted before or after the online fee.
+//!
+//! Gross is what the buyers paid, fee included: it's the value column in the
+//! CSV as it came. Net is what the organizer gets: the matched ticket prices,
+//! or the value with the fee undone for sales that didn't resolve. Offline
+//! sales only have a fee if the context sets one; without it, both agree on
+//! them. Ticket counts never change.
+
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::i18n::tr;
+use crate::sale::plus::SalePlus;
+
+/// Which side of the fee the report's money is on.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub enum Revenue {
+  /// What the buyers paid, fees included.
+  Gross,
+  /// What the organizer gets, fees left out.
+  Net
+}
+
+impl Default for Revenue {
+  /// Gross, since that's what the CSV says.
+  fn default() -> Self {
+    return Self::Gross;
+  }
+}
+
+impl Display for Revenue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      Revenue::Gross => "revenue_gross",
+      Revenue::Net => "revenue_net",
+    }));
+  }
+}
+
+impl Revenue {
+  /// How much a sale counts for, in cents.
+  pub fn of(&self, s: &SalePlus) -> usize {
+    return match self {
+      Revenue::Gross => s.sale.value,
+      Revenue::Net => match &s.pricematch {
+        Some(pm) => pm.price(),
+        None => s.sale.real_price(),
+      },
+    };
+  }
+}
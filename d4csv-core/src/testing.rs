@@ -0,0 +1,59 @@
+//! Shorthands for the unit tests: sales made by hand, without going through
+//! a CSV.
+
+use std::rc::Rc;
+use chrono::{Duration, TimeZone, Utc};
+use crate::context::SalesContext;
+use crate::sale::Sale;
+use crate::sale::kind::{SaleKind, Seller};
+use crate::sale::plus::SalesPlus;
+
+/// A sale worth some cents (fee and all), some minutes into the 2022 sales.
+/// Online without a seller, offline with one. The token and ID go by the
+/// minute, so no two sales share them.
+pub(crate) fn sale(
+  minutes: i64,
+  value: usize,
+  seller: Option<&str>,
+  ctx: &SalesContext
+) -> Sale {
+  let start = Utc.with_ymd_and_hms(2022, 10, 1, 12, 0, 0).unwrap();
+  return Sale {
+    row: minutes as usize,
+    when: start + Duration::minutes(minutes),
+    buyer_email: Some(format!("b{}@d4.com", minutes)),
+    buyer_username: None,
+    value,
+    subtotal: None,
+    sale_kind: match seller {
+      None => SaleKind::Online(ctx.online_fee),
+      Some(_) => SaleKind::Offline(ctx.offline_fee),
+    },
+    seller_name: seller.map(Rc::from),
+    seller_id: None,
+    seller_email: None,
+    seller_key: seller.map(|s| Rc::from(Seller::normalize(s))),
+    token: format!("t{}", minutes),
+    sale_id: format!("i{}", minutes),
+    card_name: None,
+    card_pfx: None,
+    card_sfx: None
+  };
+}
+
+/// A sale of some price (in cents, without the fee), with the fee its kind
+/// pays put on top.
+pub(crate) fn priced(
+  minutes: i64,
+  price: usize,
+  seller: Option<&str>,
+  ctx: &SalesContext
+) -> Sale {
+  let s = sale(minutes, 0, seller, ctx);
+  return Sale { value: s.sale_kind.apply_fee(price), ..s };
+}
+
+/// Prices the sales in a context, without solving anything yet.
+pub(crate) fn sales_plus(ctx: SalesContext, sales: Vec<Sale>) -> SalesPlus {
+  return SalesPlus::from_sales(sales.into_iter(), Rc::new(ctx));
+}
@@ -0,0 +1,5 @@
+//! Ticket abstractions and such.
+
+pub mod batchnum;
+pub mod batch;
+
@@ -0,0 +1,75 @@
+//! Abstractions for ticket batches.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::format;
+use crate::ticket::batchnum::BatchNum;
+
+/// A single ticket batch.
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize
+)]
+pub struct Batch {
+  /// Batch number.
+  pub num: BatchNum,
+  /// Price in cents.
+  pub price: usize
+}
+
+impl Display for Batch {
+  /// The number alone is ambiguous across events, so the price goes along,
+  /// e.g. "2º lote (R$ 65,00)".
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{} ({})", self.num, format::money(self.price));
+  }
+}
+
+/// A list of batch prices, kept in batch order (promo first).
+pub type BatchPrices = BTreeMap<BatchNum, usize>;
+
+/// Generates a BatchPrices from a list of prices (in cents). If there's a
+/// promo batch, its position in the list is given; all other prices become
+/// numbered batches, in order, starting at 1.
+pub fn iter2bp<T: IntoIterator<Item = usize>>(
+  iter: T,
+  promo_at: Option<usize>
+) -> BatchPrices {
+  let mut bp = BatchPrices::new();
+  let mut next: usize = 1;
+  for (i, n) in iter.into_iter().enumerate() {
+    if promo_at == Some(i) {
+      bp.insert(BatchNum::Promo, n);
+    } else {
+      bp.insert(BatchNum::Numbered(next), n);
+      next += 1;
+    }
+  }
+  return bp;
+}
+
+/// Generates an iterator of Batch from a BatchPrices, in batch order: the
+/// promo batch first, then the numbered ones going up.
+pub fn bp2iter(
+  bp: &BatchPrices
+) -> impl Iterator<Item = Batch> + '_ + Clone {
+  return bp.iter().map(|(num, price)| Batch { num: *num, price: *price });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn promo_only_where_told() {
+    let prices = [5500, 6500, 7500];
+    let with: Vec<BatchNum> = iter2bp(prices, Some(0)).into_keys().collect();
+    assert_eq!(with, vec![
+      BatchNum::Promo, BatchNum::Numbered(1), BatchNum::Numbered(2)
+    ]);
+    let without: Vec<BatchNum> = iter2bp(prices, None).into_keys().collect();
+    assert_eq!(without, vec![
+      BatchNum::Numbered(1), BatchNum::Numbered(2), BatchNum::Numbered(3)
+    ]);
+  }
+}
@@ -0,0 +1,100 @@
+//! Ticket batch numbering.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use crate::i18n::{tr, trf};
+
+/// The number of a single ticket batch. Serialized as "promo", "1", "2"...
+/// so it works as a JSON map key.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum BatchNum {
+  /// Promo batch
+  Promo,
+  /// Numbered batches (start at 1!).
+  Numbered(usize)
+}
+
+impl BatchNum {
+  /// Implicit batch number -- promo is zero.
+  pub fn inum(&self) -> usize {
+    return match self {
+      BatchNum::Promo => 0,
+      BatchNum::Numbered(n) => *n,
+    }
+  }
+}
+
+impl PartialOrd for BatchNum {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    return Some(self.cmp(other));
+  }
+}
+
+impl Ord for BatchNum {
+  fn cmp(&self, other: &Self) -> Ordering {
+    return match (self, other) {
+      (BatchNum::Promo, BatchNum::Promo) => Ordering::Equal,
+      (BatchNum::Promo, BatchNum::Numbered(_)) => Ordering::Less,
+      (BatchNum::Numbered(_), BatchNum::Promo) => Ordering::Greater,
+      (BatchNum::Numbered(a), BatchNum::Numbered(b)) => a.cmp(b),
+    };
+  }
+}
+
+impl Display for BatchNum {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return match self {
+      Self::Promo => write!(f, "{}", tr("batch_promo")),
+      Self::Numbered(n) => write!(f, "{}", trf("batch_n", &[n]))
+    };
+  }
+}
+
+impl From<usize> for BatchNum {
+  fn from(n: usize) -> Self {
+    return match n {
+      0 => Self::Promo,
+      n => Self::Numbered(n)
+    }
+  }
+}
+
+impl From<BatchNum> for String {
+  fn from(bn: BatchNum) -> Self {
+    return match bn {
+      BatchNum::Promo => "promo".to_owned(),
+      BatchNum::Numbered(n) => n.to_string(),
+    };
+  }
+}
+
+impl TryFrom<String> for BatchNum {
+  type Error = String;
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    if s == "promo" {
+      return Ok(BatchNum::Promo);
+    }
+    return match s.parse::<usize>() {
+      Ok(n) if n > 0 => Ok(BatchNum::Numbered(n)),
+      _ => Err(trf("bad_batch_key", &[&s])),
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn serializes_as_map_keys() {
+    let json = serde_json::to_string(&[BatchNum::Promo, BatchNum::Numbered(2)]);
+    assert_eq!(json.unwrap(), r#"["promo","2"]"#);
+    let back: Vec<BatchNum> = serde_json::from_str(r#"["promo","2"]"#).unwrap();
+    assert_eq!(back, vec![BatchNum::Promo, BatchNum::Numbered(2)]);
+    for bad in [r#""0""#, r#""x""#, r#""-1""#] {
+      assert!(serde_json::from_str::<BatchNum>(bad).is_err());
+    }
+  }
+}
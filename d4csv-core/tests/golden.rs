@@ -0,0 +1,78 @@
+//! Golden-file regression test: a small export, anonymized the way the real
+//! ones get shared (e-mails, names and tokens hashed, values and timestamps
+//! kept), goes through the whole pipeline with the default context, and the
+//! better CSV and the report's string fields have to come out exactly as
+//! saved. When a change to them is on purpose, run with D4CSV_BLESS=1 to save
+//! the new ones, and check the diff before committing it.
+
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use d4csv_core::{Report, SalesContext};
+use d4csv_core::loader::{Loader, LoadSource, LoadStep, QuoteChar};
+
+/// Set to anything to write the goldens instead of checking against them.
+static BLESS: &str = "D4CSV_BLESS";
+
+/// Where the fixtures are.
+fn fixture(name: &str) -> PathBuf {
+  return PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("tests")
+    .join("fixtures")
+    .join(name);
+}
+
+/// Runs a fixture CSV through the loader, solvers and all.
+fn run(name: &str) -> Report {
+  let csv = fs::read_to_string(fixture(name)).unwrap();
+  let src = LoadSource::Csv(csv, QuoteChar::default(), true);
+  let mut ld = Loader::new(SalesContext::default(), src, None);
+  loop {
+    match ld.step() {
+      LoadStep::Progress(_) => continue,
+      LoadStep::Done(_, report, _) => return *report,
+      LoadStep::Failed(ve) => panic!("{} didn't load: {:?}", name, ve),
+    }
+  }
+}
+
+/// Compares some output with its golden, or saves it as the golden when
+/// blessing.
+fn check(name: &str, got: &str) {
+  let path = fixture(name);
+  if env::var_os(BLESS).is_some() {
+    fs::write(&path, got).unwrap();
+    return;
+  }
+  let want = fs::read_to_string(&path)
+    .unwrap_or_else(|e| panic!("{}: {} ({}=1 saves it)", name, e, BLESS));
+  assert!(
+    got == want,
+    "{} changed ({}=1 saves it if that's on purpose):\n{}",
+    name,
+    BLESS,
+    got
+  );
+}
+
+/// The CSV minus its footer, which stamps the time and build it came from.
+fn without_footer(csv: &str) -> &str {
+  return match csv.rfind("\n\"# ") {
+    Some(i) => &csv[..=i],
+    None => csv,
+  };
+}
+
+#[test]
+fn vendas() {
+  let report = run("vendas.csv");
+  let better = report.better_csv_txt(false).unwrap();
+  check("vendas.better.csv", without_footer(&better));
+  let sfields: String = report.sfields.iter()
+    .map(|sf| format!("{}: {}\n", sf.0, sf.1))
+    .collect();
+  check("vendas.sfields.txt", &sfields);
+}
@@ -0,0 +1,39 @@
+// Runs a D4 export through d4csv from Node, without the page.
+//
+// Build the bindings first:
+//   wasm-pack build --target nodejs --out-dir pkg -- --features api
+// then:
+//   node examples/process_csv.js vendas.csv contexto.json [solver]
+//
+// The context is a file exported from the form ("exportar contexto"). The
+// solver is optional: nothing, temporal, seller, anchor, popularity or
+// global.
+
+const fs = require("fs");
+const { process_csv } = require("../pkg/d4csv.js");
+
+const [csvPath, ctxPath, solver = ""] = process.argv.slice(2);
+if (!csvPath || !ctxPath) {
+  console.error("usage: node process_csv.js <csv> <context.json> [solver]");
+  process.exit(2);
+}
+
+let out;
+try {
+  out = JSON.parse(process_csv(
+    fs.readFileSync(csvPath, "utf8"),
+    fs.readFileSync(ctxPath, "utf8"),
+    solver
+  ));
+} catch (e) {
+  console.error(e);
+  process.exit(1);
+}
+
+for (const line of out.skipped) {
+  console.warn(line);
+}
+console.log(out.better_csv_header.join(";"));
+for (const row of out.report.better_csv) {
+  console.log(row.join(";"));
+}
@@ -0,0 +1,82 @@
+//! The pipeline as a plain function for JavaScript callers (scripts, Node),
+//! with no page or yew involved. Only built with the "api" feature;
+//! analyze_csv also needs "embed".
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use d4csv_core::context::SalesContext;
+use d4csv_core::i18n::trf;
+use d4csv_core::loader::{Loader, LoadSource, LoadStep, QuoteChar};
+use d4csv_core::report::Report;
+use d4csv_core::sale::plus::SalePlus;
+
+/// What process_csv hands back, as JSON.
+#[derive(Serialize)]
+struct ProcessOutput {
+  /// The better CSV's header, lined up with the report's better_csv rows.
+  better_csv_header: Vec<String>,
+  /// CSV lines that didn't make a sale, and why.
+  skipped: Vec<String>,
+  /// The whole report.
+  report: Report
+}
+
+/// Parses the context and runs the CSV (with a header row, double-quoted)
+/// through the loader until it's done. Gives back the skipped lines too.
+fn run(
+  csv: &str,
+  context_json: &str,
+  solver: &str
+) -> Result<(Report, Vec<String>), JsValue> {
+  let mut ctx = SalesContext::from_json(context_json)
+    .map_err(|e| JsValue::from_str(&e))?;
+  if !solver.is_empty() {
+    ctx.solver = solver.try_into()
+      .map_err(|_| JsValue::from_str(&trf("bad_solver", &[&solver])))?;
+  }
+  let src = LoadSource::Csv(csv.to_owned(), QuoteChar::default(), true);
+  let mut ld = Loader::new(ctx, src, None);
+  let report = loop {
+    match ld.step() {
+      LoadStep::Progress(_) => continue,
+      LoadStep::Done(_, report, _) => break *report,
+      LoadStep::Failed(ve) => {
+        let msgs: Vec<String> = ve.iter().map(|e| e.to_string()).collect();
+        return Err(JsValue::from_str(&msgs.join("\n")));
+      },
+    }
+  };
+  let skipped = ld.skipped().iter().map(|e| e.to_string()).collect();
+  return Ok((report, skipped));
+}
+
+/// Runs a CSV through the whole pipeline and returns the report as a JSON
+/// string. The context is a context file, as exported by the form. An empty
+/// solver name keeps the context's.
+#[wasm_bindgen]
+pub fn process_csv(
+  csv: &str,
+  context_json: &str,
+  solver: &str
+) -> Result<JsValue, JsValue> {
+  let (report, skipped) = run(csv, context_json, solver)?;
+  let out = ProcessOutput {
+    better_csv_header: SalePlus::better_csv_header(),
+    skipped,
+    report
+  };
+  return serde_json::to_string(&out)
+    .map(|s| JsValue::from_str(&s))
+    .map_err(|e| JsValue::from_str(&e.to_string()));
+}
+
+/// Runs a CSV through the whole pipeline and returns just the better CSV, as
+/// the page would download it. For pages embedding the engine that don't
+/// care about the rest of the report.
+#[cfg(feature = "embed")]
+#[wasm_bindgen]
+pub fn analyze_csv(csv: &str, context_json: &str) -> Result<String, JsValue> {
+  let (report, _) = run(csv, context_json, "")?;
+  return report.better_csv_txt(false)
+    .map_err(|e| JsValue::from_str(&e.to_string()));
+}
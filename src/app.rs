@@ -1,77 +1,614 @@
 //! The main app thing, minus the wrapper all-around.
 
-use std::error::Error;
-use csv::ReaderBuilder;
-use web_sys::{Event, HtmlTextAreaElement, MouseEvent};
-use yew::{Component, html, html_nested};
+pub(crate) mod price_check;
+#[cfg(feature = "worker")]
+pub(crate) mod worker;
+
+use std::collections::HashSet;
+use std::rc::Rc;
+use chrono::NaiveDate;
+use gloo_storage::{LocalStorage, SessionStorage, Storage};
+use gloo_timers::callback::Timeout;
+#[cfg(feature = "worker")]
+use gloo_worker::{Spawnable, WorkerBridge};
+use itertools::Itertools;
+use wasm_bindgen::JsCast;
+use web_sys::{
+  Event, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, MouseEvent
+};
+use yew::{Component, Html, html, html_nested};
+use yew::context::ContextHandle;
 use yew::html::TargetCast;
-use crate::context::{SalesContext, ContextInput};
-use crate::report::{ReportDisplay, ReportTemplate};
-use crate::sale::Sale;
-use crate::sale::plus::SalesPlus;
+#[cfg(not(feature = "worker"))]
+use d4csv_core::budget::Budget;
+use d4csv_core::context::{SalesContext, ContextInputData};
+use d4csv_core::error::D4Error;
+use d4csv_core::format;
+use d4csv_core::i18n::{Lang, tr, trf};
+use d4csv_core::loader::{
+  Loader, LoadProgress, LoadSource, LoadStep, QuoteChar, sniff_headers
+};
+use d4csv_core::report::{Report, ReportTemplate};
+use d4csv_core::sale::ambiguity::AmbiguitySolver;
+use d4csv_core::sale::columns::HeaderInfo;
+use d4csv_core::sale::diff::RunDiff;
+use d4csv_core::sale::error::{EXAMPLE_LINES, ErrorGroup, group_errors};
+use d4csv_core::sale::filter::SalesFilter;
+use d4csv_core::sale::plus::{SalePlus, SalesPlus};
+use d4csv_core::sale::price_deriving::PricingCandidateCache;
+use d4csv_core::sale::revenue::Revenue;
+use crate::app::price_check::PriceCheck;
+#[cfg(feature = "worker")]
+use crate::app::worker::{LoadRequest, LoadUpdate, LoadWorker, WORKER_PATH};
+use crate::context::ContextInput;
+use crate::report::ReportDisplay;
+
+/// Where the reviewed sale IDs are kept in localStorage.
+static REVIEWED_KEY: &str = "d4csv.reviewed";
+
+/// Loads the reviewed sale IDs, if there are any stored.
+fn load_reviewed() -> HashSet<String> {
+  return LocalStorage::get(REVIEWED_KEY).unwrap_or_default();
+}
+
+/// Saves the reviewed sale IDs.
+fn store_reviewed(reviewed: &HashSet<String>) {
+  if let Err(e) = LocalStorage::set(REVIEWED_KEY, reviewed) {
+    log::warn!("couldn't store reviewed sales: {}", e);
+  }
+}
+
+/// Where the pasted CSV is kept in sessionStorage, to survive a reload.
+static CSV_KEY: &str = "d4csv.csv";
+
+/// CSVs bigger than this (in bytes) aren't kept, browsers only give us so
+/// much room.
+static CSV_STORE_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Keeps the CSV text around for this tab, unless it's too big. A too big one
+/// also throws away the older copy, so a reload doesn't bring back a stale one.
+fn remember_csv(txt: &str) {
+  if txt.len() > CSV_STORE_LIMIT {
+    log::warn!("CSV too big to keep across reloads ({} bytes)", txt.len());
+    SessionStorage::delete(CSV_KEY);
+  } else if let Err(e) = SessionStorage::set(CSV_KEY, txt) {
+    log::warn!("couldn't keep the CSV: {}", e);
+  }
+}
+
+/// How long a run gets to price and solve, in milliseconds, before it gives
+/// up and shows what it has.
+pub(crate) static RUN_BUDGET_MS: f64 = 120_000.0;
+
+/// Milliseconds since the page (or worker) started, for the run budget.
+pub(crate) fn now_ms() -> f64 {
+  let global = js_sys::global();
+  let perf = match global.dyn_ref::<web_sys::Window>() {
+    Some(w) => w.performance(),
+    None => global.dyn_ref::<web_sys::WorkerGlobalScope>()
+      .and_then(|w| w.performance()),
+  };
+  return perf.map(|p| p.now()).unwrap_or_else(js_sys::Date::now);
+}
 
 #[derive(Debug)]
 pub(crate) enum AppState {
   Input,
-  Errors(Vec<Box<dyn Error>>),
-  Loaded(SalesPlus)
+  Errors(Vec<D4Error>),
+  Working { progress: LoadProgress },
+  /// All the sales, and the report on the (maybe filtered) sales.
+  Loaded(SalesPlus, Rc<Report>)
 }
 
 #[derive(Debug)]
 pub(crate) enum AppMsg {
   DoNothing,
-  ShowErrors(Vec<Box<dyn Error>>),
+  ShowErrors(Vec<D4Error>),
   GotContext(SalesContext),
+  ContextValidity(bool),
   GotCsv(String),
-  TryReport
+  ClearCsv,
+  GotQuote(QuoteChar),
+  GotHasHeaders(bool),
+  TryReport,
+  LoadStep,
+  #[cfg(feature = "worker")]
+  WorkerSaid(LoadUpdate),
+  FilterChanged(SalesFilter),
+  RevenueToggled(bool),
+  RerunSolverPicked(AmbiguitySolver),
+  Rerun,
+  ToggleReviewed(String),
+  CompareToggled(bool),
+  LangChanged(Lang),
+  BackToInput
 }
 
 #[derive(Debug)]
 pub(crate) struct App {
   context: Option<SalesContext>,
+  context_ok: bool,
   csv_txt: Option<String>,
-  state: AppState
+  quote: QuoteChar,
+  has_headers: bool,
+  /// What didn't match in the pasted CSV's header, for mapping by hand.
+  headers: HeaderInfo,
+  state: AppState,
+  filter: SalesFilter,
+  /// Whether the report's money has the fees in. Kept between runs.
+  revenue: Revenue,
+  /// Solver picked for running again on the same sales.
+  rerun_solver: Option<AmbiguitySolver>,
+  /// Pricing candidates from the last run, kept for the next one.
+  cache: Option<PricingCandidateCache>,
+  /// The sales from the run before this one, for comparing.
+  previous: Option<SalesPlus>,
+  /// Whether to show what changed since the previous run.
+  compare: bool,
+  /// Sale IDs of unresolved sales someone already looked at.
+  reviewed: HashSet<String>,
+  loader: Option<Loader>,
+  timeout: Option<Timeout>,
+  #[cfg(feature = "worker")]
+  bridge: Option<WorkerBridge<LoadWorker>>,
+  /// Keeps us posted on language changes.
+  _lang: Option<ContextHandle<Lang>>
 }
 
 impl App {
-  /// Try and convert form data to SalesPlus.
-  fn try_load(&self) -> Option<SalesPlus> {
-    if let Some(ctx) = &self.context {
-      if let Some(txt) = &self.csv_txt {
-        let mut rdr = ReaderBuilder::new()
-          .delimiter(b',')
-          .quote(b'\"')
-          .has_headers(true)
-          .from_reader(txt.as_bytes());
-        let sales = Sale::parse_csv(rdr.records(), &ctx);
-        let mut sp = SalesPlus::from_sales(sales.0.into_iter(), ctx.clone());
-        let (passes, solves) = sp.solve_ambiguities();
-        log::info!(
-          "solver \"{}\" removed {} ambiguities in {} passes",
-          sp.context.solver.name(),
-          solves,
-          passes
-        );
-        return Some(sp);
-      }
+  /// Why we can't make a report yet, if that's the case.
+  fn not_ready_reason(&self) -> Option<&'static str> {
+    if !self.context_ok || self.context.is_none() {
+      return Some(tr("fix_context"));
+    }
+    if self.csv_txt.as_ref().map(|s| s.trim().is_empty()).unwrap_or(true) {
+      return Some(tr("paste_csv"));
     }
     return None;
   }
+
+  /// Shows the spinner and starts loading, forgetting the old filter.
+  fn start_loading(
+    &mut self,
+    ctx: &yew::Context<Self>,
+    context: SalesContext,
+    source: LoadSource
+  ) {
+    self.state = AppState::Working {
+      progress: LoadProgress::Parsing(0)
+    };
+    self.filter = SalesFilter::default();
+    self.rerun_solver = None;
+    self.spawn_loader(ctx, context, source);
+  }
+
+  /// Starts loading in a worker, which reports back with WorkerSaid.
+  #[cfg(feature = "worker")]
+  fn spawn_loader(
+    &mut self,
+    ctx: &yew::Context<Self>,
+    context: SalesContext,
+    source: LoadSource
+  ) {
+    let link = ctx.link().clone();
+    let bridge = LoadWorker::spawner()
+      .callback(move |up| link.send_message(AppMsg::WorkerSaid(up)))
+      .spawn(WORKER_PATH);
+    bridge.send(LoadRequest {
+      context,
+      source,
+      cache: self.cache.take(),
+      lang: Lang::current()
+    });
+    self.bridge = Some(bridge);
+  }
+
+  /// Starts loading right here, a chunk at a time.
+  #[cfg(not(feature = "worker"))]
+  fn spawn_loader(
+    &mut self,
+    ctx: &yew::Context<Self>,
+    context: SalesContext,
+    source: LoadSource
+  ) {
+    let budget = Budget::new(now_ms, RUN_BUDGET_MS);
+    let loader = Loader::new(context, source, self.cache.take());
+    self.loader = Some(loader.with_budget(budget));
+    self.schedule_step(ctx);
+  }
+
+  /// Picking another solver and running it again on the same sales.
+  fn view_rerun(&self, ctx: &yew::Context<Self>, sp: &SalesPlus) -> Html {
+    let picked = self.rerun_solver.unwrap_or(sp.context.solver);
+    let pick_cb = ctx.link().callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      let solv = input.value().as_str().try_into().unwrap_or_default();
+      return AppMsg::RerunSolverPicked(solv);
+    });
+    let rerun_cb = ctx.link().callback(|_e: MouseEvent| AppMsg::Rerun);
+    return html! {
+      <div class="rerun">
+        <i>{ trf("ran_with", &[&sp.context.solver]) }</i>
+        <br />
+        <select onchange={pick_cb}>
+          {
+            for AmbiguitySolver::available().map(|solv| {
+              html_nested! {
+                <option value={ solv.name() } selected={ solv == picked }>
+                  { solv }
+                </option>
+              }
+            })
+          }
+        </select>
+        <button onclick={rerun_cb}>{ tr("run_again") }</button>
+      </div>
+    };
+  }
+
+  /// A loud warning for when almost nothing resolved, which almost always
+  /// means the context is wrong, not the sales.
+  fn view_misconfigured(ctx: &yew::Context<Self>, sp: &SalesPlus) -> Html {
+    if !sp.looks_misconfigured() {
+      return html! {};
+    }
+    let back_cb = ctx.link().callback(|_e: MouseEvent| AppMsg::BackToInput);
+    let pct = 100 * sp.oks().count() / sp.sales.len();
+    return html! {
+      <div class="misconfigured">
+        <b>{ trf("misconfigured", &[&pct]) }</b>
+        <ul>
+          <li>{ tr("culprit_fee") }</li>
+          <li>{ tr("culprit_prices") }</li>
+          <li>{ tr("culprit_delimiter") }</li>
+        </ul>
+        <button onclick={back_cb}>{ tr("fix_context_now") }</button>
+      </div>
+    };
+  }
+
+  /// A warning for runs that ran out of time, so the report is partial.
+  fn view_interrupted(sp: &SalesPlus) -> Html {
+    if !sp.interrupted {
+      return html! {};
+    }
+    return html! {
+      <div class="interrupted">
+        <b>{ tr("interrupted_warn") }</b>
+      </div>
+    };
+  }
+
+  /// One group of errors: what went wrong, how many times, and where.
+  fn view_error_group(g: &ErrorGroup) -> Html {
+    let lines = |ls: &[u64]| ls.iter().map(|l| l.to_string()).join(", ");
+    let (shown, rest) = g.lines.split_at(g.lines.len().min(EXAMPLE_LINES));
+    return html! {
+      <div class="error-group">
+        <b>{ format!("{}x {}", g.count, g.kind) }</b>
+        {
+          if g.example.is_empty() {
+            html! {}
+          } else {
+            html! { <pre><code>{ &g.example }</code></pre> }
+          }
+        }
+        {
+          if shown.is_empty() {
+            html! {}
+          } else {
+            html! { <p>{ trf("error_lines", &[&lines(shown)]) }</p> }
+          }
+        }
+        {
+          if rest.is_empty() {
+            html! {}
+          } else {
+            html! {
+              <details>
+                <summary>{ trf("more_lines", &[&rest.len()]) }</summary>
+                { lines(rest) }
+              </details>
+            }
+          }
+        }
+        <br />
+      </div>
+    };
+  }
+
+  /// The filter controls: sellers to keep and a date range.
+  fn view_filter(&self, ctx: &yew::Context<Self>, sp: &SalesPlus) -> Html {
+    let date_cb = |until: bool| {
+      let f = self.filter.clone();
+      return ctx.link().callback(move |e: Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        let d = NaiveDate::parse_from_str(&input.value(), "%Y-%m-%d").ok();
+        let mut f = f.clone();
+        match until {
+          true => f.until = d,
+          false => f.from = d,
+        }
+        return AppMsg::FilterChanged(f);
+      });
+    };
+    let clear_cb = ctx.link().callback(|_e: MouseEvent| {
+      return AppMsg::FilterChanged(SalesFilter::default());
+    });
+    let day = |d: Option<NaiveDate>| {
+      return d.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+    };
+    return html! {
+      <fieldset class="filter">
+        <b>{ tr("filter") }</b>
+        {
+          for sp.seller_names().into_iter().map(|name| {
+            let f = self.filter.clone();
+            let checked = f.sellers.contains(&name);
+            let label = name.clone();
+            let cb = ctx.link().callback(move |e: Event| {
+              let input: HtmlInputElement = e.target_unchecked_into();
+              let mut f = f.clone();
+              match input.checked() {
+                true => f.sellers.insert(name.clone()),
+                false => f.sellers.remove(&name),
+              };
+              return AppMsg::FilterChanged(f);
+            });
+            html_nested! {
+              <label>
+                <input type="checkbox" onchange={cb} checked={checked} />
+                { " " }{ label }{ " " }
+              </label>
+            }
+          })
+        }
+        <br />
+        { tr("from") }
+        <input
+          type="date"
+          onchange={date_cb(false)}
+          value={day(self.filter.from)}
+        />
+        { tr("until") }
+        <input
+          type="date"
+          onchange={date_cb(true)}
+          value={day(self.filter.until)}
+        />
+        { " " }
+        <button onclick={clear_cb} disabled={self.filter.is_empty()}>
+          { tr("clear_filter") }
+        </button>
+      </fieldset>
+    };
+  }
+
+  /// The gross/net checkbox. Only the money changes, so it's just a redo of
+  /// the report, like the filter.
+  fn view_revenue(&self, ctx: &yew::Context<Self>) -> Html {
+    let cb = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return AppMsg::RevenueToggled(input.checked());
+    });
+    return html! {
+      <label class="revenue">
+        <input
+          type="checkbox"
+          onchange={cb}
+          checked={self.revenue == Revenue::Net}
+        />
+        { " " }{ tr("net_values") }
+      </label>
+    };
+  }
+
+  /// The unresolved sales that pass the filter, each with a box to mark it
+  /// as reviewed. Reviewed ones don't count as needing attention, and
+  /// neither do zero-value ones.
+  fn view_unresolved(&self, ctx: &yew::Context<Self>, sp: &SalesPlus) -> Html {
+    let unresolved: Vec<&SalePlus> = sp.sales.iter()
+      .filter(|s| s.pricematch.is_none() && !s.is_free())
+      .filter(|s| self.filter.matches(s, &sp.context))
+      .collect();
+    let pending = unresolved.iter()
+      .filter(|s| !self.reviewed.contains(&s.sale.sale_id))
+      .count();
+    return html! {
+      <details class="unresolved">
+        <summary>
+          {
+            trf(
+              "needs_attention",
+              &[&pending, &(unresolved.len() - pending)]
+            )
+          }
+        </summary>
+        <table>
+          <tr>
+            <th>{ tr("reviewed") }</th>
+            <th>{ tr("h_id") }</th>
+            <th>{ tr("h_date") }</th>
+            <th>{ tr("h_value") }</th>
+            <th>{ tr("h_seller_name") }</th>
+            <th>{ tr("h_alternatives") }</th>
+          </tr>
+          {
+            for unresolved.into_iter().map(|s| {
+              let id = s.sale.sale_id.clone();
+              let checked = self.reviewed.contains(&id);
+              let cb = ctx.link().callback(move |_e: Event| {
+                return AppMsg::ToggleReviewed(id.clone());
+              });
+              html_nested! {
+                <tr class={ s.status().class() }>
+                  <td>
+                    <input type="checkbox" onchange={cb} checked={checked} />
+                  </td>
+                  <td>{ &s.sale.sale_id }</td>
+                  <td>{ s.sale.local_when(&sp.context) }</td>
+                  <td>{ format::money(sp.revenue.of(s)) }</td>
+                  <td>{ s.sale.seller_name.as_deref().unwrap_or_default() }</td>
+                  <td>{ s.alternatives() }</td>
+                </tr>
+              }
+            })
+          }
+        </table>
+      </details>
+    };
+  }
+
+  /// Checks the pasted CSV's header against the context again. Returns
+  /// whether anything changed.
+  fn sniff(&mut self) -> bool {
+    let headers = match (&self.context, &self.csv_txt, self.has_headers) {
+      (Some(c), Some(txt), true) => sniff_headers(txt, self.quote, c),
+      _ => HeaderInfo::default(),
+    };
+    let changed = headers != self.headers;
+    self.headers = headers;
+    return changed;
+  }
+
+  /// Goes back to the input, keeping the sales from a finished run around
+  /// to compare the next one against.
+  fn shelve_run(&mut self) {
+    let state = std::mem::replace(&mut self.state, AppState::Input);
+    if let AppState::Loaded(sp, _) = state {
+      self.previous = Some(sp);
+    }
+  }
+
+  /// The box for comparing with the previous run and, if it's checked, what
+  /// changed since then: counts by kind of change, then the sales.
+  fn view_compare(&self, ctx: &yew::Context<Self>, sp: &SalesPlus) -> Html {
+    let prev = match &self.previous {
+      Some(prev) => prev,
+      None => return html! {},
+    };
+    let cb = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return AppMsg::CompareToggled(input.checked());
+    });
+    let toggle = html! {
+      <label>
+        <input type="checkbox" onchange={cb} checked={self.compare} />
+        { " " }{ tr("compare_previous") }
+      </label>
+    };
+    if !self.compare {
+      return html! { <fieldset class="compare">{ toggle }</fieldset> };
+    }
+    let diff = RunDiff::between(prev, sp);
+    let table = match diff.changes.is_empty() {
+      true => html! {},
+      false => html! {
+        <table>
+          <tr>
+            <th>{ tr("h_id") }</th>
+            <th>{ tr("h_change") }</th>
+            <th>{ tr("h_before") }</th>
+            <th>{ tr("h_after") }</th>
+          </tr>
+          {
+            for diff.changes.iter().map(|sc| {
+              html_nested! {
+                <tr>
+                  <td>{ &sc.sale_id }</td>
+                  <td>{ sc.change }</td>
+                  <td>{ &sc.before }</td>
+                  <td>{ &sc.after }</td>
+                </tr>
+              }
+            })
+          }
+        </table>
+      }
+    };
+    return html! {
+      <fieldset class="compare">
+        { toggle }
+        <ul>
+          {
+            match diff.changes.is_empty() {
+              true => html! { <li>{ tr("diff_none") }</li> },
+              false => html! {}
+            }
+          }
+          {
+            for diff.counts().into_iter().map(|(change, n)| {
+              html_nested! { <li>{ format!("{}: {}", change, n) }</li> }
+            })
+          }
+          {
+            match diff.gone + diff.new > 0 {
+              true => html! {
+                <li>{ trf("diff_unmatched", &[&diff.gone, &diff.new]) }</li>
+              },
+              false => html! {}
+            }
+          }
+        </ul>
+        { table }
+      </fieldset>
+    };
+  }
+
+  /// Redoes the report fields for the current filter and gross/net choice.
+  /// The solvers already ran on everything, so that's all there is to do.
+  fn recompute_report(&mut self) {
+    if let AppState::Loaded(sp, report) = &mut self.state {
+      sp.revenue = self.revenue;
+      let t = ReportTemplate::default();
+      *report = Rc::new(match self.filter.is_empty() {
+        true => t.compute(sp),
+        false => t.compute(&sp.filtered(&self.filter))
+      });
+    }
+  }
+
+  /// Schedules the next loader step, giving the browser a chance to render
+  /// and handle input first.
+  fn schedule_step(&mut self, ctx: &yew::Context<Self>) {
+    let link = ctx.link().clone();
+    self.timeout = Some(Timeout::new(0, move || {
+      link.send_message(AppMsg::LoadStep);
+    }));
+  }
 }
 
 impl Component for App {
   type Message = AppMsg;
   type Properties = ();
 
-  fn create(_ctx: &yew::Context<Self>) -> Self {
+  fn create(ctx: &yew::Context<Self>) -> Self {
+    let lang_cb = ctx.link().callback(AppMsg::LangChanged);
     return Self {
       context: None,
-      csv_txt: None,
-      state: AppState::Input
+      context_ok: false,
+      // only fills the textarea back in, the report waits for the button
+      csv_txt: SessionStorage::get(CSV_KEY).ok(),
+      quote: QuoteChar::default(),
+      has_headers: true,
+      headers: HeaderInfo::default(),
+      state: AppState::Input,
+      filter: SalesFilter::default(),
+      revenue: Revenue::default(),
+      rerun_solver: None,
+      cache: None,
+      previous: None,
+      compare: false,
+      reviewed: load_reviewed(),
+      loader: None,
+      timeout: None,
+      #[cfg(feature = "worker")]
+      bridge: None,
+      _lang: ctx.link().context::<Lang>(lang_cb).map(|(_, h)| h)
     };
   }
 
-  fn update(&mut self, _ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
+  fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
     let mut b = false;
     // log::info!("{:#?}", &self);
     match msg {
@@ -80,17 +617,154 @@ impl Component for App {
         b = true;
       },
       AppMsg::GotContext(ctx) => {
+        // the price checker wants the fresh context
+        b = self.context.as_ref() != Some(&ctx);
         self.context = Some(ctx);
+        b |= self.sniff();
+      },
+      AppMsg::ContextValidity(ok) => {
+        b = self.context_ok != ok;
+        self.context_ok = ok;
       },
       AppMsg::GotCsv(s) => {
+        let was_ready = self.not_ready_reason().is_none();
+        remember_csv(&s);
         self.csv_txt = Some(s);
+        b = was_ready != self.not_ready_reason().is_none();
+        b |= self.sniff();
+      },
+      AppMsg::ClearCsv => {
+        SessionStorage::delete(CSV_KEY);
+        self.csv_txt = None;
+        self.sniff();
+        b = true;
+      },
+      AppMsg::GotQuote(q) => {
+        self.quote = q;
+        b = self.sniff();
+      },
+      AppMsg::GotHasHeaders(h) => {
+        self.has_headers = h;
+        b = self.sniff();
+      },
+      AppMsg::FilterChanged(f) => {
+        self.filter = f;
+        self.recompute_report();
+        b = matches!(self.state, AppState::Loaded(_, _));
+      },
+      AppMsg::RevenueToggled(net) => {
+        self.revenue = match net {
+          true => Revenue::Net,
+          false => Revenue::Gross
+        };
+        self.recompute_report();
+        b = matches!(self.state, AppState::Loaded(_, _));
+      },
+      AppMsg::CompareToggled(c) => {
+        self.compare = c;
+        b = true;
+      },
+      AppMsg::ToggleReviewed(id) => {
+        if !self.reviewed.remove(&id) {
+          self.reviewed.insert(id);
+        }
+        store_reviewed(&self.reviewed);
+        b = true;
+      },
+      AppMsg::LangChanged(_) => {
+        // the report is made of words, so it has to be redone
+        self.recompute_report();
+        b = true;
+      },
+      AppMsg::RerunSolverPicked(solv) => {
+        self.rerun_solver = Some(solv);
+      },
+      AppMsg::Rerun => {
+        if let AppState::Loaded(sp, _) = &self.state {
+          let mut c = SalesContext::clone(&sp.context);
+          c.solver = self.rerun_solver.unwrap_or(c.solver);
+          // the sales are already parsed and sorted, no need for the CSV
+          let src = LoadSource::Parsed(
+            sp.sales.iter().map(|s| s.sale.clone()).collect(),
+            sp.rows,
+            sp.header_is_sale,
+            sp.headers.clone(),
+            sp.duplicates
+          );
+          // so the form shows this solver when going back
+          self.context = Some(c.clone());
+          self.shelve_run();
+          self.start_loading(ctx, c, src);
+          b = true;
+        }
+      },
+      AppMsg::BackToInput => {
+        // dropping these cancels any pending work
+        self.loader = None;
+        self.timeout = None;
+        #[cfg(feature = "worker")]
+        {
+          self.bridge = None;
+        }
+        self.shelve_run();
+        b = true;
       },
       AppMsg::TryReport => {
-        if let Some(sp) = self.try_load() {
-          self.state = AppState::Loaded(sp);
+        if let (Some(c), Some(txt)) = (&self.context, &self.csv_txt) {
+          let c = c.clone();
+          let src = LoadSource::Csv(txt.clone(), self.quote, self.has_headers);
+          self.start_loading(ctx, c, src);
+          b = true;
+        }
+      },
+      AppMsg::LoadStep => {
+        self.timeout = None;
+        if let Some(loader) = self.loader.as_mut() {
+          match loader.step() {
+            LoadStep::Progress(progress) => {
+              self.state = AppState::Working { progress };
+              self.schedule_step(ctx);
+            },
+            LoadStep::Done(sp, report, dude) => {
+              self.loader = None;
+              self.cache = Some(dude);
+              self.state = AppState::Loaded(*sp, Rc::new(*report));
+              // the loader reports gross, so net needs a redo
+              if self.revenue != Revenue::default() {
+                self.recompute_report();
+              }
+            },
+            LoadStep::Failed(ve) => {
+              self.loader = None;
+              self.state = AppState::Errors(ve);
+            },
+          }
           b = true;
         }
       },
+      // stragglers from a cancelled worker are ignored
+      #[cfg(feature = "worker")]
+      AppMsg::WorkerSaid(up) if self.bridge.is_some() => {
+        match up {
+          LoadUpdate::Progress(progress) => {
+            self.state = AppState::Working { progress };
+          },
+          LoadUpdate::Done(sp, report, dude) => {
+            self.bridge = None;
+            self.cache = Some(dude);
+            self.state = AppState::Loaded(*sp, Rc::new(*report));
+            // the loader reports gross, so net needs a redo
+            if self.revenue != Revenue::default() {
+              self.recompute_report();
+            }
+          },
+          LoadUpdate::Failed(vs) => {
+            self.bridge = None;
+            self.state = AppState::Errors(vs);
+          },
+        }
+        b = true;
+      },
       _ => {}
     }
     return b;
@@ -102,49 +776,217 @@ impl Component for App {
       let s = input.value();
       return Self::Message::GotCsv(s);
     });
+    let quote_cb = ctx.link().callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      let q = input.value().as_str().try_into().unwrap_or_default();
+      return Self::Message::GotQuote(q);
+    });
+    let headers_cb = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::GotHasHeaders(input.checked());
+    });
     let btn_cb = ctx.link().callback(|_e: MouseEvent| {
       return Self::Message::TryReport;
     });
+    let clear_cb = ctx.link().callback(|_e: MouseEvent| {
+      return Self::Message::ClearCsv;
+    });
+    let back_cb = ctx.link().callback(|_e: MouseEvent| {
+      return Self::Message::BackToInput;
+    });
     return match &self.state {
       AppState::Input => {
+        let initial = self.context.as_ref().map(ContextInputData::from);
+        let not_ready = self.not_ready_reason();
+        let ctx_cb = ctx.link().callback(Self::Message::GotContext);
+        let valid_cb = ctx.link().callback(Self::Message::ContextValidity);
         html! {
           <div class="app-input">
-            <ContextInput />
+            <ContextInput
+              initial={ initial }
+              on_context={ ctx_cb }
+              on_validity={ valid_cb }
+              headers={ self.headers.clone() }
+            />
+            <details>
+              <summary>{ tr("check_price") }</summary>
+              <PriceCheck
+                context={ self.context.clone().filter(|_| self.context_ok) }
+              />
+            </details>
+            <br />
+            { tr("csv_quotes") }
+            <select onchange={quote_cb}>
+              {
+                for QuoteChar::available().map(|q| {
+                  html_nested! {
+                    <option value={ q.name() } selected={ q == self.quote }>
+                      { q }
+                    </option>
+                  }
+                })
+              }
+            </select>
             <br />
+            <input
+              type="checkbox"
+              onchange={headers_cb}
+              checked={self.has_headers}
+            />
+            { tr("csv_has_header") }
             <br />
-            <textarea onchange={csv_cb} class="csv-in" />
             <br />
-            <button onclick={btn_cb}>{ "bora" }</button>
+            <textarea
+              onchange={csv_cb}
+              class="csv-in"
+              value={ self.csv_txt.clone().unwrap_or_default() }
+            />
+            <br />
+            <button onclick={btn_cb} disabled={not_ready.is_some()}>
+              { tr("go") }
+            </button>
+            {
+              match not_ready {
+                Some(why) => html! { <i>{ " (" }{ why }{ ")" }</i> },
+                None => html! {}
+              }
+            }
+            { " " }
+            <button onclick={clear_cb} disabled={self.csv_txt.is_none()}>
+              { tr("clear") }
+            </button>
           </div>
         }
       },
       AppState::Errors(ref v) => {
         html! {
           <div class="app-errors">
-            { "deu ruim" }
+            { tr("went_wrong") }
             <br />
+            <button onclick={back_cb}>{ tr("back_and_fix") }</button>
             <br />
-            {
-              for v.iter().map(|e| {
-                html_nested! {
-                  <>
-                    <pre><code>{ e }</code></pre>
-                    <br />
-                    <br />
-                  </>
-                }
-              })
-            }
+            <br />
+            { for group_errors(v).iter().map(Self::view_error_group) }
           </div>
         }
       },
-      AppState::Loaded(sp) => {
+      AppState::Working { progress } => {
+        html! {
+          <div class="app-working">
+            <div class="spinner" />
+            <p>{ progress }</p>
+            <button onclick={back_cb}>{ tr("cancel") }</button>
+          </div>
+        }
+      },
+      AppState::Loaded(sp, report) => {
         html! {
           <div class="app-report">
-            <ReportDisplay ..ReportTemplate::default().compute(&sp) />
+            <button onclick={back_cb}>{ tr("back") }</button>
+            { Self::view_interrupted(sp) }
+            { Self::view_misconfigured(ctx, sp) }
+            { self.view_rerun(ctx, sp) }
+            { self.view_filter(ctx, sp) }
+            { self.view_revenue(ctx) }
+            { self.view_unresolved(ctx, sp) }
+            { self.view_compare(ctx, sp) }
+            <ReportDisplay report={ report.clone() } />
           </div>
         }
       },
     }
   }
 }
+
+/// The whole flow in a browser, with the loader on the main thread (there's
+/// no worker script to spawn in there). Run with
+/// `wasm-pack test --headless --firefox -- --no-default-features`.
+#[cfg(all(test, target_arch = "wasm32", not(feature = "worker")))]
+mod tests {
+  use gloo_timers::future::TimeoutFuture;
+  use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+  use web_sys::{Element, HtmlElement};
+  use yew::AppHandle;
+  use super::*;
+
+  wasm_bindgen_test_configure!(run_in_browser);
+
+  /// Five sales: a promo and a 1st batch ticket online, a promo + 1st batch
+  /// combo online, and two 2nd batch tickets from a point of sale.
+  static CSV: &str = concat!(
+    "Data Compra,Email Usuario,Nome Usuario,Valor,Status,Vendedor,",
+    "ID Vendedor,Email Vendedor,Token,ID,Nome Cartao,Prim Digitos Cartao,",
+    "Ult Digitos Cartao\n",
+    "2022-10-01T12:00:00Z,a@d4.com,a,60.5,Paga Online,N/A,N/A,N/A,",
+    "tk1,id1,N/A,N/A,N/A\n",
+    "2022-10-01T12:05:00Z,b@d4.com,b,71.5,Paga Online,N/A,N/A,N/A,",
+    "tk2,id2,N/A,N/A,N/A\n",
+    "2022-10-01T12:10:00Z,c@d4.com,c,132,Paga Online,N/A,N/A,N/A,",
+    "tk3,id3,N/A,N/A,N/A\n",
+    "2022-10-01T12:15:00Z,d@d4.com,d,75,Paga Offline,Fulano,v1,f@d4.com,",
+    "tk4,id4,N/A,N/A,N/A\n",
+    "2022-10-01T12:20:00Z,e@d4.com,e,75,Paga Offline,Fulano,v1,f@d4.com,",
+    "tk5,id5,N/A,N/A,N/A\n"
+  );
+
+  /// Mounts a fresh app in its own div.
+  fn mount() -> (Element, AppHandle<App>) {
+    let doc = web_sys::window().unwrap().document().unwrap();
+    let div = doc.create_element("div").unwrap();
+    doc.body().unwrap().append_child(&div).unwrap();
+    let app = yew::start_app_in_element::<App>(div.clone());
+    return (div, app);
+  }
+
+  /// Hands the app a context and a CSV, like the form and the textarea
+  /// would, and hits the button.
+  fn submit(app: &AppHandle<App>, csv: &str) {
+    app.send_message(AppMsg::GotContext(SalesContext::default()));
+    app.send_message(AppMsg::ContextValidity(true));
+    app.send_message(AppMsg::GotCsv(csv.to_owned()));
+    app.send_message(AppMsg::TryReport);
+  }
+
+  /// Lets the loader's steps and the renders run until some element shows
+  /// up, or gives up after a while.
+  async fn wait_for(div: &Element, selector: &str) -> bool {
+    for _ in 0..200 {
+      if div.query_selector(selector).unwrap().is_some() {
+        return true;
+      }
+      TimeoutFuture::new(10).await;
+    }
+    return false;
+  }
+
+  #[wasm_bindgen_test]
+  async fn csv_to_report() {
+    let (div, app) = mount();
+    submit(&app, CSV);
+    assert!(wait_for(&div, ".app-report").await);
+    let html = div.inner_html();
+    assert!(html.contains(tr("total_sales")));
+    // the better CSV is behind its tab
+    let tabs = div.query_selector_all(".report-tabs button").unwrap();
+    let csv_tab = (0..tabs.length())
+      .filter_map(|i| tabs.get(i))
+      .filter_map(|n| n.dyn_into::<HtmlElement>().ok())
+      .find(|b| b.text_content().as_deref() == Some(tr("tab_csv")))
+      .unwrap();
+    csv_tab.click();
+    TimeoutFuture::new(10).await;
+    assert!(div.inner_html().contains("tk3"));
+  }
+
+  #[wasm_bindgen_test]
+  async fn wrong_delimiter_to_grouped_errors() {
+    let (div, app) = mount();
+    submit(&app, &CSV.replace(',', ";"));
+    assert!(wait_for(&div, ".app-errors").await);
+    let html = div.inner_html();
+    assert!(html.contains(tr("went_wrong")));
+    let columns = format!("5x {}", trf("err_columns", &[&1, &13]));
+    assert!(html.contains(&columns));
+    assert_eq!(div.query_selector_all(".error-group").unwrap().length(), 2);
+  }
+}
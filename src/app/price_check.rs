@@ -0,0 +1,146 @@
+//! A little tool to decode a single price, without any CSV. Handy for
+//! checking what the inference thinks of a weird value.
+
+use web_sys::{Event, HtmlInputElement};
+use yew::{Component, Properties, html, html_nested};
+use yew::context::ContextHandle;
+use yew::html::TargetCast;
+use d4csv_core::context::SalesContext;
+use d4csv_core::format;
+use d4csv_core::i18n::{Lang, tr, trf};
+use d4csv_core::sale::kind::SaleKind;
+use d4csv_core::sale::price_deriving::{PricingCandidate, PricingMatch};
+
+/// Properties for the price checker: the context to decode prices with.
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub(crate) struct PriceCheckProps {
+  /// No context, no decoding.
+  pub(crate) context: Option<SalesContext>
+}
+
+/// The events the price checker reacts to.
+// named like the other components' messages
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug)]
+pub(crate) enum PriceCheckMsg {
+  /// A change to the price, in reais.
+  PriceChanged(f64),
+  /// A change to the online toggle.
+  OnlineChanged(bool),
+  /// The page switched languages.
+  LangChanged(Lang)
+}
+
+/// The price checker component.
+pub(crate) struct PriceCheck {
+  /// Price as paid, in reais.
+  price: f64,
+  /// Whether the price includes the online fee.
+  online: bool,
+  /// Keeps us posted on language changes.
+  _lang: Option<ContextHandle<Lang>>
+}
+
+/// A short name for the kind of match.
+fn match_kind(pm: &PricingMatch) -> &'static str {
+  return match pm {
+    PricingMatch::Multiple(_) => tr("kind_single"),
+    PricingMatch::PromoCombo(_, _) => tr("kind_promo_combo"),
+    PricingMatch::TurnOfBatch(_, _) => tr("kind_turn"),
+    PricingMatch::PromoPlusTurn(_, _, _) => tr("kind_promo_turn"),
+    PricingMatch::MultiBatch(_) => tr("kind_multi"),
+  };
+}
+
+impl Component for PriceCheck {
+  type Message = PriceCheckMsg;
+  type Properties = PriceCheckProps;
+
+  fn create(ctx: &yew::Context<Self>) -> Self {
+    let lang_cb = ctx.link().callback(PriceCheckMsg::LangChanged);
+    return Self {
+      price: 0.0,
+      online: false,
+      _lang: ctx.link().context::<Lang>(lang_cb).map(|(_, h)| h)
+    };
+  }
+
+  fn update(&mut self, _ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      PriceCheckMsg::PriceChanged(x) => self.price = x,
+      PriceCheckMsg::OnlineChanged(o) => self.online = o,
+      PriceCheckMsg::LangChanged(_) => {},
+    }
+    return true;
+  }
+
+  fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+    let price_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return PriceCheckMsg::PriceChanged(input.value_as_number());
+    });
+    let online_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return PriceCheckMsg::OnlineChanged(input.checked());
+    });
+    let results = match &ctx.props().context {
+      None => html! { <i>{ tr("fix_context_first") }</i> },
+      Some(_) if self.price.is_nan() || self.price <= 0.0 => html! {},
+      Some(sctx) => {
+        let paid = (self.price * 100.0).round() as usize;
+        let real = match self.online {
+          true => SaleKind::Online(sctx.online_fee).undo_fee(paid),
+          false => SaleKind::Offline(sctx.offline_fee).undo_fee(paid)
+        };
+        let matches = PricingMatch::all_priced(real, sctx);
+        let verdict = match PricingCandidate::from_price(real, sctx) {
+          PricingCandidate::Precise(_) => tr("verdict_precise"),
+          PricingCandidate::Ambiguous(_) => tr("verdict_ambiguous"),
+          PricingCandidate::NoMatch => tr("verdict_none"),
+        };
+        html! {
+          <>
+            <i>
+              { trf("without_fee", &[&format::money(real), &verdict]) }
+            </i>
+            <br />
+            <table class="price-check">
+              <tr>
+                <th>{ tr("decoding") }</th>
+                <th>{ tr("tickets") }</th>
+                <th>{ tr("kind") }</th>
+              </tr>
+              {
+                for matches.iter().map(|pm| {
+                  html_nested! {
+                    <tr>
+                      <td>{ format!("{:#}", pm) }</td>
+                      <td>{ pm.tickets() }</td>
+                      <td>{ match_kind(pm) }</td>
+                    </tr>
+                  }
+                })
+              }
+            </table>
+          </>
+        }
+      },
+    };
+    return html! {
+      <div class="price-check">
+        { tr("price_reais") }
+        <input
+          type="number"
+          min=0
+          step=0.01
+          onchange={price_change}
+          value={Some(self.price.to_string())}
+        />
+        <input type="checkbox" onchange={online_change} checked={self.online} />
+        { tr("online_with_fee") }
+        <br />
+        { results }
+      </div>
+    };
+  }
+}
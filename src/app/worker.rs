@@ -0,0 +1,78 @@
+//! Runs the loader in a web worker, so the page stays responsive while big
+//! CSVs are crunched.
+
+use gloo_worker::{HandlerId, Worker, WorkerScope};
+use serde::{Deserialize, Serialize};
+use d4csv_core::budget::Budget;
+use d4csv_core::context::SalesContext;
+use d4csv_core::error::D4Error;
+use d4csv_core::i18n::Lang;
+use d4csv_core::loader::{Loader, LoadProgress, LoadSource, LoadStep};
+use d4csv_core::report::Report;
+use d4csv_core::sale::plus::SalesPlus;
+use d4csv_core::sale::price_deriving::PricingCandidateCache;
+use crate::app::{RUN_BUDGET_MS, now_ms};
+
+/// Where trunk puts the worker script.
+pub(crate) static WORKER_PATH: &str = "d4csv-worker.js";
+
+/// What the page asks the worker to do.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct LoadRequest {
+  /// The context, solver included.
+  pub(crate) context: SalesContext,
+  /// What to load.
+  pub(crate) source: LoadSource,
+  /// The pricing cache from the last run, if any.
+  pub(crate) cache: Option<PricingCandidateCache>,
+  /// The language the page is in, for the report to match.
+  pub(crate) lang: Lang
+}
+
+/// What the worker tells the page.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) enum LoadUpdate {
+  /// Still going.
+  Progress(LoadProgress),
+  /// All done. The sales come back too, for filtering and re-running, and
+  /// so does the pricing cache. Boxed like in the loader's step.
+  Done(Box<SalesPlus>, Box<Report>, PricingCandidateCache),
+  /// Nothing to report on.
+  Failed(Vec<D4Error>)
+}
+
+/// The worker itself. Runs one request to completion at a time.
+pub(crate) struct LoadWorker;
+
+impl Worker for LoadWorker {
+  type Message = ();
+  type Input = LoadRequest;
+  type Output = LoadUpdate;
+
+  fn create(_scope: &WorkerScope<Self>) -> Self {
+    return Self;
+  }
+
+  fn update(&mut self, _scope: &WorkerScope<Self>, _msg: Self::Message) {}
+
+  fn received(
+    &mut self, scope: &WorkerScope<Self>, req: Self::Input, id: HandlerId
+  ) {
+    // the worker has its own globals, so it learns the language here
+    req.lang.set_current();
+    let mut loader = Loader::new(req.context, req.source, req.cache)
+      .with_budget(Budget::new(now_ms, RUN_BUDGET_MS));
+    loop {
+      // no need to yield in here, we're off the main thread
+      let (up, done) = match loader.step() {
+        LoadStep::Progress(p) => (LoadUpdate::Progress(p), false),
+        LoadStep::Done(sp, report, dude) => {
+          (LoadUpdate::Done(sp, report, dude), true)
+        },
+        LoadStep::Failed(ve) => (LoadUpdate::Failed(ve), true),
+      };
+      scope.respond(id, up);
+      if done { break; }
+    }
+  }
+}
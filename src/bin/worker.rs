@@ -0,0 +1,6 @@
+//! Set up the loader worker.
+//! Not much to see here either.
+
+fn main() {
+  d4csv::start_worker();
+}
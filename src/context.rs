@@ -1,114 +1,108 @@
-//! Sale context that comes from outside the CSV.
+//! The form for the sale context, and remembering what went in it.
 
-use std::error::Error;
-use itertools::Itertools;
-use yew::{Component, Properties, html, html_nested};
-use yew::html::{TargetCast, Scope};
-use web_sys::{Event, HtmlInputElement};
-use crate::app::{App, AppMsg};
-use crate::sale::ambiguity::AmbiguitySolver;
-use crate::ticket::batch::{BatchPrices, iter2bp, bp2iter, Batch};
+pub(crate) mod presets;
+pub(crate) mod url;
 
-static WEBFEE_PRECISION: usize = 1000;
-static PRICES_SEPARATOR: &str = ";";
+use gloo_storage::{LocalStorage, Storage};
+use yew::{
+  Callback, Component, Html, Properties, html, html_nested
+};
+use yew::context::ContextHandle;
+use yew::html::TargetCast;
+use web_sys::{
+  Event, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, InputEvent,
+  MouseEvent
+};
+use d4csv_core::context::{
+  ContextField, ContextInputData, ContextWarning, SalesContext, offset_name,
+  parse_quick
+};
+use d4csv_core::error::D4Error;
+use d4csv_core::format;
+use d4csv_core::i18n::{Lang, tr, trf};
+use d4csv_core::sale::ambiguity::AmbiguitySolver;
+use d4csv_core::sale::columns::{Column, HeaderInfo};
+use d4csv_core::sale::dedup::DedupKey;
+use d4csv_core::sale::kind::{ForcedKind, SaleKind};
+use d4csv_core::sale::price_deriving::PricingMatch;
+use d4csv_core::ticket::batch::{bp2iter, Batch};
+use crate::context::presets::{
+  Presets, builtin_preset, builtin_presets, load_presets, store_presets,
+  preset_names
+};
 
-/// The context needed to derive ticket information from the CSV.
-#[derive(Clone, Debug)]
-pub(crate) struct SalesContext {
-  /// Online fee.
-  pub(crate) online_fee: (usize, usize),
-  /// Batch prices.
-  pub(crate) batches: BatchPrices,
-  /// Promo batch limit per person.type Error;
-  pub(crate) promo_limit: Option<usize>,
-  /// Ambiguity solver.
-  pub(crate) solver: AmbiguitySolver
-}
+static STORAGE_KEY: &str = "d4csv.context";
 
-impl Default for SalesContext {
-  /// Data from the 2022 D4.
-  fn default() -> Self {
-    Self {
-      online_fee: (11, 10),
-      batches: iter2bp(vec![5500, 6500, 7500, 8500].into_iter()),
-      promo_limit: Some(1),
-      solver: AmbiguitySolver::SellerLookBehind
-    }
-  }
-}
-
-/// Context input as it comes from the document.
-#[derive(Clone, Debug, PartialEq, Properties)]
-pub(crate) struct ContextInputData {
-  webfee: f64,
-  prices: String,
-  promos: f64,
-  solver: String
+/// Loads the last good form data from localStorage, if it's there and it
+/// still makes sense.
+pub(crate) fn load_input() -> Option<ContextInputData> {
+  return match LocalStorage::get::<ContextInputData>(STORAGE_KEY) {
+    Ok(data) => match SalesContext::try_from(data.clone()) {
+      Ok(_) => Some(data),
+      Err(e) => {
+        log::warn!("ignoring stored context: {}", e);
+        None
+      }
+    },
+    Err(_) => None
+  };
 }
 
-impl TryFrom<ContextInputData> for SalesContext {
-  type Error = Box<dyn Error>;
-
-  fn try_from(data: ContextInputData) -> Result<Self, Self::Error> {
-    let mut cents: Vec<usize> = Vec::new();
-    let ic = data.prices
-      .split(PRICES_SEPARATOR)
-      .map(|s| str::parse::<f64>(s));
-    for c in ic {
-      match c {
-        Ok(f) => cents.push((f * 100.0) as usize),
-        Err(_) => return Err(
-          format!(
-            "preços inválidos! faça tipo: 55;65;77.5;100.0;101"
-          ).into()
-        )
-      }
-    }
-    return Ok(Self {
-      online_fee: (
-        ((data.webfee + 1.0) * (WEBFEE_PRECISION as f64)) as usize,
-        WEBFEE_PRECISION
-      ),
-      batches: iter2bp(cents.into_iter()),
-      promo_limit: {
-        if data.promos != 0.0 {
-          Some(data.promos as usize)
-        } else {
-          None
-        }
-      },
-      solver: data.solver.as_str().try_into().unwrap_or_default()
-    });
+/// Saves some form data to localStorage.
+pub(crate) fn store_input(data: &ContextInputData) {
+  if let Err(e) = LocalStorage::set(STORAGE_KEY, data) {
+    log::warn!("couldn't store context: {}", e);
   }
 }
 
-impl From<&SalesContext> for ContextInputData {
-  fn from(ctx: &SalesContext) -> Self {
-    let mut bps: Vec<Batch> = bp2iter(&ctx.batches).collect();
-    bps.sort_by_key(|b| b.num);
-    return Self {
-      webfee: (ctx.online_fee.0 as f64) / (ctx.online_fee.1 as f64) - 1.0,
-      prices: bps.into_iter()
-        .map(|b| (b.price as f64)/100.0)
-        .join(PRICES_SEPARATOR),
-      promos: match ctx.promo_limit {
-        Some(n) => n as f64,
-        None => 0.0,
-      },
-      solver: ctx.solver.name().to_owned()
-    }
-  }
+/// Forgets whatever form data was stored.
+pub(crate) fn forget_input() {
+  LocalStorage::delete(STORAGE_KEY);
 }
 
-impl Default for ContextInputData {
-  fn default() -> Self {
-    return (&SalesContext::default()).into();
-  }
+/// Properties for the context input: where to start from, and where to send
+/// the parsed context.
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub(crate) struct ContextInputProps {
+  /// Initial form data, if there's any (e.g. when coming back from a report).
+  #[prop_or_default]
+  pub(crate) initial: Option<ContextInputData>,
+  /// Called with every context that parses successfully.
+  pub(crate) on_context: Callback<SalesContext>,
+  /// Called with whether the form currently parses into a context.
+  #[prop_or_default]
+  pub(crate) on_validity: Callback<bool>,
+  /// What didn't match in the pasted CSV's header, for mapping by hand.
+  #[prop_or_default]
+  pub(crate) headers: HeaderInfo
 }
 
 /// A component for the user to input context info.
 pub(crate) struct ContextInput {
-  data: ContextInputData
+  data: ContextInputData,
+  /// Saved presets, by name.
+  presets: Presets,
+  /// Name typed in for saving a preset.
+  preset_name: String,
+  /// The preset currently picked in the dropdown.
+  preset_selected: Option<String>,
+  /// What was wrong with the form, last we checked.
+  error: Option<D4Error>,
+  /// What looked odd about the context, last time it parsed.
+  warnings: Vec<ContextWarning>,
+  /// The quick-entry price list, as typed.
+  quick: String,
+  /// What was wrong with the quick-entry price list.
+  quick_error: Option<String>,
+  /// A context JSON pasted in for importing.
+  import_txt: String,
+  /// What was wrong with the last import.
+  import_error: Option<String>,
+  /// Price typed in for a new manual rule, in the form's unit (without the
+  /// fee).
+  override_price: f64,
+  /// Keeps us posted on language changes.
+  _lang: Option<ContextHandle<Lang>>
 }
 
 /// The events the context input reacts to.
@@ -116,53 +110,278 @@ pub(crate) struct ContextInput {
 pub(crate) enum ContextInputMsg {
   /// A change to the web fee number.
   WebfeeChanged(f64),
-  /// A change to the batch prices list.
-  PricesChanged(String),
+  /// A change to the offline fee number.
+  OfflineFeeChanged(f64),
+  /// A change to the centavos toggle.
+  CentsChanged(bool),
+  /// A change to the price in a batch row.
+  RowPriceChanged(usize, f64),
+  /// A change to the capacity in a batch row.
+  RowCapacityChanged(usize, f64),
+  /// A change to the promo batch capacity.
+  PromoCapacityChanged(f64),
+  /// Add a batch row at the end.
+  AddRow,
+  /// Remove a batch row.
+  RemoveRow(usize),
+  /// A keystroke in the quick-entry price list: only checks it.
+  QuickTyped(String),
+  /// A change to the quick-entry price list: checks and applies it.
+  QuickChanged(String),
+  /// A change to the promo batch toggle.
+  PromoEnabledChanged(bool),
+  /// A change to the promo batch price.
+  PromoPriceChanged(f64),
   /// A change to the promo limits.
   PromosChanged(f64),
+  /// A change to the unlimited promo toggle.
+  PromoUnlimitedChanged(bool),
+  /// A change to the promo combo toggle.
+  PromoComboChanged(bool),
+  /// A change to the most batches a purchase may span.
+  MaxSpanChanged(f64),
   /// A change to the ambiguity solver selection.
-  SolverChanged(String)
+  SolverChanged(String),
+  /// A change to the online-per-day toggle.
+  OnlinePerDayChanged(bool),
+  /// A change to whether sales are all forced online or offline.
+  ForcedKindChanged(ForcedKind),
+  /// A change to what tells repeated sales apart.
+  DedupKeyChanged(DedupKey),
+  /// A change to whether zero-value sales count as a ticket.
+  FreeTicketsChanged(bool),
+  /// A change to the date format.
+  DateFormatChanged(String),
+  /// A change to the time zone, in hours from UTC.
+  TimezoneChanged(f64),
+  /// A change to the offline seller commission.
+  CommissionChanged(f64),
+  /// A change to the commission-as-percentage toggle.
+  CommissionPercentChanged(bool),
+  /// Throw away the stored data and go back to the defaults.
+  ResetDefaults,
+  /// A change to the name to save a preset as.
+  PresetNameChanged(String),
+  /// A preset was picked from the dropdown.
+  PresetSelected(String),
+  /// Save the current form as a preset.
+  SavePreset,
+  /// Delete the preset picked in the dropdown.
+  DeletePreset,
+  /// A change to the pasted context JSON.
+  ImportTyped(String),
+  /// Replace the form with the pasted context JSON.
+  ImportContext,
+  /// A change to the price for a new manual rule.
+  OverridePriceChanged(f64),
+  /// Always take a price (in cents, without the fee) as some match.
+  AddOverride(usize, PricingMatch),
+  /// Drop the manual rule for a price.
+  RemoveOverride(usize),
+  /// Take a CSV header as some sale field.
+  MapColumn(String, Column),
+  /// Forget what a CSV header was taken as.
+  UnmapColumn(String),
+  /// The page switched languages.
+  LangChanged(Lang)
 }
 
 impl ContextInput {
-  /// Try and send the context upward.
-  fn send_up(&self, ctx: &yew::Context<Self>) {
-    if let Ok(sc) = self.try_get_context() {
-      if let Some(scope) = ctx.link().get_parent() {
-        let app: Scope<App> = scope.clone().downcast::<App>();
-        app.send_message(AppMsg::GotContext(sc));
+  /// Try and send the context upward. Remembers the error if it fails.
+  fn send_up(&mut self, ctx: &yew::Context<Self>) {
+    match self.try_get_context() {
+      Ok(sc) => {
+        self.error = None;
+        self.warnings = sc.validate();
+        store_input(&self.data);
+        url::replace_location(&self.data);
+        ctx.props().on_validity.emit(true);
+        ctx.props().on_context.emit(sc);
+      },
+      Err(e) => {
+        self.error = Some(e);
+        self.warnings.clear();
+        ctx.props().on_validity.emit(false);
       }
     }
   }
+
+  /// Whole-hour offsets for the time zone dropdown, plus the current one if
+  /// it came from somewhere else (a URL, say) with minutes in it.
+  fn timezone_options(&self) -> Vec<f64> {
+    let mut v: Vec<f64> = (-12..=14).map(|h| h as f64).collect();
+    if !v.contains(&self.data.utc_offset) {
+      v.push(self.data.utc_offset);
+      v.sort_by(|a, b| a.total_cmp(b));
+    }
+    return v;
+  }
+
+  /// Shows the last error in red, and any warnings in orange, if they were
+  /// about this field.
+  fn field_error(&self, field: ContextField) -> Html {
+    let error = match &self.error {
+      Some(D4Error::Context(e)) if e.field == field => html! {
+        <>
+          <br />
+          <span class="field-error" style="color: red;">{ &e.msg }</span>
+        </>
+      },
+      _ => html! {}
+    };
+    return html! {
+      <>
+        { error }
+        {
+          for self.warnings.iter().filter(|w| w.field() == field).map(|w| {
+            html_nested! {
+              <>
+                <br />
+                <span class="field-warning" style="color: orange;">
+                  { w }
+                </span>
+              </>
+            }
+          })
+        }
+      </>
+    };
+  }
 }
 
 impl Component for ContextInput {
   type Message = ContextInputMsg;
-  type Properties = ();
+  type Properties = ContextInputProps;
 
   fn create(ctx: &yew::Context<Self>) -> Self {
-    let s = Self {
-      data: ContextInputData::default()
+    let mut s = Self {
+      data: ctx.props().initial.clone()
+        .or_else(url::from_location)
+        .or_else(load_input)
+        .unwrap_or_default(),
+      presets: load_presets(),
+      preset_name: String::new(),
+      preset_selected: None,
+      error: None,
+      warnings: Vec::new(),
+      quick: String::new(),
+      quick_error: None,
+      import_txt: String::new(),
+      import_error: None,
+      override_price: 0.0,
+      _lang: ctx.link()
+        .context::<Lang>(ctx.link().callback(ContextInputMsg::LangChanged))
+        .map(|(_, h)| h)
     };
+    s.quick = s.data.quick_prices();
     s.send_up(ctx);
     return s;
   }
 
   fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
-    let b = false;
+    let mut b = false;
     match msg {
       ContextInputMsg::WebfeeChanged(x) => {
         if self.data.webfee != x {
           self.data.webfee = x;
-          // b = true;
+          b = true;
         }
       },
-      ContextInputMsg::PricesChanged(s) => {
-        if self.data.prices != s {
-          self.data.prices = s;
-          // b = true;
+      ContextInputMsg::OfflineFeeChanged(x) => {
+        if self.data.offline_fee != x {
+          self.data.offline_fee = x;
+          b = true;
         }
       },
+      ContextInputMsg::CentsChanged(c) => {
+        if self.data.cents != c {
+          // same prices, other unit
+          let conv = |x: f64| match c {
+            true => (x * 100.0).round(),
+            false => x / 100.0
+          };
+          self.data.rows.iter_mut().for_each(|p| *p = conv(*p));
+          self.data.promo_price = conv(self.data.promo_price);
+          self.override_price = conv(self.override_price);
+          if !self.data.commission_percent {
+            self.data.commission = conv(self.data.commission);
+          }
+          self.data.cents = c;
+          self.quick = self.data.quick_prices();
+          b = true;
+        }
+      },
+      ContextInputMsg::RowPriceChanged(i, x) => {
+        if let Some(row) = self.data.rows.get_mut(i) {
+          *row = x;
+          b = true;
+        }
+      },
+      ContextInputMsg::RowCapacityChanged(i, x) => {
+        if self.data.capacities.len() <= i {
+          self.data.capacities.resize(i + 1, 0.0);
+        }
+        self.data.capacities[i] = x;
+        b = true;
+      },
+      ContextInputMsg::PromoCapacityChanged(x) => {
+        self.data.promo_capacity = x;
+        b = true;
+      },
+      ContextInputMsg::AddRow => {
+        let price = self.data.rows.last().copied().unwrap_or(0.0);
+        self.data.rows.push(price);
+        b = true;
+      },
+      ContextInputMsg::RemoveRow(i) => {
+        if i < self.data.rows.len() {
+          self.data.rows.remove(i);
+          if i < self.data.capacities.len() {
+            self.data.capacities.remove(i);
+          }
+          b = true;
+        }
+      },
+      ContextInputMsg::QuickTyped(s) => {
+        let err = parse_quick(&s).err();
+        b = err != self.quick_error;
+        self.quick_error = err;
+        self.quick = s;
+      },
+      ContextInputMsg::QuickChanged(s) => {
+        // the first price is the promo batch, like it's always been
+        match parse_quick(&s) {
+          Ok(v) => {
+            self.data.promo_enabled = true;
+            self.data.promo_price = v[0];
+            self.data.rows = v[1..].to_vec();
+            self.quick_error = None;
+          },
+          Err(e) => self.quick_error = Some(e),
+        }
+        self.quick = s;
+        b = true;
+      },
+      ContextInputMsg::PromoEnabledChanged(e) => {
+        self.data.promo_enabled = e;
+        b = true;
+      },
+      ContextInputMsg::PromoPriceChanged(x) => {
+        self.data.promo_price = x;
+        b = true;
+      },
+      ContextInputMsg::PromoUnlimitedChanged(u) => {
+        self.data.promo_unlimited = u;
+        b = true;
+      },
+      ContextInputMsg::PromoComboChanged(c) => {
+        self.data.promo_combo = c;
+        b = true;
+      },
+      ContextInputMsg::MaxSpanChanged(x) => {
+        self.data.max_span = x;
+        b = true;
+      },
       ContextInputMsg::PromosChanged(x) => {
         if self.data.promos != x {
           self.data.promos = x;
@@ -174,10 +393,115 @@ impl Component for ContextInput {
           self.data.solver = sn;
         }
       },
-      
+      ContextInputMsg::OnlinePerDayChanged(d) => {
+        self.data.online_per_day = d;
+      },
+      ContextInputMsg::ForcedKindChanged(fk) => {
+        self.data.forced_kind = fk;
+      },
+      ContextInputMsg::DedupKeyChanged(dk) => {
+        self.data.dedup_key = dk;
+      },
+      ContextInputMsg::FreeTicketsChanged(f) => {
+        self.data.free_tickets = f;
+      },
+      ContextInputMsg::DateFormatChanged(f) => {
+        self.data.date_format = f;
+      },
+      ContextInputMsg::TimezoneChanged(h) => {
+        self.data.utc_offset = h;
+      },
+      ContextInputMsg::CommissionChanged(x) => {
+        self.data.commission = x;
+      },
+      ContextInputMsg::CommissionPercentChanged(p) => {
+        self.data.commission_percent = p;
+        b = true;
+      },
+      ContextInputMsg::ResetDefaults => {
+        forget_input();
+        self.data = ContextInputData::default();
+        b = true;
+      },
+      ContextInputMsg::PresetNameChanged(s) => {
+        self.preset_name = s;
+      },
+      ContextInputMsg::PresetSelected(name) => {
+        let picked = self.presets.get(&name).cloned()
+          .or_else(|| builtin_preset(&name));
+        if let Some(data) = picked {
+          self.data = data;
+          self.preset_selected = Some(name);
+          b = true;
+        } else {
+          self.preset_selected = None;
+        }
+      },
+      ContextInputMsg::SavePreset => {
+        let name = self.preset_name.trim().to_owned();
+        if name.is_empty() {
+          alert(tr("ctx_name_preset"));
+        } else if builtin_preset(&name).is_some() {
+          alert(&trf("ctx_preset_builtin", &[&name]));
+        } else if !self.presets.contains_key(&name) || confirm(
+          &trf("ctx_preset_exists", &[&name])
+        ) {
+          self.presets.insert(name.clone(), self.data.clone());
+          store_presets(&self.presets);
+          self.preset_selected = Some(name);
+          b = true;
+        }
+      },
+      ContextInputMsg::DeletePreset => {
+        if let Some(name) = self.preset_selected.take() {
+          self.presets.remove(&name);
+          store_presets(&self.presets);
+          b = true;
+        }
+      },
+      ContextInputMsg::ImportTyped(s) => {
+        self.import_txt = s;
+      },
+      ContextInputMsg::ImportContext => {
+        match SalesContext::from_json(&self.import_txt) {
+          Ok(sc) => {
+            self.data = ContextInputData::from(&sc);
+            self.preset_selected = None;
+            self.import_txt.clear();
+            self.import_error = None;
+          },
+          Err(e) => self.import_error = Some(e),
+        }
+        b = true;
+      },
+      ContextInputMsg::OverridePriceChanged(x) => {
+        self.override_price = x;
+        b = true;
+      },
+      ContextInputMsg::AddOverride(price, pm) => {
+        self.data.overrides.insert(price, pm);
+        b = true;
+      },
+      ContextInputMsg::RemoveOverride(price) => {
+        self.data.overrides.remove(&price);
+        b = true;
+      },
+      ContextInputMsg::MapColumn(header, c) => {
+        self.data.columns.insert(header, c);
+        b = true;
+      },
+      ContextInputMsg::UnmapColumn(header) => {
+        self.data.columns.remove(&header);
+        b = true;
+      },
+      ContextInputMsg::LangChanged(_) => {
+        // the error messages get redone in the new language below
+        b = true;
+      },
     }
+    let had_error = self.error.clone();
     self.send_up(ctx);
-    return b;
+    return b || had_error != self.error;
   }
 
   fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
@@ -186,57 +510,398 @@ impl Component for ContextInput {
       let v = input.value_as_number();
       return Self::Message::WebfeeChanged(v);
     });
-    let prices_change = ctx.link().callback(|e: Event| {
+    let offline_fee_change = ctx.link().callback(|e: Event| {
       let input: HtmlInputElement = e.target_unchecked_into();
-      let v = input.value();
-      return Self::Message::PricesChanged(v);
+      let v = input.value_as_number();
+      return Self::Message::OfflineFeeChanged(v);
+    });
+    let cents_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::CentsChanged(input.checked());
+    });
+    let quick_input = ctx.link().callback(|e: InputEvent| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::QuickTyped(input.value());
+    });
+    let quick_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::QuickChanged(input.value());
+    });
+    let add_row = ctx.link().callback(|_e: MouseEvent| {
+      return Self::Message::AddRow;
+    });
+    let promo_enabled_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PromoEnabledChanged(input.checked());
+    });
+    let promo_price_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PromoPriceChanged(input.value_as_number());
+    });
+    let promo_capacity_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PromoCapacityChanged(input.value_as_number());
+    });
+    let promo_unlimited_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PromoUnlimitedChanged(input.checked());
+    });
+    let promo_combo_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PromoComboChanged(input.checked());
     });
     let promos_change = ctx.link().callback(|e: Event| {
       let input: HtmlInputElement = e.target_unchecked_into();
       let v = input.value_as_number();
       return Self::Message::PromosChanged(v);
     });
+    let max_span_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::MaxSpanChanged(input.value_as_number());
+    });
+    let date_format_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::DateFormatChanged(input.value());
+    });
+    let commission_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::CommissionChanged(input.value_as_number());
+    });
+    let commission_pct_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::CommissionPercentChanged(input.checked());
+    });
+    let reset_click = ctx.link().callback(|e: MouseEvent| {
+      e.prevent_default();
+      return Self::Message::ResetDefaults;
+    });
+    let per_day_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::OnlinePerDayChanged(input.checked());
+    });
+    let free_tickets_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::FreeTicketsChanged(input.checked());
+    });
+    let forced_kind_change = ctx.link().batch_callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      return ForcedKind::try_from(input.value().as_str()).ok()
+        .map(Self::Message::ForcedKindChanged);
+    });
+    let dedup_change = ctx.link().batch_callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      return DedupKey::try_from(input.value().as_str()).ok()
+        .map(Self::Message::DedupKeyChanged);
+    });
+    let timezone_change = ctx.link().callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      let h = input.value().parse::<f64>().unwrap_or(-3.0);
+      return Self::Message::TimezoneChanged(h);
+    });
     let solver_change = ctx.link().callback(|e: Event| {
       let input: HtmlInputElement = e.target_unchecked_into();
       let v = input.value();
       return Self::Message::SolverChanged(v);
     });
+    let preset_select = ctx.link().callback(|e: Event| {
+      let input: HtmlSelectElement = e.target_unchecked_into();
+      return Self::Message::PresetSelected(input.value());
+    });
+    let preset_name_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return Self::Message::PresetNameChanged(input.value());
+    });
+    let preset_save = ctx.link().callback(|_e: MouseEvent| {
+      return Self::Message::SavePreset;
+    });
+    let preset_delete = ctx.link().callback(|_e: MouseEvent| {
+      return Self::Message::DeletePreset;
+    });
+    // built-in presets can't be deleted
+    let deletable = self.preset_selected.as_ref()
+      .map(|name| self.presets.contains_key(name))
+      .unwrap_or(false);
     return html! {
       <div id="context-form">
-        { "taxa web:" }
+        { tr("preset") }
+        <select onchange={ preset_select }>
+          <option value="" selected={ self.preset_selected.is_none() }>
+            { tr("preset_none") }
+          </option>
+          <optgroup label={ tr("presets_builtin") }>
+            {
+              for builtin_presets().into_iter().map(|(name, _)| {
+                let sel = self.preset_selected.as_deref() == Some(name);
+                html_nested! {
+                  <option value={ name } selected={ sel }>{ name }</option>
+                }
+              })
+            }
+          </optgroup>
+          <optgroup label={ tr("presets_saved") }>
+            {
+              for preset_names(&self.presets).into_iter().map(|name| {
+                let sel = self.preset_selected.as_ref() == Some(&name);
+                html_nested! {
+                  <option value={ name.clone() } selected={ sel }>
+                    { name }
+                  </option>
+                }
+              })
+            }
+          </optgroup>
+        </select>
+        <button onclick={ preset_delete } disabled={ !deletable }>
+          { tr("delete") }
+        </button>
+        <br />
+        <input
+          type="text"
+          placeholder={ tr("preset_name") }
+          onchange={ preset_name_change }
+          value={ self.preset_name.clone() }
+        />
+        <button onclick={ preset_save }>{ tr("save_as") }</button>
+        <br />
+        { self.view_import_export(ctx) }
+        <br />
+        { tr("webfee_label") }
         <input
           type="number"
           min=0 step=0.1
           onchange={webfee_change}
           value={Some(self.data.webfee.to_string())}
         />
+        { self.field_error(ContextField::Webfee) }
+        <br />
+        { tr("offline_fee_label") }
+        <input
+          type="number"
+          min=0 step=0.01
+          onchange={offline_fee_change}
+          value={Some(self.data.offline_fee.to_string())}
+        />
+        { self.field_error(ContextField::OfflineFee) }
+        { self.view_fee_preview() }
         <br />
-        { "preços dos lotes: " }
+        { tr("prices_label") }
+        <input
+          type="checkbox"
+          onchange={cents_change}
+          checked={self.data.cents}
+        />
+        { tr("cents_cb") }
+        <table class="price-rows">
+          <tr>
+            <th>{ tr("batch") }</th>
+            <th>{ self.unit_label("price_col", "price_col_cents") }</th>
+            <th>{ tr("capacity_col") }</th>
+            <th></th>
+          </tr>
+          {
+            for self.data.rows.iter().enumerate().map(|(i, price)| {
+              self.view_row(ctx, i, *price)
+            })
+          }
+        </table>
+        <button onclick={add_row}>{ tr("add_batch") }</button>
+        { self.field_error(ContextField::Prices) }
+        { self.field_error(ContextField::Capacities) }
+        <br />
+        { tr("quick_entry") }
         <input
           type="text"
-          onchange={prices_change}
-          value={Some(self.data.prices.clone())}
+          placeholder="55;65;77.5"
+          oninput={quick_input}
+          onchange={quick_change}
+          value={Some(self.quick.clone())}
         />
+        {
+          match &self.quick_error {
+            Some(e) => html! {
+              <>
+                <br />
+                <span class="field-error" style="color: red;">{ e }</span>
+              </>
+            },
+            None => html! {}
+          }
+        }
         <br />
-        { "promo/pessoa:" }
+        <fieldset class="promo">
+          <input
+            type="checkbox"
+            onchange={promo_enabled_change}
+            checked={self.data.promo_enabled}
+          />
+          { tr("promo_batch_cb") }
+          <br />
+          { self.unit_label("promo_price_label", "promo_price_cents") }
+          <input
+            type="number"
+            min=0
+            step={ self.price_step() }
+            disabled={!self.data.promo_enabled}
+            onchange={promo_price_change}
+            value={Some(self.data.promo_price.to_string())}
+          />
+          { self.field_error(ContextField::Promo) }
+          <br />
+          { tr("promo_capacity_label") }
+          <input
+            type="number"
+            min=0
+            step=1
+            disabled={!self.data.promo_enabled}
+            onchange={promo_capacity_change}
+            value={Some(self.data.promo_capacity.to_string())}
+          />
+          <br />
+          { tr("promos_label") }
+          <input
+            type="number"
+            min=1
+            step=1
+            disabled={!self.data.promo_enabled || self.data.promo_unlimited}
+            onchange={promos_change}
+            value={Some(self.data.promos.to_string())}
+          />
+          <input
+            type="checkbox"
+            disabled={!self.data.promo_enabled}
+            onchange={promo_unlimited_change}
+            checked={self.data.promo_unlimited}
+          />
+          { tr("unlimited_cb") }
+          { self.field_error(ContextField::Promos) }
+          <br />
+          <input
+            type="checkbox"
+            disabled={!self.data.promo_enabled}
+            onchange={promo_combo_change}
+            checked={self.data.promo_combo}
+          />
+          { tr("combo_cb") }
+        </fieldset>
+        { tr("max_span_label") }
         <input
           type="number"
-          min=1
+          min=2
           step=1
-          onchange={promos_change}
-          value={Some(self.data.promos.to_string())}
+          onchange={max_span_change}
+          value={Some(self.data.max_span.to_string())}
         />
+        { self.field_error(ContextField::MaxSpan) }
         <br />
-        { "método para resolver ambiguidades: "}
+        { tr("solver_label") }
         <select onchange={ solver_change }>
           {
             for AmbiguitySolver::available().map(|solv| {
               html_nested! {
-                <option value={ solv.name() }>{ solv }</option>
+                <option
+                  value={ solv.name() }
+                  selected={ solv.name() == self.data.solver }
+                >
+                  { solv }
+                </option>
+              }
+            })
+          }
+        </select>
+        <br />
+        <input
+          type="checkbox"
+          onchange={per_day_change}
+          checked={self.data.online_per_day}
+        />
+        { tr("per_day_cb") }
+        <br />
+        { tr("forced_kind_label") }
+        <select onchange={ forced_kind_change }>
+          {
+            for ForcedKind::available().map(|fk| {
+              html_nested! {
+                <option
+                  value={ fk.name() }
+                  selected={ fk == self.data.forced_kind }
+                >
+                  { fk }
+                </option>
+              }
+            })
+          }
+        </select>
+        <br />
+        { tr("dedup_label") }
+        <select onchange={ dedup_change }>
+          {
+            for DedupKey::available().map(|dk| {
+              html_nested! {
+                <option
+                  value={ dk.name() }
+                  selected={ dk == self.data.dedup_key }
+                >
+                  { dk }
+                </option>
+              }
+            })
+          }
+        </select>
+        <br />
+        <input
+          type="checkbox"
+          onchange={free_tickets_change}
+          checked={self.data.free_tickets}
+        />
+        { tr("free_tickets_cb") }
+        <br />
+        { tr("date_format_label") }
+        <input
+          type="text"
+          placeholder="%d/%m/%Y %H:%M"
+          onchange={date_format_change}
+          value={Some(self.data.date_format.clone())}
+        />
+        <br />
+        { tr("timezone_label") }
+        <select onchange={ timezone_change }>
+          {
+            for self.timezone_options().into_iter().map(|h| {
+              html_nested! {
+                <option
+                  value={ h.to_string() }
+                  selected={ h == self.data.utc_offset }
+                >
+                  { offset_name((h * 3600.0) as i32) }
+                </option>
               }
             })
           }
         </select>
+        { self.field_error(ContextField::Timezone) }
+        <br />
+        { tr("commission_label") }
+        <input
+          type="number"
+          min=0
+          step={match self.data.commission_percent {
+            true => "0.01".to_owned(),
+            false => self.price_step()
+          }}
+          onchange={commission_change}
+          value={Some(self.data.commission.to_string())}
+        />
+        <input
+          type="checkbox"
+          onchange={commission_pct_change}
+          checked={self.data.commission_percent}
+        />
+        { tr("commission_pct_cb") }
+        { self.field_error(ContextField::Commission) }
+        <br />
+        { self.view_overrides(ctx) }
+        { self.view_columns(ctx) }
+        <a href="#" onclick={reset_click}>{ tr("reset_defaults") }</a>
         <br />
       </div>
     }
@@ -244,10 +909,339 @@ impl Component for ContextInput {
 }
 
 impl ContextInput {
+  /// Renders the export link and the import box.
+  fn view_import_export(&self, ctx: &yew::Context<Self>) -> Html {
+    let import_input = ctx.link().callback(|e: InputEvent| {
+      let input: HtmlTextAreaElement = e.target_unchecked_into();
+      return ContextInputMsg::ImportTyped(input.value());
+    });
+    let import_click = ctx.link().callback(|_e: MouseEvent| {
+      return ContextInputMsg::ImportContext;
+    });
+    let export = match self.try_get_context() {
+      Ok(sc) => html! {
+        <a
+          href={ format!(
+            "data:application/json;charset=utf-8,{}",
+            js_sys::encode_uri_component(&sc.to_json())
+          ) }
+          download="contexto.json"
+        >
+          <button>{ tr("export_context") }</button>
+        </a>
+      },
+      Err(_) => html! {
+        <button disabled=true>{ tr("export_context") }</button>
+      },
+    };
+    return html! {
+      <details class="context-json">
+        <summary>
+          { tr("export_context") }{ " / " }{ tr("import_context") }
+        </summary>
+        { export }
+        <br />
+        <textarea
+          placeholder={ tr("import_placeholder") }
+          oninput={ import_input }
+          value={ self.import_txt.clone() }
+        />
+        <br />
+        <button
+          onclick={ import_click }
+          disabled={ self.import_txt.trim().is_empty() }
+        >
+          { tr("import_context") }
+        </button>
+        {
+          match &self.import_error {
+            Some(e) => html! {
+              <>
+                <br />
+                <span class="field-error" style="color: red;">{ e }</span>
+              </>
+            },
+            None => html! {}
+          }
+        }
+      </details>
+    };
+  }
+
+  /// Renders the manual price rules, plus a way to add new ones: type in a
+  /// price, then pick which of its decodings it always is.
+  fn view_overrides(&self, ctx: &yew::Context<Self>) -> Html {
+    let price_change = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return ContextInputMsg::OverridePriceChanged(input.value_as_number());
+    });
+    let mut rules: Vec<(usize, PricingMatch)> = self.data.overrides.iter()
+      .map(|(price, pm)| (*price, pm.clone()))
+      .collect();
+    rules.sort_by_key(|(price, _)| *price);
+    let cents = self.data.to_cents(self.override_price);
+    let candidates = match self.try_get_context() {
+      Ok(sc) if self.override_price > 0.0 => {
+        PricingMatch::all_priced(cents, &sc)
+      },
+      _ => Vec::new(),
+    };
+    return html! {
+      <details class="overrides" open={ !rules.is_empty() }>
+        <summary>{ tr("overrides") }</summary>
+        <ul>
+          {
+            for rules.into_iter().map(|(price, pm)| {
+              let remove = ctx.link().callback(move |_e: MouseEvent| {
+                return ContextInputMsg::RemoveOverride(price);
+              });
+              html_nested! {
+                <li>
+                  { format!("{} = {} ", format::money(price), pm) }
+                  <button onclick={ remove }>{ tr("remove") }</button>
+                </li>
+              }
+            })
+          }
+        </ul>
+        { self.unit_label("override_price", "override_price_cents") }
+        <input
+          type="number"
+          min=0
+          step={ self.price_step() }
+          onchange={ price_change }
+          value={ Some(self.override_price.to_string()) }
+        />
+        <ul>
+          {
+            for candidates.into_iter().map(|pm| {
+              let label = format!("{} ", pm);
+              let add = ctx.link().callback(move |_e: MouseEvent| {
+                return ContextInputMsg::AddOverride(cents, pm.clone());
+              });
+              html_nested! {
+                <li>
+                  { label }
+                  <button onclick={ add }>{ tr("always_this") }</button>
+                </li>
+              }
+            })
+          }
+        </ul>
+        { self.field_error(ContextField::Overrides) }
+      </details>
+    };
+  }
+
+  /// Renders the manual CSV header mappings, and a picker for each header
+  /// the pasted CSV has that didn't match, while some field is missing.
+  /// Optional fields nothing matched can be picked too.
+  fn view_columns(&self, ctx: &yew::Context<Self>) -> Html {
+    let info = &ctx.props().headers;
+    let mut mapped: Vec<(String, Column)> = self.data.columns.iter()
+      .map(|(h, c)| (h.clone(), *c))
+      .collect();
+    mapped.sort();
+    let unknown: Vec<String> = match info.missing.is_empty() {
+      true => Vec::new(),
+      false => info.unknown.clone(),
+    };
+    return html! {
+      <details class="columns" open={ !unknown.is_empty() }>
+        <summary>{ tr("columns") }</summary>
+        {
+          match unknown.is_empty() {
+            true => html! {},
+            false => html! {
+              <span class="field-error" style="color: red;">
+                {
+                  trf("columns_missing", &[
+                    &info.missing.iter().map(|c| c.to_string())
+                      .collect::<Vec<String>>()
+                      .join(", ")
+                  ])
+                }
+              </span>
+            }
+          }
+        }
+        <ul>
+          {
+            for mapped.into_iter().map(|(h, c)| {
+              let label = format!("{} = {} ", h, c);
+              let remove = ctx.link().callback(move |_e: MouseEvent| {
+                return ContextInputMsg::UnmapColumn(h.clone());
+              });
+              html_nested! {
+                <li>
+                  { label }
+                  <button onclick={ remove }>{ tr("remove") }</button>
+                </li>
+              }
+            })
+          }
+          {
+            for unknown.into_iter().map(|h| {
+              let label = format!("{} = ", h);
+              let pick = ctx.link().batch_callback(move |e: Event| {
+                let input: HtmlSelectElement = e.target_unchecked_into();
+                return Column::try_from(input.value().as_str()).ok()
+                  .map(|c| ContextInputMsg::MapColumn(h.clone(), c));
+              });
+              html_nested! {
+                <li>
+                  { label }
+                  <select onchange={ pick }>
+                    <option value="" selected=true>{ "--" }</option>
+                    {
+                      for info.missing.iter().chain(&info.optional)
+                        .map(|c| {
+                          html_nested! {
+                            <option value={ c.name() }>{ c }</option>
+                          }
+                        })
+                    }
+                  </select>
+                </li>
+              }
+            })
+          }
+        </ul>
+      </details>
+    };
+  }
+
+  /// Renders a small table of face and online prices, if the form parses.
+  fn view_fee_preview(&self) -> Html {
+    let sc = match self.try_get_context() {
+      Ok(sc) => sc,
+      Err(_) => return html! {},
+    };
+    let online = SaleKind::Online(sc.online_fee);
+    let offline = SaleKind::Offline(sc.offline_fee);
+    let batches: Vec<Batch> = bp2iter(&sc.batches).collect();
+    return html! {
+      <>
+        <table class="fee-preview">
+          <tr>
+            <th>{ tr("batch") }</th>
+            <th>{ tr("price") }</th>
+            <th>{ tr("online_col") }</th>
+            {
+              match sc.offline_fee.is_some() {
+                true => html! { <th>{ tr("offline_col") }</th> },
+                false => html! {}
+              }
+            }
+          </tr>
+          {
+            for batches.into_iter().map(|b| {
+              html_nested! {
+                <tr>
+                  <td>{ b.num }</td>
+                  <td>{ format::money(b.price) }</td>
+                  <td>{ format::money(online.apply_fee(b.price)) }</td>
+                  {
+                    match sc.offline_fee.is_some() {
+                      true => html! {
+                        <td>{ format::money(offline.apply_fee(b.price)) }</td>
+                      },
+                      false => html! {}
+                    }
+                  }
+                </tr>
+              }
+            })
+          }
+        </table>
+      </>
+    };
+  }
+
+  /// Renders a single batch price row.
+  fn view_row(
+    &self,
+    ctx: &yew::Context<Self>,
+    i: usize,
+    price: f64
+  ) -> Html {
+    let price_change = ctx.link().callback(move |e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return ContextInputMsg::RowPriceChanged(i, input.value_as_number());
+    });
+    let capacity_change = ctx.link().callback(move |e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return ContextInputMsg::RowCapacityChanged(i, input.value_as_number());
+    });
+    let remove = ctx.link().callback(move |_e: MouseEvent| {
+      return ContextInputMsg::RemoveRow(i);
+    });
+    let capacity = self.data.capacities.get(i).copied().unwrap_or(0.0);
+    return html! {
+      <tr>
+        <td>{ format!("{}º", i + 1) }</td>
+        <td>
+          <input
+            type="number"
+            min=0
+            step={ self.price_step() }
+            onchange={price_change}
+            value={Some(price.to_string())}
+          />
+        </td>
+        <td>
+          <input
+            type="number"
+            min=0
+            step=1
+            placeholder={ tr("dont_know") }
+            onchange={capacity_change}
+            value={Some(capacity.to_string())}
+          />
+        </td>
+        <td><button onclick={remove}>{ tr("remove") }</button></td>
+      </tr>
+    };
+  }
+
+  /// How much the price inputs go up by: a centavo, in either unit.
+  fn price_step(&self) -> String {
+    return match self.data.cents {
+      true => "1".to_owned(),
+      false => "0.01".to_owned()
+    };
+  }
+
+  /// Picks the label that says the prices are in reais or in centavos.
+  fn unit_label(
+    &self,
+    reais: &'static str,
+    cents: &'static str
+  ) -> &'static str {
+    return tr(match self.data.cents {
+      true => cents,
+      false => reais
+    });
+  }
+
   /// Tries to convert the input data into a proper SalesContext.
   pub(crate) fn try_get_context(
     &self
-  ) -> Result<SalesContext, Box<dyn Error>> {
+  ) -> Result<SalesContext, D4Error> {
     return self.data.clone().try_into();
   }
 }
+
+/// Shows a browser alert, if there's a browser.
+fn alert(msg: &str) {
+  if let Some(w) = web_sys::window() {
+    w.alert_with_message(msg).ok();
+  }
+}
+
+/// Asks the user a yes/no question through the browser.
+fn confirm(msg: &str) -> bool {
+  return web_sys::window()
+    .and_then(|w| w.confirm_with_message(msg).ok())
+    .unwrap_or(false);
+}
@@ -0,0 +1,46 @@
+//! Named context presets: a few built in, for the events we keep going back
+//! to, and the user's own, kept in localStorage.
+
+use std::collections::HashMap;
+use gloo_storage::{LocalStorage, Storage};
+use d4csv_core::context::ContextInputData;
+
+static PRESETS_KEY: &str = "d4csv.presets";
+
+/// All saved presets, by name.
+pub(crate) type Presets = HashMap<String, ContextInputData>;
+
+/// Loads all saved presets. Anything unreadable counts as no presets.
+pub(crate) fn load_presets() -> Presets {
+  return LocalStorage::get::<Presets>(PRESETS_KEY).unwrap_or_default();
+}
+
+/// Saves all presets, replacing the stored ones.
+pub(crate) fn store_presets(presets: &Presets) {
+  if let Err(e) = LocalStorage::set(PRESETS_KEY, presets) {
+    log::warn!("couldn't store presets: {}", e);
+  }
+}
+
+/// Presets that come with the page. They can be picked, but not overwritten
+/// nor deleted.
+pub(crate) fn builtin_presets() -> Vec<(&'static str, ContextInputData)> {
+  return vec![
+    // the default context is the 2022 D4's
+    ("D4 2022", ContextInputData::default()),
+  ];
+}
+
+/// A built-in preset, by name.
+pub(crate) fn builtin_preset(name: &str) -> Option<ContextInputData> {
+  return builtin_presets().into_iter()
+    .find(|(n, _)| *n == name)
+    .map(|(_, data)| data);
+}
+
+/// Preset names, sorted, for display.
+pub(crate) fn preset_names(presets: &Presets) -> Vec<String> {
+  let mut v: Vec<String> = presets.keys().cloned().collect();
+  v.sort();
+  return v;
+}
@@ -0,0 +1,257 @@
+//! Encoding the context form in the URL query string, for sharing links.
+
+use itertools::Itertools;
+use wasm_bindgen::JsValue;
+use d4csv_core::context::{
+  ContextInputData, parse_capacities, parse_prices, join_prices
+};
+use d4csv_core::sale::columns::Column;
+use d4csv_core::sale::dedup::DedupKey;
+use d4csv_core::sale::kind::ForcedKind;
+use d4csv_core::sale::price_deriving::PricingMatch;
+
+/// Percent-encodes everything that isn't obviously safe in a query value.
+fn escape(s: &str) -> String {
+  let mut out = String::new();
+  for b in s.bytes() {
+    match b {
+      b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+        out.push(b as char);
+      },
+      _ => out.push_str(&format!("%{:02X}", b))
+    }
+  }
+  return out;
+}
+
+/// Undoes escape(). Also takes '+' as a space, like forms do.
+fn unescape(s: &str) -> Result<String, String> {
+  let bytes = s.as_bytes();
+  let mut out: Vec<u8> = Vec::new();
+  let mut i: usize = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'%' => {
+        let hex = s.get(i+1..i+3).ok_or(format!("escape truncado em {}", s))?;
+        let b = u8::from_str_radix(hex, 16)
+          .map_err(|_| format!("escape inválido em {}", s))?;
+        out.push(b);
+        i += 3;
+      },
+      b'+' => {
+        out.push(b' ');
+        i += 1;
+      },
+      b => {
+        out.push(b);
+        i += 1;
+      }
+    }
+  }
+  return String::from_utf8(out).map_err(|_| format!("UTF-8 inválido: {}", s));
+}
+
+/// Encodes the form data as k=v pairs, without the leading '?'.
+pub(crate) fn encode(data: &ContextInputData) -> String {
+  return [
+    ("fee", data.webfee.to_string()),
+    ("ofee", data.offline_fee.to_string()),
+    ("prices", join_prices(&data.rows)),
+    ("cents", match data.cents {
+      true => "1".to_owned(),
+      false => "0".to_owned()
+    }),
+    ("caps", join_prices(&data.capacities)),
+    ("promocap", data.promo_capacity.to_string()),
+    ("promo", match data.promo_enabled {
+      true => data.promo_price.to_string(),
+      false => "0".to_owned()
+    }),
+    ("promos", match data.promo_unlimited {
+      true => "0".to_owned(),
+      false => data.promos.to_string()
+    }),
+    ("combo", match data.promo_combo {
+      true => "1".to_owned(),
+      false => "0".to_owned()
+    }),
+    ("span", data.max_span.to_string()),
+    ("solver", data.solver.clone()),
+    ("perday", match data.online_per_day {
+      true => "1".to_owned(),
+      false => "0".to_owned()
+    }),
+    ("kind", data.forced_kind.name().to_owned()),
+    ("dedup", data.dedup_key.name().to_owned()),
+    ("free", match data.free_tickets {
+      true => "1".to_owned(),
+      false => "0".to_owned()
+    }),
+    ("datefmt", data.date_format.clone()),
+    ("tz", data.utc_offset.to_string()),
+    ("comm", data.commission.to_string()),
+    ("commpct", match data.commission_percent {
+      true => "1".to_owned(),
+      false => "0".to_owned()
+    }),
+    ("cols", data.columns.iter()
+      .map(|(h, c)| format!("{}={}", h, c.name()))
+      .collect::<Vec<String>>()
+      .join(";")),
+    ("ovr", data.overrides.iter()
+      .sorted_by_key(|(p, _)| **p)
+      .map(|(p, pm)| format!("{}={}", p, pm.code()))
+      .collect::<Vec<String>>()
+      .join(";"))
+  ].iter()
+    .map(|(k, v)| format!("{}={}", k, escape(v)))
+    .collect::<Vec<String>>()
+    .join("&");
+}
+
+/// Decodes form data from a query string (with or without the leading '?').
+/// Missing keys keep their defaults; unknown keys are ignored. A promo price
+/// or limit of 0 means no promo batch or no limit, respectively.
+pub(crate) fn decode(query: &str) -> Result<ContextInputData, String> {
+  let mut data = ContextInputData::default();
+  let num = |k: &str, v: &str| {
+    return v.parse::<f64>().map_err(|_| format!("valor inválido para {}", k));
+  };
+  for pair in query.trim_start_matches('?').split('&') {
+    if pair.is_empty() { continue; }
+    let (k, v) = pair.split_once('=')
+      .ok_or(format!("par sem '=': {}", pair))?;
+    let v = unescape(v)?;
+    match k {
+      "fee" => data.webfee = num(k, &v)?,
+      "ofee" => data.offline_fee = num(k, &v)?,
+      "prices" => data.rows = parse_prices(&v)?,
+      "cents" => data.cents = num(k, &v)? != 0.0,
+      "caps" if v.is_empty() => data.capacities = Vec::new(),
+      "caps" => data.capacities = parse_capacities(&v)?,
+      "promocap" => data.promo_capacity = num(k, &v)?,
+      "promo" => {
+        let p = num(k, &v)?;
+        data.promo_enabled = p > 0.0;
+        if p > 0.0 {
+          data.promo_price = p;
+        }
+      },
+      "promos" => {
+        let p = num(k, &v)?;
+        data.promo_unlimited = p == 0.0;
+        if p > 0.0 {
+          data.promos = p;
+        }
+      },
+      "combo" => data.promo_combo = num(k, &v)? != 0.0,
+      "span" => data.max_span = num(k, &v)?,
+      "solver" => data.solver = v,
+      "perday" => data.online_per_day = num(k, &v)? != 0.0,
+      "kind" => {
+        data.forced_kind = ForcedKind::try_from(v.as_str())
+          .map_err(|_| format!("valor inválido para {}", k))?;
+      },
+      "dedup" => {
+        data.dedup_key = DedupKey::try_from(v.as_str())
+          .map_err(|_| format!("valor inválido para {}", k))?;
+      },
+      "free" => data.free_tickets = num(k, &v)? != 0.0,
+      "datefmt" => data.date_format = v,
+      "tz" => data.utc_offset = num(k, &v)?,
+      "comm" => data.commission = num(k, &v)?,
+      "commpct" => data.commission_percent = num(k, &v)? != 0.0,
+      "cols" => {
+        for m in v.split(';').filter(|m| !m.is_empty()) {
+          let (h, c) = m.split_once('=')
+            .and_then(|(h, c)| Some((h, Column::try_from(c).ok()?)))
+            .ok_or(format!("coluna inválida: {}", m))?;
+          data.columns.insert(h.to_owned(), c);
+        }
+      },
+      "ovr" => {
+        for r in v.split(';').filter(|r| !r.is_empty()) {
+          let (p, pm) = r.split_once('=')
+            .and_then(|(p, pm)| Some((
+              p.parse::<usize>().ok()?,
+              PricingMatch::try_from(pm).ok()?
+            )))
+            .ok_or(format!("regra inválida: {}", r))?;
+          data.overrides.insert(p, pm);
+        }
+      },
+      _ => {}
+    }
+  }
+  return Ok(data);
+}
+
+/// Reads the form data from the current page's URL, if there's any.
+pub(crate) fn from_location() -> Option<ContextInputData> {
+  let search = web_sys::window()?.location().search().ok()?;
+  if search.trim_start_matches('?').is_empty() {
+    return None;
+  }
+  return match decode(&search) {
+    Ok(data) => Some(data),
+    Err(e) => {
+      log::warn!("ignoring context in URL: {}", e);
+      None
+    }
+  };
+}
+
+/// Writes the form data into the current URL without adding to the history.
+pub(crate) fn replace_location(data: &ContextInputData) {
+  let url = format!("?{}", encode(data));
+  if let Some(history) = web_sys::window().and_then(|w| w.history().ok()) {
+    history.replace_state_with_url(&JsValue::NULL, "", Some(&url)).ok();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use d4csv_core::context::SalesContext;
+
+  #[test]
+  fn query_round_trip() {
+    let mut data = ContextInputData {
+      webfee: 0.125,
+      offline_fee: 0.05,
+      rows: vec![70.0, 80.5, 99.99],
+      capacities: vec![0.0, 300.0, 150.0],
+      promo_price: 42.0,
+      promos: 3.0,
+      promo_combo: false,
+      max_span: 3.0,
+      solver: "popularity".to_owned(),
+      online_per_day: true,
+      forced_kind: ForcedKind::Online,
+      dedup_key: DedupKey::SaleId,
+      free_tickets: true,
+      date_format: "%d/%m/%Y %H:%M".to_owned(),
+      utc_offset: -2.5,
+      commission: 150.0,
+      ..Default::default()
+    };
+    data.columns.insert("valor pago".to_owned(), Column::Value);
+    data.columns.insert("e-mail & cia".to_owned(), Column::BuyerEmail);
+    let ctx = SalesContext::default();
+    for price in [12000, 19500] {
+      let pm = PricingMatch::all_priced(price, &ctx).remove(0);
+      data.overrides.insert(price, pm);
+    }
+    assert_eq!(decode(&format!("?{}", encode(&data))), Ok(data));
+  }
+
+  #[test]
+  fn zero_promo_means_none_and_unlimited() {
+    let data = ContextInputData {
+      promo_enabled: false,
+      promo_unlimited: true,
+      ..Default::default()
+    };
+    assert_eq!(decode(&encode(&data)), Ok(data));
+  }
+}
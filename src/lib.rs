@@ -0,0 +1,31 @@
+//! The whole app, shared by the page binary and the worker binary.
+
+#![allow(dead_code)]
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+// yew 0.19's html! expands props and bare components into statements these
+// trip on
+#![allow(clippy::unnecessary_operation, clippy::let_unit_value)]
+
+pub(crate) mod context;
+pub(crate) mod report;
+pub(crate) mod app;
+mod wrapper;
+#[cfg(feature = "api")]
+pub mod api;
+
+/// Sets up logging and starts the yew app.
+pub fn start_app() {
+  wasm_logger::init(wasm_logger::Config::default());
+  log::info!("d4csv {}", d4csv_core::build_info::describe());
+  yew::start_app::<wrapper::Wrapper>();
+}
+
+/// Sets up logging and registers the loader worker. Only makes sense when
+/// running inside a web worker.
+#[cfg(feature = "worker")]
+pub fn start_worker() {
+  use gloo_worker::Registrable;
+  wasm_logger::init(wasm_logger::Config::default());
+  app::worker::LoadWorker::registrar().register();
+}
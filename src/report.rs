@@ -1,102 +1,221 @@
-//! Useful information to report after looking at the sales list.
+//! Shows a computed report, split into tabs.
 
-pub(crate) mod sfields;
-pub(crate) mod tfields;
+pub(crate) mod table;
 
-use std::collections::HashMap;
-use yew::{Component, Properties, html, html_nested};
-use crate::report::sfields::SFIELDS;
-use crate::report::tfields::TFIELDS;
-use crate::sale::plus::{SalesPlus, SalePlus};
+use std::fmt::Display;
+use std::rc::Rc;
+use web_sys::{Event, HtmlInputElement, InputEvent, MouseEvent};
+use yew::{Component, Html, Properties, html, html_nested};
+use yew::html::TargetCast;
+use d4csv_core::error::D4Error;
+use d4csv_core::format;
+use d4csv_core::i18n::{tr, trf};
+use d4csv_core::report::Report;
+use d4csv_core::report::table::{Resolution, RowStatus};
+use d4csv_core::sale::plus::SalePlus;
+use crate::report::table::{SortableTable, TableRow};
 
-/// A report field made out to be a single string.
-#[derive(Clone, PartialEq, Eq)]
-pub(crate) struct StringField(String, String);
-
-/// A function that computes a string field from sales data.
-pub(crate) type FieldFn = fn(&SalesPlus) -> StringField;
-
-/// A report field that's a string-string table.
-#[derive(Clone, PartialEq, Eq)]
-pub(crate) struct TableField(String, HashMap<String, String>);
+/// Properties for the report display: a shared, pre-computed report.
+#[derive(Clone, Properties)]
+pub(crate) struct ReportProps {
+  /// The report itself, computed once and shared.
+  pub(crate) report: Rc<Report>
+}
 
-/// A function that computes a table field from sales data.
-pub(crate) type TableFn = fn(&SalesPlus) -> TableField;
+impl PartialEq for ReportProps {
+  /// Same report means same allocation. No need to look inside.
+  fn eq(&self, other: &Self) -> bool {
+    return Rc::ptr_eq(&self.report, &other.report);
+  }
+}
 
-/// A report skeleton, made out of field functions.
-pub(crate) struct ReportTemplate {
-  /// All string field functions.
-  sfields: Vec<FieldFn>,
-  /// All table field functions.
-  tfields: Vec<TableFn>
+/// The tabs the report is split into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ReportTab {
+  /// Stamp and string fields.
+  Summary,
+  /// Table and matrix fields.
+  Tables,
+  /// The better CSV, its search, and the downloads.
+  Csv,
+  /// Only the sales that didn't get resolved.
+  Problems,
+  /// Only the sales with no way to reach the buyer.
+  NoContact,
+  /// Only the sales a solver narrowed down without resolving.
+  Narrowed,
+  /// Only the zero-value sales.
+  Free
 }
 
-impl Default for ReportTemplate {
-  fn default() -> Self {
-    return Self {
-      sfields: SFIELDS.to_vec(),
-      tfields: TFIELDS.to_vec()
-    };
+impl Display for ReportTab {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return write!(f, "{}", tr(match self {
+      ReportTab::Summary => "tab_summary",
+      ReportTab::Tables => "tab_tables",
+      ReportTab::Csv => "tab_csv",
+      ReportTab::Problems => "tab_problems",
+      ReportTab::NoContact => "tab_no_contact",
+      ReportTab::Narrowed => "tab_narrowed",
+      ReportTab::Free => "tab_free",
+    }));
   }
 }
 
-impl ReportTemplate {
-  /// Computes the report from sales data.
-  pub(crate) fn compute(&self, data: &SalesPlus) -> Report {
-    return Report {
-      sfields: self.sfields.iter().map(|f| f(data)).collect(),
-      tfields: self.tfields.iter().map(|f| f(data)).collect(),
-      better_csv: data.gen_csv()
-    }
+impl ReportTab {
+  pub(crate) fn available() -> impl Iterator<Item = Self> {
+    return [
+      Self::Summary,
+      Self::Tables,
+      Self::Csv,
+      Self::Problems,
+      Self::NoContact,
+      Self::Narrowed,
+      Self::Free
+    ].into_iter();
   }
 }
 
-/// A corresponding report, calculated from the skeleton and sales data.
-#[derive(Clone, Properties, PartialEq, Eq)]
-pub(crate) struct Report {
-  /// All string fields.
-  sfields: Vec<StringField>,
-  /// All table fields.
-  tfields: Vec<TableField>,
-  /// The "better" CSV.
-  better_csv: Vec<Vec<String>>
+/// A component that displays a report.
+pub(crate) struct ReportDisplay {
+  /// The tab being shown. Kept when the report is recomputed.
+  tab: ReportTab,
+  /// What's typed in the search box.
+  query: String,
+  /// The better CSV rows matching the search, and their statuses.
+  rows: Rc<Vec<TableRow>>,
+  /// The better CSV rows that aren't resolved, and their statuses.
+  problems: Rc<Vec<TableRow>>,
+  /// The better CSV rows with no buyer contact, and their statuses.
+  no_contact: Rc<Vec<TableRow>>,
+  /// The better CSV rows a solver narrowed down, and their statuses.
+  narrowed: Rc<Vec<TableRow>>,
+  /// The better CSV rows worth nothing, and their statuses.
+  free: Rc<Vec<TableRow>>,
+  /// Whether the CSVs are made for Excel: CRLF lines and a BOM.
+  excel: bool,
+  /// The better CSV as text, or why it couldn't be made.
+  better_txt: Result<String, D4Error>,
+  /// The per-seller CSV as text, or why it couldn't be made.
+  seller_txt: Result<String, D4Error>,
+  /// The better CSV without personal data, or why it couldn't be made.
+  anon_txt: Result<String, D4Error>,
+  /// The rows with no buyer contact as CSV, or why they couldn't be.
+  no_contact_txt: Result<String, D4Error>
 }
 
-/// A component that displays a report.
-pub(crate) struct ReportDisplay;
+/// The events the report display reacts to.
+#[derive(Clone, Debug)]
+pub(crate) enum ReportMsg {
+  /// A change to the search box.
+  SearchChanged(String),
+  /// A tab was clicked.
+  TabPicked(ReportTab),
+  /// The Excel-compatible checkbox changed.
+  ExcelToggled(bool)
+}
 
 impl ReportDisplay {
-  fn make_csv_txt(report: &Report) -> String {
-    let mut wr = csv::WriterBuilder::new()
-      .double_quote(true)
-      .delimiter(b',')
-      .has_headers(true)
-      .from_writer(vec![]);
-    wr.write_record(SalePlus::better_csv_header()).ok();
-    for l in &report.better_csv {
-      wr.write_record(l).ok();
-    }
-    return String::from_utf8(
-      wr.into_inner().unwrap_or(vec![])
-    ).unwrap_or("ERRO".to_owned());
+  /// Pairs up the better CSV rows with their statuses and resolution traces,
+  /// keeping only the ones whose sales match the query (case-insensitive).
+  fn table_rows(report: &Report, query: &str) -> Rc<Vec<TableRow>> {
+    let q = query.trim().to_lowercase();
+    return Rc::new(
+      report.better_status.iter().copied()
+        .zip(report.better_traces.iter())
+        .zip(report.better_csv.iter())
+        .zip(report.better_keys.iter())
+        .filter(|(_, key)| key.contains(&q))
+        .map(|(((st, why), row), _)| (st, why.clone(), row.clone()))
+        .collect()
+    );
   }
-}
 
-impl Component for ReportDisplay {
-  type Message = ();
-  type Properties = Report;
+  /// The better CSV rows that aren't resolved, with their statuses. The
+  /// zero-value ones have a tab of their own.
+  fn problem_rows(report: &Report) -> Rc<Vec<TableRow>> {
+    return Rc::new(
+      report.better_status.iter().copied()
+        .zip(report.better_csv.iter())
+        .filter(|(st, _)| !matches!(st, RowStatus::Resolved | RowStatus::Free))
+        .map(|(st, row)| (st, None, row.clone()))
+        .collect()
+    );
+  }
 
-  fn create(_ctx: &yew::Context<Self>) -> Self {
-    return Self;
+  /// Some of the better CSV rows, by index, with their statuses.
+  fn picked_rows(report: &Report, picked: &[usize]) -> Rc<Vec<TableRow>> {
+    return Rc::new(
+      picked.iter()
+        .filter_map(|i| {
+          let st = report.better_status.get(*i)?;
+          let row = report.better_csv.get(*i)?;
+          return Some((*st, None, row.clone()));
+        })
+        .collect()
+    );
   }
 
-  fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+  /// Makes the CSV texts again, for a new report or another line ending.
+  fn make_csv_txts(&mut self, report: &Report) {
+    self.better_txt = report.better_csv_txt(self.excel);
+    self.seller_txt = report.seller_csv_txt(self.excel);
+    self.no_contact_txt = report.no_contact_csv_txt(self.excel);
+    // a new salt every time, kept nowhere
+    let mut salt = [0u8; 8];
+    self.anon_txt = getrandom::getrandom(&mut salt)
+      .map_err(|e| D4Error::Export(e.to_string()))
+      .and_then(|_| {
+        report.anon_csv_txt(self.excel, u64::from_le_bytes(salt))
+      });
+  }
+
+  /// A download button for some CSV text, disabled if there's no text.
+  fn download(
+    txt: &Result<String, D4Error>,
+    file: &'static str,
+    label: &'static str
+  ) -> Html {
+    return match txt {
+      Ok(t) => html! {
+        <a href={ ReportDisplay::csv_href(t) } download={ file }>
+          <button>{ tr(label) }</button>
+        </a>
+      },
+      Err(_) => html! {
+        <button disabled=true>{ tr(label) }</button>
+      },
+    };
+  }
+
+  /// Turns some CSV text into a link that downloads it.
+  fn csv_href(txt: &str) -> String {
+    return format!(
+      "data:text/csv;charset=utf-8,{}",
+      js_sys::encode_uri_component(txt)
+    );
+  }
+
+  /// The summary tab: the stamp and the string fields.
+  fn view_summary(report: &Report) -> Html {
     return html! {
-      <div class="report">
+      <>
+        <table class="stamp">
+          {
+            for report.stamp.iter().map(|sf| {
+              html_nested! {
+                <tr>
+                  <td><i>{ &sf.0 }</i></td>
+                  <td>{ &sf.1 }</td>
+                </tr>
+              }
+            })
+          }
+        </table>
         <hr />
         <table class="sfields">
           {
-            for ctx.props().sfields.iter().map(|sf| {
+            for report.sfields.iter().map(|sf| {
               html_nested! {
                 <tr>
                   <td><b>{ &sf.0 }</b></td>
@@ -106,10 +225,17 @@ impl Component for ReportDisplay {
             })
           }
         </table>
-        <hr />
+      </>
+    };
+  }
+
+  /// The tables tab: table fields, then matrix fields.
+  fn view_tables(report: &Report) -> Html {
+    return html! {
+      <>
         <div class="tfields">
           {
-            for ctx.props().tfields.iter().map(|tf| {
+            for report.tfields.iter().map(|tf| {
               html_nested! {
                 <div>
                   <b>{ &tf.0 }</b>{ ": " }
@@ -133,17 +259,364 @@ impl Component for ReportDisplay {
           }
         </div>
         <hr />
+        <div class="mfields">
+          {
+            for report.mfields.iter().map(|mf| {
+              html_nested! {
+                <div>
+                  <b>{ &mf.0 }</b>{ ": " }
+                  <br />
+                  <br />
+                  <table class="mfield-vals">
+                    <tr>
+                      <th></th>
+                      {
+                        for mf.1.iter().map(|c| {
+                          html_nested! { <th>{ c }</th> }
+                        })
+                      }
+                    </tr>
+                    {
+                      for mf.2.iter().map(|(rh, cells)| {
+                        html_nested! {
+                          <tr>
+                            <td><b>{ rh }</b></td>
+                            {
+                              for cells.iter().map(|c| {
+                                html_nested! { <td>{ c }</td> }
+                              })
+                            }
+                          </tr>
+                        }
+                      })
+                    }
+                  </table>
+                </div>
+              }
+            })
+          }
+        </div>
+        <hr />
+        <div class="rfields">
+          {
+            for report.rfields.iter().map(|rf| {
+              html_nested! {
+                <div>
+                  <b>{ &rf.0 }</b>{ ": " }
+                  <br />
+                  <br />
+                  <table class="rfield-vals">
+                    <tr>
+                      <th></th>
+                      {
+                        for Resolution::ALL.iter().map(|r| {
+                          html_nested! {
+                            <th class={ r.class() }>{ r }</th>
+                          }
+                        })
+                      }
+                    </tr>
+                    {
+                      for rf.1.iter().map(|(rh, counts)| {
+                        html_nested! {
+                          <tr>
+                            <td><b>{ rh }</b></td>
+                            {
+                              for Resolution::ALL.iter().zip(counts.iter())
+                                .map(|(r, n)| {
+                                  html_nested! {
+                                    <td class={ r.class() }>
+                                      { format::tenths(*n) }
+                                    </td>
+                                  }
+                                })
+                            }
+                          </tr>
+                        }
+                      })
+                    }
+                  </table>
+                </div>
+              }
+            })
+          }
+        </div>
+      </>
+    };
+  }
+
+  /// The CSV tab: search, the big table, and the downloads.
+  fn view_csv(&self, ctx: &yew::Context<Self>) -> Html {
+    let excel_cb = ctx.link().callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return ReportMsg::ExcelToggled(input.checked());
+    });
+    let search_cb = ctx.link().callback(|e: InputEvent| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      return ReportMsg::SearchChanged(input.value());
+    });
+    return html! {
+      <>
         <div class="better-csv">
-          <b>{ "CSV melhorado:" }</b>
+          <b>{ tr("better_csv") }</b>
+          <br />
+          <br />
+          <input
+            type="search"
+            placeholder={ tr("search_placeholder") }
+            oninput={search_cb}
+            value={ self.query.clone() }
+          />
+          {
+            match self.query.trim().is_empty() {
+              true => html! {},
+              false => html! {
+                <i>{ trf("found", &[&self.rows.len()]) }</i>
+              }
+            }
+          }
           <br />
+          <SortableTable
+            header={ SalePlus::better_csv_header() }
+            kinds={ SalePlus::better_csv_kinds() }
+            rows={ self.rows.clone() }
+            empty={ tr("no_sales_found") }
+          />
           <br />
           <textarea
             class="csv-in" 
             readonly=true
-            value={ ReportDisplay::make_csv_txt(ctx.props()) }
+            value={ self.better_txt.clone().unwrap_or_default() }
           >
           </textarea>
+          <br />
+          <input type="checkbox" onchange={excel_cb} checked={self.excel} />
+          { tr("excel_compatible") }
+          <br />
+          {
+            Self::download(
+              &self.better_txt,
+              "vendas.csv",
+              "download_better_csv"
+            )
+          }
+          {
+            Self::download(
+              &self.anon_txt,
+              "vendas-anonimizado.csv",
+              "download_anon_csv"
+            )
+          }
+        </div>
+        <hr />
+        <div class="seller-csv">
+          {
+            Self::download(
+              &self.seller_txt,
+              "vendedores.csv",
+              "download_seller_csv"
+            )
+          }
+        </div>
+      </>
+    };
+  }
+
+  /// The problems tab: the ambiguous and unsolvable sales only.
+  fn view_problems(&self) -> Html {
+    return html! {
+      <SortableTable
+        header={ SalePlus::better_csv_header() }
+        kinds={ SalePlus::better_csv_kinds() }
+        rows={ self.problems.clone() }
+        empty={ tr("no_problems") }
+      />
+    };
+  }
+
+  /// The narrowed tab: the sales a solver got down to fewer candidates, but
+  /// not to one.
+  fn view_narrowed(&self) -> Html {
+    return html! {
+      <SortableTable
+        header={ SalePlus::better_csv_header() }
+        kinds={ SalePlus::better_csv_kinds() }
+        rows={ self.narrowed.clone() }
+        empty={ tr("no_narrowed") }
+      />
+    };
+  }
+
+  /// The zero-value tab: courtesies and tests, which aren't problems.
+  fn view_free(&self) -> Html {
+    return html! {
+      <SortableTable
+        header={ SalePlus::better_csv_header() }
+        kinds={ SalePlus::better_csv_kinds() }
+        rows={ self.free.clone() }
+        empty={ tr("no_free") }
+      />
+    };
+  }
+
+  /// The no contact tab: the sales with no buyer e-mail nor username, and a
+  /// download with just those.
+  fn view_no_contact(&self) -> Html {
+    return html! {
+      <>
+        <SortableTable
+          header={ SalePlus::better_csv_header() }
+          kinds={ SalePlus::better_csv_kinds() }
+          rows={ self.no_contact.clone() }
+          empty={ tr("no_no_contact") }
+        />
+        <br />
+        {
+          Self::download(
+            &self.no_contact_txt,
+            "vendas-sem-contato.csv",
+            "download_no_contact_csv"
+          )
+        }
+      </>
+    };
+  }
+}
+
+impl Component for ReportDisplay {
+  type Message = ReportMsg;
+  type Properties = ReportProps;
+
+  fn create(ctx: &yew::Context<Self>) -> Self {
+    let report = &ctx.props().report;
+    let mut s = Self {
+      tab: ReportTab::Summary,
+      query: String::new(),
+      rows: Self::table_rows(report, ""),
+      problems: Self::problem_rows(report),
+      no_contact: Self::picked_rows(report, &report.no_contact),
+      narrowed: Self::picked_rows(report, &report.narrowed),
+      free: Self::picked_rows(report, &report.free),
+      excel: false,
+      better_txt: Ok(String::new()),
+      seller_txt: Ok(String::new()),
+      anon_txt: Ok(String::new()),
+      no_contact_txt: Ok(String::new())
+    };
+    s.make_csv_txts(report);
+    return s;
+  }
+
+  fn changed(&mut self, ctx: &yew::Context<Self>) -> bool {
+    let report = &ctx.props().report;
+    self.rows = Self::table_rows(report, &self.query);
+    self.problems = Self::problem_rows(report);
+    self.no_contact = Self::picked_rows(report, &report.no_contact);
+    self.narrowed = Self::picked_rows(report, &report.narrowed);
+    self.free = Self::picked_rows(report, &report.free);
+    self.make_csv_txts(report);
+    return true;
+  }
+
+  fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      ReportMsg::SearchChanged(q) => {
+        self.rows = Self::table_rows(&ctx.props().report, &q);
+        self.query = q;
+      },
+      ReportMsg::TabPicked(t) => {
+        self.tab = t;
+      },
+      ReportMsg::ExcelToggled(x) => {
+        self.excel = x;
+        self.make_csv_txts(&ctx.props().report);
+      },
+    }
+    return true;
+  }
+
+  fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+    let report = &ctx.props().report;
+    return html! {
+      <div class="report">
+        {
+          match &report.filter {
+            Some(f) => html! {
+              <p class="filter-warning" style="color: red;">
+                <b>{ tr("filtered_report") }</b>
+                { f }
+              </p>
+            },
+            None => html! {}
+          }
+        }
+        {
+          for [
+            &self.better_txt,
+            &self.seller_txt,
+            &self.anon_txt,
+            &self.no_contact_txt
+          ].into_iter()
+            .filter_map(|t| t.as_ref().err())
+            .map(|e| {
+              html_nested! {
+                <p class="csv-error" style="color: red;"><b>{ e }</b></p>
+              }
+            })
+        }
+        {
+          // a filter can leave nothing behind, and then it's all zeroes
+          match report.better_csv.is_empty() {
+            true => html! {
+              <p class="filter-warning"><b>{ tr("no_sales_found") }</b></p>
+            },
+            false => html! {}
+          }
+        }
+        <hr />
+        <div class="report-tabs">
+          {
+            for ReportTab::available().map(|t| {
+              let cb = ctx.link().callback(move |_e: MouseEvent| {
+                return ReportMsg::TabPicked(t);
+              });
+              html_nested! {
+                <button onclick={cb} disabled={t == self.tab}>
+                  {
+                    match t {
+                      ReportTab::Problems => {
+                        format!("{} ({})", t, self.problems.len())
+                      },
+                      ReportTab::NoContact => {
+                        format!("{} ({})", t, self.no_contact.len())
+                      },
+                      ReportTab::Narrowed => {
+                        format!("{} ({})", t, self.narrowed.len())
+                      },
+                      ReportTab::Free => {
+                        format!("{} ({})", t, self.free.len())
+                      },
+                      _ => t.to_string()
+                    }
+                  }
+                </button>
+              }
+            })
+          }
         </div>
+        <hr />
+        {
+          // only the active tab is rendered, the CSV one is heavy
+          match self.tab {
+            ReportTab::Summary => Self::view_summary(report),
+            ReportTab::Tables => Self::view_tables(report),
+            ReportTab::Csv => self.view_csv(ctx),
+            ReportTab::Problems => self.view_problems(),
+            ReportTab::NoContact => self.view_no_contact(),
+            ReportTab::Narrowed => self.view_narrowed(),
+            ReportTab::Free => self.view_free(),
+          }
+        }
       </div>
     }
   }
@@ -0,0 +1,202 @@
+//! A paginated, sortable table for big lists of rows, like the better CSV.
+
+use std::rc::Rc;
+use web_sys::MouseEvent;
+use yew::{Component, Properties, html, html_nested};
+use d4csv_core::i18n::{tr, trf};
+use d4csv_core::report::table::{ColumnKind, RowStatus};
+
+/// How many rows a page shows.
+pub(crate) static PAGE_ROWS: usize = 100;
+
+/// A row: its status, what to show when hovering it, and its cells.
+pub(crate) type TableRow = (RowStatus, Option<String>, Vec<String>);
+
+/// Properties for the table: a header, how each column sorts, and the rows
+/// with their statuses.
+#[derive(Clone, Debug, Properties)]
+pub(crate) struct TableProps {
+  pub(crate) header: Vec<String>,
+  pub(crate) kinds: Vec<ColumnKind>,
+  pub(crate) rows: Rc<Vec<TableRow>>,
+  /// Shown in a row of its own when there are no rows.
+  #[prop_or_default]
+  pub(crate) empty: String
+}
+
+impl PartialEq for TableProps {
+  fn eq(&self, other: &Self) -> bool {
+    return self.header == other.header
+      && self.kinds == other.kinds
+      && Rc::ptr_eq(&self.rows, &other.rows)
+      && self.empty == other.empty;
+  }
+}
+
+/// The events the table reacts to.
+#[derive(Clone, Debug)]
+pub(crate) enum TableMsg {
+  /// Go to a page (zero-based).
+  Page(usize),
+  /// Sort by a column, or flip the order if already sorted by it.
+  SortBy(usize)
+}
+
+/// The table component.
+pub(crate) struct SortableTable {
+  /// Current page, zero-based.
+  page: usize,
+  /// Column sorted by, and whether it's ascending.
+  sort: Option<(usize, bool)>,
+  /// Row indices, in display order.
+  order: Vec<usize>
+}
+
+impl SortableTable {
+  /// Recomputes the display order from the current sort.
+  fn reorder(&mut self, props: &TableProps) {
+    self.order = (0..props.rows.len()).collect();
+    if let Some((col, asc)) = self.sort {
+      let kind = props.kinds.get(col).copied().unwrap_or(ColumnKind::Text);
+      let cell = |i: usize| {
+        return props.rows[i].2.get(col).map(|s| s.as_str()).unwrap_or("");
+      };
+      self.order.sort_by(|a, b| {
+        let o = kind.cmp(cell(*a), cell(*b));
+        return if asc { o } else { o.reverse() };
+      });
+    }
+  }
+
+  /// How many pages there are (at least one, even if empty).
+  fn pages(&self) -> usize {
+    return self.order.len().div_ceil(PAGE_ROWS).max(1);
+  }
+}
+
+impl Component for SortableTable {
+  type Message = TableMsg;
+  type Properties = TableProps;
+
+  fn create(ctx: &yew::Context<Self>) -> Self {
+    let mut s = Self { page: 0, sort: None, order: Vec::new() };
+    s.reorder(ctx.props());
+    return s;
+  }
+
+  fn changed(&mut self, ctx: &yew::Context<Self>) -> bool {
+    self.page = 0;
+    self.reorder(ctx.props());
+    return true;
+  }
+
+  fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      TableMsg::Page(p) => {
+        self.page = p.min(self.pages() - 1);
+      },
+      TableMsg::SortBy(col) => {
+        self.sort = match self.sort {
+          Some((c, asc)) if c == col => Some((col, !asc)),
+          _ => Some((col, true)),
+        };
+        self.page = 0;
+        self.reorder(ctx.props());
+      },
+    }
+    return true;
+  }
+
+  fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+    let props = ctx.props();
+    let total = self.order.len();
+    let first = self.page * PAGE_ROWS;
+    let last = (first + PAGE_ROWS).min(total);
+    let go = |p: usize| {
+      return ctx.link().callback(move |_e: MouseEvent| TableMsg::Page(p));
+    };
+    let arrow = |col: usize| {
+      return match self.sort {
+        Some((c, true)) if c == col => " ▲",
+        Some((c, false)) if c == col => " ▼",
+        _ => "",
+      };
+    };
+    return html! {
+      <div class="sortable-table">
+        <table>
+          <tr>
+            {
+              for props.header.iter().enumerate().map(|(i, h)| {
+                let sort = ctx.link().callback(move |_e: MouseEvent| {
+                  return TableMsg::SortBy(i);
+                });
+                html_nested! {
+                  <th onclick={sort} style="cursor: pointer;">
+                    { h }{ arrow(i) }
+                  </th>
+                }
+              })
+            }
+          </tr>
+          {
+            match total {
+              0 => html! {
+                <tr>
+                  <td colspan={ props.header.len().to_string() }>
+                    <i>{ &props.empty }</i>
+                  </td>
+                </tr>
+              },
+              _ => html! {}
+            }
+          }
+          {
+            for self.order[first..last].iter().map(|i| {
+              let (status, hover, cells) = &props.rows[*i];
+              html_nested! {
+                <tr class={ status.class() } title={ hover.clone() }>
+                  {
+                    for cells.iter().map(|c| {
+                      html_nested! { <td>{ c }</td> }
+                    })
+                  }
+                </tr>
+              }
+            })
+          }
+        </table>
+        <br />
+        <button onclick={go(0)} disabled={self.page == 0}>{ "«" }</button>
+        <button
+          onclick={go(self.page.saturating_sub(1))}
+          disabled={self.page == 0}
+        >
+          { tr("previous") }
+        </button>
+        { trf("page_of", &[&(self.page + 1), &self.pages()]) }
+        <button
+          onclick={go(self.page + 1)}
+          disabled={self.page + 1 >= self.pages()}
+        >
+          { tr("next") }
+        </button>
+        <button
+          onclick={go(self.pages() - 1)}
+          disabled={self.page + 1 >= self.pages()}
+        >
+          { "»" }
+        </button>
+        <br />
+        <i>
+          {
+            match total {
+              0 => tr("no_rows").to_owned(),
+              _ => trf("rows_range", &[&(first + 1), &last, &total])
+            }
+          }
+        </i>
+      </div>
+    };
+  }
+}
@@ -1,5 +0,0 @@
-//! Ticket abstractions and such.
-
-pub(crate) mod batchnum;
-pub(crate) mod batch;
-
@@ -1,35 +1,88 @@
 //! A nice lil' wrapper for the app so I don't have to repeat HTML everywhere.
 
-use yew::{Component, html};
+use gloo_storage::{LocalStorage, Storage};
+use web_sys::MouseEvent;
+use yew::{Component, ContextProvider, html};
+use d4csv_core::build_info;
+use d4csv_core::i18n::{Lang, tr};
 use crate::app::App;
 
-pub(crate) struct Wrapper;
+/// Where the language choice is kept in localStorage.
+static STORAGE_KEY: &str = "d4csv.lang";
+
+/// Loads the language choice from localStorage, if there's one.
+fn load_lang() -> Lang {
+  return LocalStorage::get::<Lang>(STORAGE_KEY).unwrap_or_default();
+}
+
+/// Saves the language choice.
+fn store_lang(lang: Lang) {
+  if let Err(e) = LocalStorage::set(STORAGE_KEY, lang) {
+    log::warn!("couldn't store language: {}", e);
+  }
+}
+
+pub(crate) struct Wrapper {
+  /// The language everything below is in.
+  lang: Lang
+}
+
+pub(crate) enum WrapperMsg {
+  /// Switch to the other language.
+  ToggleLang
+}
 
 impl Component for Wrapper {
-    type Message = ();
+    type Message = WrapperMsg;
     type Properties = ();
 
     fn create(_ctx: &yew::Context<Self>) -> Self {
-      return Self;
+      let lang = load_lang();
+      lang.set_current();
+      return Self { lang };
+    }
+
+    fn update(
+      &mut self, _ctx: &yew::Context<Self>, msg: Self::Message
+    ) -> bool {
+      match msg {
+        WrapperMsg::ToggleLang => {
+          self.lang = self.lang.other();
+          self.lang.set_current();
+          store_lang(self.lang);
+        },
+      }
+      return true;
     }
 
-    fn view(&self, _ctx: &yew::Context<Self>) -> yew::Html {
+    fn view(&self, ctx: &yew::Context<Self>) -> yew::Html {
+      let lang_cb = ctx.link().callback(|_e: MouseEvent| {
+        return WrapperMsg::ToggleLang;
+      });
       return html! {
-        <div class="wrapper">
-          <center>
-            <h1>{ "parser do csv da D4" }</h1>
-            { "eu preciso dormir, cara. " }
+        <ContextProvider<Lang> context={self.lang}>
+          <div class="wrapper">
+            <center>
+              <h1>{ tr("title") }</h1>
+              { tr("tagline") }
+              <br />
+              <b>
+                <a href="https://github.com/Bruno02468/d4csv" target="_blank">
+                  { tr("open_source") }
+                </a>
+              </b>
+              <br />
+              <button onclick={lang_cb}>{ self.lang.other() }</button>
+            </center>
+            <br />
+            <br />
+            <App />
             <br />
-            <b>
-              <a href="https://github.com/Bruno02468/d4csv" target="_blank">
-                { "ah, e o código é aberto." }
-              </a>
-            </b>
-          </center>
-          <br />
-          <br />
-          <App />
-        </div>
+            <center class="build-info">
+              <small>{ build_info::describe() }</small>
+            </center>
+          </div>
+        </ContextProvider<Lang>>
       };
     }
 }
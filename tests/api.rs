@@ -0,0 +1,65 @@
+//! process_csv end to end, the way a script would call it. These only build
+//! for wasm; run them with `wasm-pack test --node -- --features api`.
+
+#![cfg(all(target_arch = "wasm32", feature = "api"))]
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+use d4csv::api::process_csv;
+use d4csv_core::SalesContext;
+
+/// Five sales: a promo and a 1st batch ticket online, a promo + 1st batch
+/// combo online, and two 2nd batch tickets from a point of sale.
+static CSV: &str = concat!(
+  "Data Compra,Email Usuario,Nome Usuario,Valor,Status,Vendedor,",
+  "ID Vendedor,Email Vendedor,Token,ID,Nome Cartao,Prim Digitos Cartao,",
+  "Ult Digitos Cartao\n",
+  "2022-10-01T12:00:00Z,a@d4.com,a,60.5,Paga Online,N/A,N/A,N/A,",
+  "tk1,id1,N/A,N/A,N/A\n",
+  "2022-10-01T12:05:00Z,b@d4.com,b,71.5,Paga Online,N/A,N/A,N/A,",
+  "tk2,id2,N/A,N/A,N/A\n",
+  "2022-10-01T12:10:00Z,c@d4.com,c,132,Paga Online,N/A,N/A,N/A,",
+  "tk3,id3,N/A,N/A,N/A\n",
+  "2022-10-01T12:15:00Z,d@d4.com,d,75,Paga Offline,Fulano,v1,f@d4.com,",
+  "tk4,id4,N/A,N/A,N/A\n",
+  "2022-10-01T12:20:00Z,e@d4.com,e,75,Paga Offline,Fulano,v1,f@d4.com,",
+  "tk5,id5,N/A,N/A,N/A\n"
+);
+
+/// Calls process_csv with the default context and reads the JSON back.
+fn process(solver: &str) -> serde_json::Value {
+  let ctx = SalesContext::default().to_json();
+  let out = process_csv(CSV, &ctx, solver).unwrap();
+  return serde_json::from_str(&out.as_string().unwrap()).unwrap();
+}
+
+#[wasm_bindgen_test]
+fn every_sale_comes_back_resolved() {
+  let out = process("");
+  let rows = out["report"]["better_csv"].as_array().unwrap();
+  assert_eq!(rows.len(), 5);
+  assert!(out["skipped"].as_array().unwrap().is_empty());
+  let header: Vec<&str> = out["better_csv_header"].as_array().unwrap()
+    .iter()
+    .map(|h| h.as_str().unwrap())
+    .collect();
+  let tokens: Vec<&str> = rows.iter()
+    .map(|r| r[8].as_str().unwrap())
+    .collect();
+  assert_eq!(tokens, vec!["tk1", "tk2", "tk3", "tk4", "tk5"]);
+  assert_eq!(rows[0].as_array().unwrap().len(), header.len());
+}
+
+#[wasm_bindgen_test]
+fn a_solver_can_be_picked() {
+  let out = process("temporal");
+  assert_eq!(out["report"]["better_csv"].as_array().unwrap().len(), 5);
+}
+
+#[wasm_bindgen_test]
+fn bad_input_is_an_error() {
+  let ctx = SalesContext::default().to_json();
+  assert!(process_csv(CSV, &ctx, "nope").is_err());
+  assert!(process_csv(CSV, "{}", "").is_err());
+}